@@ -1,17 +1,35 @@
-use anyhow::{anyhow, Result};
-use dicom_core::value::DicomDateTime;
+use chrono::Datelike;
 use dicom_core::{DataElement, DicomValue, PrimitiveValue, Tag, VR};
 use dicom_dictionary_std::tags;
-use dicom_object::{open_file, DefaultDicomObject, InMemDicomObject};
+use dicom_object::{DefaultDicomObject, InMemDicomObject};
+use std::fs;
+use std::mem;
+use std::str::FromStr;
+use std::sync::Arc;
+use tracing::debug;
 
-use crate::file::AnonymizerFile;
+use crate::charset;
+use crate::error::{Error, Result};
+use crate::file::{self, AnonymizerFile};
+use crate::hook::{self, Decision, ElementHook, Hooks, Planned};
+use crate::inspect::{diff, elements, ElementChange, ElementInfo};
 use crate::meta::{AnonymizerMeta, AnonymizerMetaBuilder};
-use crate::TagAction;
+use crate::pseudonyms::Pseudonyms;
+use crate::script::Scripts;
+use crate::scrub::Scrubber;
+use crate::transfer_syntax;
+use crate::types::{Age, AgeUnit, DaValue, FilterRule, PartialDate, Temporal};
+use crate::{
+    vr_rules, BirthDateGeneralization, FilterAction, PatientAgeAction, PatientSex, TagAction,
+    ValueStrictness,
+};
 
 #[derive(Debug, Clone)]
 pub struct Anonymizer {
     file: Option<AnonymizerFile>,
     meta: AnonymizerMeta,
+    warnings: Vec<String>,
+    hooks: Hooks,
 }
 
 // Constructors
@@ -19,21 +37,35 @@ impl Anonymizer {
     pub fn new() -> Result<Self> {
         Ok(Self {
             file: Option::None,
-            meta: AnonymizerMetaBuilder::default().build()?,
+            meta: AnonymizerMetaBuilder::default()
+                .build()
+                .map_err(|e| Error::InvalidConfig(e.to_string()))?,
+            warnings: Vec::new(),
+            hooks: Hooks::default(),
         })
     }
 
     pub fn from_file(path: &str) -> Result<Self> {
         let mut back = Self::new()?;
 
+        let data = fs::read(path).map_err(|source| Error::Io {
+            message: format!("Could not read {}", path),
+            source,
+        })?;
         back.file = Option::from(AnonymizerFile {
-            obj: open_file(path)?,
+            obj: file::read(&data)?,
             updated_obj: false,
         });
 
         Ok(back)
     }
 
+    /// Reads a DICOM file from memory, with or without the 128 byte preamble, `DICM` or the file
+    /// meta group, see `FileLayout`.
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        Self::from_object(file::read(data)?)
+    }
+
     pub fn from_object(object: DefaultDicomObject) -> Result<Self> {
         let mut back = Self::new()?;
 
@@ -52,45 +84,310 @@ impl Anonymizer {
     }
 
     pub fn meta(&mut self, meta: AnonymizerMeta) {
-        self.meta = meta;
+        self.meta = meta.with_preset();
+    }
+
+    /// Lets `hook` decide about every element in `anonymize`, after the hooks added before.
+    pub fn add_hook(&mut self, hook: impl ElementHook + 'static) {
+        self.hooks.push(Arc::new(hook));
+    }
+
+    /// What a batch run should do with the loaded file according to the filters of the meta.
+    pub fn filter_action(&self) -> Result<FilterAction> {
+        match &self.file {
+            Some(file) => Ok(FilterRule::decide(&self.meta.filters, &file.obj)),
+            None => Err(Error::MissingObject),
+        }
     }
 
     pub fn save(&mut self, path: &str) -> Result<()> {
+        if self.file.as_ref().ok_or(Error::MissingObject)?.updated_obj {
+            self.anonymize()?;
+        }
+        fs::write(path, self.to_bytes()?).map_err(|source| Error::Io {
+            message: format!("Could not write {}", path),
+            source,
+        })
+    }
+
+    /// The UID of the transfer syntax the file is written with, after `anonymize` the one of the
+    /// meta.
+    pub fn transfer_syntax(&self) -> Result<&str> {
+        let file = self.file.as_ref().ok_or(Error::MissingObject)?;
+        Ok(file
+            .obj
+            .meta()
+            .transfer_syntax
+            .trim_end_matches(['\0', ' ']))
+    }
+
+    /// Problems with values of the file found by the last `anonymize`, e.g. non-standard values.
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    /// The whole file, including preamble and file meta group, e.g. to hand it back to a browser.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
         match &self.file {
             Some(file) => {
-                if file.updated_obj {
-                    self.anonymize()?;
-                }
-                self.file.as_ref().unwrap().obj.write_to_file(path)?;
+                let mut data = Vec::new();
+                transfer_syntax::write_all(&file.obj, &mut data)?;
+                Ok(data)
+            }
+            None => Err(Error::MissingObject),
+        }
+    }
+
+    /// Replaces instance UIDs and the patient ID, consistently for every file that shares
+    /// `pseudonyms`.
+    pub fn pseudonymize(&mut self, pseudonyms: &mut Pseudonyms) -> Result<()> {
+        match &mut self.file {
+            Some(file) => {
+                pseudonyms.apply(&mut file.obj)?;
+                debug!("Replaced the instance UIDs and the patient ID");
                 Ok(())
             }
-            None => Err(anyhow!("Need to have a initialised DICOM object")),
+            None => Err(Error::MissingObject),
         }
     }
 
+    /// Every element of the loaded dataset, including the ones inside sequences.
+    pub fn elements(&self) -> Result<Vec<ElementInfo>> {
+        match &self.file {
+            Some(file) => Ok(elements(&file.obj)),
+            None => Err(Error::MissingObject),
+        }
+    }
+
+    /// The changes `anonymize` would make with the current meta, without touching the file.
+    pub fn preview(&self) -> Result<Vec<ElementChange>> {
+        let before = self.elements()?;
+
+        let mut anonymized = self.clone();
+        anonymized.anonymize()?;
+
+        Ok(diff(&before, &anonymized.elements()?))
+    }
+
+    /// Applies `item` to `tag`, a changed value gets the VR of the dictionary and has to follow
+    /// its rules.
     fn match_value<T>(
         &mut self,
         item: &TagAction<T>,
         tag: Tag,
-        vr: VR,
         translate: fn(value: &T) -> PrimitiveValue,
     ) -> Result<()> {
         match item {
             TagAction::Change(value) => {
+                let (name, vr) = vr_rules::attribute(tag).map_err(config_error)?;
+                let value = self.conform(name, vr, translate(value))?;
                 self.file
                     .as_mut()
-                    .unwrap()
+                    .ok_or(Error::MissingObject)?
                     .obj
                     .put(DataElement::<InMemDicomObject, Vec<u8>>::new(
                         tag,
                         vr,
-                        DicomValue::from(translate(value)),
-                    ))
-                    .unwrap();
+                        DicomValue::from(value),
+                    ));
+                debug!(attribute = name, %tag, "Changed");
             }
             TagAction::Keep => {}
             TagAction::Remove => {
-                todo!("Implement logic to delete a tag");
+                let obj = &mut self.file.as_mut().ok_or(Error::MissingObject)?.obj;
+                if obj.remove_element(tag) {
+                    debug!(%tag, "Removed");
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// `value` if it follows the rules of `vr`. Otherwise an error or, with
+    /// `ValueStrictness::Coerce`, the fixed value and a warning.
+    fn conform(&mut self, name: &str, vr: VR, value: PrimitiveValue) -> Result<PrimitiveValue> {
+        let text = value.to_str().to_string();
+        let problem = match vr_rules::validate(vr, &text) {
+            Ok(()) => return Ok(value),
+            Err(problem) => problem,
+        };
+
+        let violation = |problem: anyhow::Error| Error::VrViolation {
+            name: name.to_string(),
+            value: text.clone(),
+            vr,
+            problem: format!("{:#}", problem),
+        };
+        match self.meta.value_strictness {
+            ValueStrictness::Reject => Err(violation(problem)),
+            ValueStrictness::Coerce => {
+                let coerced = vr_rules::coerce(vr, &text).map_err(violation)?;
+                self.warnings.push(format!(
+                    "The new {} was written as `{}`: {}",
+                    name, coerced, problem
+                ));
+                Ok(PrimitiveValue::from(coerced))
+            }
+        }
+    }
+
+    /// The configured action, or the replacement of the mapping table if the value is kept.
+    /// Warns about values the standard doesn't define.
+    fn patient_sex_action(&mut self) -> Result<TagAction<PatientSex>> {
+        let obj = &self.file.as_ref().ok_or(Error::MissingObject)?.obj;
+        let value = match obj.element_opt(tags::PATIENT_SEX)? {
+            Some(element) => element.to_str()?.to_string(),
+            None => return Ok(self.meta.patient_sex.clone()),
+        };
+
+        if !PatientSex::is_standard(&value) {
+            self.warnings.push(format!(
+                "Patient Sex `{}` isn't one of the values M, F, O or empty",
+                value.trim_end()
+            ));
+        }
+
+        Ok(match &self.meta.patient_sex {
+            TagAction::Keep => match self.meta.patient_sex_map.get(&value) {
+                Some(sex) => TagAction::Change(sex),
+                None => TagAction::Keep,
+            },
+            action => action.clone(),
+        })
+    }
+
+    /// The birth date action and the Patient Age to write, both derived from the original values
+    /// of the file so a generalized birth date still gives the right age.
+    fn birth_date_and_age(&mut self) -> Result<(TagAction<DaValue>, Option<Age>)> {
+        let meta = &self.meta;
+        if meta.generalize_birth_date.is_full()
+            && meta.patient_age.is_keep()
+            && meta.max_patient_age.is_none()
+        {
+            return Ok((meta.patient_birth_date.clone(), None));
+        }
+
+        let obj = &self.file.as_ref().ok_or(Error::MissingObject)?.obj;
+        let text = |tag| -> Result<Option<String>> {
+            Ok(match obj.element_opt(tag)? {
+                Some(element) => Some(element.to_str()?.trim().to_string()),
+                None => None,
+            }
+            .filter(|value| !value.is_empty()))
+        };
+
+        let birth = match text(tags::PATIENT_BIRTH_DATE)? {
+            Some(value) => match DaValue::from_str(&value) {
+                Ok(Temporal::Single(date)) => Some(date),
+                _ => {
+                    self.warnings.push(format!(
+                        "Patient Birth Date `{}` isn't a DICOM date like 19800101",
+                        value
+                    ));
+                    None
+                }
+            },
+            None => None,
+        };
+        let stored = match text(tags::PATIENT_AGE)? {
+            Some(value) => match Age::from_str(&value) {
+                Ok(age) => Some(age),
+                Err(_) => {
+                    self.warnings.push(format!(
+                        "Patient Age `{}` isn't a DICOM age like 045Y",
+                        value
+                    ));
+                    None
+                }
+            },
+            None => None,
+        };
+        // The first date of the imaging the age refers to
+        let reference = [
+            tags::STUDY_DATE,
+            tags::SERIES_DATE,
+            tags::ACQUISITION_DATE,
+            tags::CONTENT_DATE,
+        ]
+        .into_iter()
+        .filter_map(|tag| text(tag).ok().flatten())
+        .find_map(|value| PartialDate::from_str(&value).ok()?.to_naive_date());
+        let computed = match (birth.and_then(|date| date.to_naive_date()), reference) {
+            (Some(birth), Some(reference)) => Age::between(birth, reference),
+            _ => None,
+        };
+
+        let mut age = match meta.patient_age {
+            PatientAgeAction::Keep => stored,
+            PatientAgeAction::Recompute => computed.or(stored),
+        };
+        let max = meta
+            .max_patient_age
+            .filter(|max| computed.or(stored).is_some_and(|age| age.years() > *max));
+        if let Some(max) = max {
+            age = age
+                .map(|_| Age::new(max + 1, AgeUnit::Years))
+                .transpose()
+                .map_err(config_error)?;
+        }
+
+        let generalization = meta.generalize_birth_date;
+        let birth_date = match (&meta.patient_birth_date, birth, max, reference) {
+            // The year that makes the patient `max + 1` years old at the reference date
+            (TagAction::Keep, Some(_), Some(max), Some(reference)) => {
+                let year = reference.year() - max as i32 - 1;
+                let date =
+                    PartialDate::from_ymd(year.max(0) as u16, None, None).map_err(config_error)?;
+                TagAction::Change(Temporal::Single(match generalization {
+                    BirthDateGeneralization::Year => date,
+                    _ => date.january_first(),
+                }))
+            }
+            (TagAction::Keep, Some(_), Some(_), None) => TagAction::Remove,
+            (TagAction::Keep, Some(date), None, _) if !generalization.is_full() => {
+                TagAction::Change(Temporal::Single(generalization.apply(date)))
+            }
+            (action, ..) => action.clone(),
+        };
+
+        Ok((birth_date, age.filter(|age| Some(*age) != stored)))
+    }
+
+    /// Applies the decisions of the hooks over the built-in rules. Changed values have to follow
+    /// the rules of the VR of the element, like the ones of the meta.
+    fn apply_hooks(&mut self, plan: Vec<Planned>) -> Result<()> {
+        for planned in plan {
+            let Planned {
+                steps,
+                path,
+                element,
+                decision,
+            } = planned;
+            let (tag, vr) = (element.header().tag, element.vr());
+
+            let (element, message) = match decision {
+                Decision::Pass => continue,
+                Decision::Keep => (Some(element), "Kept by a hook"),
+                Decision::Remove => (None, "Removed by a hook"),
+                Decision::Change(value) => {
+                    let value = self.conform(&path, vr, PrimitiveValue::from(value))?;
+                    let element = DataElement::new(tag, vr, DicomValue::from(value));
+                    (Some(element), "Changed by a hook")
+                }
+            };
+
+            let obj = &mut self.file.as_mut().ok_or(Error::MissingObject)?.obj;
+            let found = hook::edit(obj, &steps, |dataset| match element {
+                Some(element) => {
+                    dataset.put(element);
+                }
+                None => {
+                    dataset.remove_element(tag);
+                }
+            });
+            if found {
+                debug!(%path, "{}", message);
             }
         }
 
@@ -98,40 +395,661 @@ impl Anonymizer {
     }
 
     pub fn anonymize(&mut self) -> Result<()> {
-        println!("{:?}", self.meta);
+        self.warnings.clear();
+
+        // Before any other change, values of ISO 2022 character sets are decoded and the identity
+        // is taken from the original values
+        let obj = &mut self.file.as_mut().ok_or(Error::MissingObject)?.obj;
+        let dataset = charset::decode(mem::replace(&mut **obj, InMemDicomObject::new_empty()));
+        let mut hooks = self.hooks.clone();
+        if let Some(scripts) = Scripts::load(&self.meta.scripts, self.meta.pseudonym_salt())? {
+            hooks.push(Arc::new(scripts));
+        }
+        let plan = hooks.plan(&dataset);
+        **obj = match Scrubber::new(&dataset, self.meta.scrub_text, &self.meta.scrub_patterns) {
+            Some(scrubber) => {
+                debug!("Scrubbing identifiers from free text");
+                scrubber.dataset(dataset)
+            }
+            None => dataset,
+        };
 
         self.match_value(
             &self.meta.patient_name.clone(),
             tags::PATIENT_NAME,
-            VR::PN,
             |value| PrimitiveValue::Str(value.to_owned()),
         )?;
 
-        self.match_value(
-            &self.meta.patient_birth_date.clone(),
-            tags::PATIENT_BIRTH_DATE,
-            VR::DA,
-            |value| {
-                let ddt = DicomDateTime::from(value.clone());
-                PrimitiveValue::from(ddt)
-            },
-        )?;
+        let (birth_date, age) = self.birth_date_and_age()?;
+        self.match_value(&birth_date, tags::PATIENT_BIRTH_DATE, |value| {
+            PrimitiveValue::Str(value.to_string())
+        })?;
+        self.match_value(&TagAction::from(age), tags::PATIENT_AGE, |value| {
+            PrimitiveValue::Str(value.to_string())
+        })?;
 
+        let obj = &mut self.file.as_mut().ok_or(Error::MissingObject)?.obj;
         for item in &self.meta.remove_tags {
-            self.file
-                .as_mut()
-                .unwrap()
-                .obj
-                .remove_element(Tag::from(item.clone()));
+            let tag = Tag::from(item.clone());
+            if obj.remove_element(tag) {
+                debug!(%tag, "Removed");
+            }
         }
 
-        self.match_value(
-            &self.meta.patient_sex.clone(),
-            tags::PATIENT_SEX,
-            VR::CS,
-            |value| PrimitiveValue::Str(value.value().to_owned()),
-        )?;
+        let patient_sex = self.patient_sex_action()?;
+        self.match_value(&patient_sex, tags::PATIENT_SEX, |value| {
+            PrimitiveValue::Str(value.value().to_owned())
+        })?;
+
+        self.apply_hooks(plan)?;
+
+        let obj = &mut self.file.as_mut().ok_or(Error::MissingObject)?.obj;
+        let dataset = mem::replace(&mut **obj, InMemDicomObject::new_empty());
+        **obj = charset::declare_utf8_if_needed(dataset);
+
+        if let Some(previous) = transfer_syntax::convert(obj, self.meta.transfer_syntax)? {
+            let to = obj.meta().transfer_syntax.trim_end_matches('\0');
+            debug!(from = %previous, to = %to, "Converted the transfer syntax");
+        }
 
         Ok(())
     }
 }
+
+/// Settings that can't be applied, like a maximum age beyond what Patient Age can hold.
+fn config_error(error: anyhow::Error) -> Error {
+    Error::InvalidConfig(format!("{:#}", error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Anonymizer;
+    use crate::inspect::ChangeKind;
+    use crate::{TagAction, ValueStrictness};
+    use dicom_core::{DataElement, PrimitiveValue, VR};
+    use dicom_dictionary_std::tags;
+    use dicom_object::{FileMetaTableBuilder, InMemDicomObject};
+
+    fn factory() -> Vec<u8> {
+        let mut obj = InMemDicomObject::new_empty();
+        obj.put(DataElement::new(
+            tags::PATIENT_NAME,
+            VR::PN,
+            PrimitiveValue::from("Doe^John"),
+        ));
+
+        let mut data = Vec::new();
+        obj.with_meta(
+            FileMetaTableBuilder::new()
+                .transfer_syntax("1.2.840.10008.1.2.1")
+                .media_storage_sop_class_uid("1.2.840.10008.5.1.4.1.1.2")
+                .media_storage_sop_instance_uid("1.2.3.4"),
+        )
+        .unwrap()
+        .write_all(&mut data)
+        .unwrap();
+        data
+    }
+
+    fn patient_name(data: &[u8]) -> String {
+        let any = Anonymizer::from_bytes(data).unwrap();
+        let obj = &any.file.as_ref().unwrap().obj;
+        let name = obj.element(tags::PATIENT_NAME).unwrap().to_str().unwrap();
+        name.trim_end().to_string()
+    }
+
+    #[test]
+    fn reads_bytes_with_and_without_preamble() {
+        let data = factory();
+
+        assert_eq!(patient_name(&data), "Doe^John");
+        assert_eq!(patient_name(&data[128..]), "Doe^John");
+    }
+
+    #[test]
+    fn writes_the_anonymized_file_to_bytes() {
+        let mut any = Anonymizer::from_bytes(&factory()).unwrap();
+        let mut builder = Anonymizer::meta_builder();
+        builder.patient_name(TagAction::Change("Anonymous".to_string()));
+        any.meta(builder.build().unwrap());
+        any.anonymize().unwrap();
+
+        let data = any.to_bytes().unwrap();
+
+        assert_eq!(&data[128..132], b"DICM");
+        assert_eq!(patient_name(&data), "Anonymous");
+    }
+
+    #[test]
+    fn previews_the_changes_without_applying_them() {
+        let mut any = Anonymizer::from_bytes(&factory()).unwrap();
+        let mut builder = Anonymizer::meta_builder();
+        builder.patient_name(TagAction::Remove);
+        any.meta(builder.build().unwrap());
+
+        let changes = any.preview().unwrap();
+
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].keyword, "PatientName");
+        assert_eq!(changes[0].kind, ChangeKind::Removed);
+        assert_eq!(any.elements().unwrap()[0].value, "Doe^John");
+    }
+
+    #[test]
+    fn to_bytes_needs_a_file() {
+        assert!(Anonymizer::new().unwrap().to_bytes().is_err());
+    }
+
+    #[test]
+    fn scrubs_free_text_with_the_original_name() {
+        let mut any = Anonymizer::from_bytes(&factory()).unwrap();
+        any.file.as_mut().unwrap().obj.put(DataElement::new(
+            tags::STUDY_DESCRIPTION,
+            VR::LO,
+            PrimitiveValue::from("Knee John Doe"),
+        ));
+        let mut builder = Anonymizer::meta_builder();
+        builder.patient_name(TagAction::Change("Anonymous".to_string()));
+        builder.scrub_text(true);
+        any.meta(builder.build().unwrap());
+        any.anonymize().unwrap();
+
+        let obj = &any.file.as_ref().unwrap().obj;
+        let description = obj.element(tags::STUDY_DESCRIPTION).unwrap();
+        assert_eq!(description.to_str().unwrap(), "Knee *** ***");
+    }
+
+    #[test]
+    fn checks_changed_values_against_their_vr() {
+        let name = format!("{}^John", "D".repeat(70));
+        let anonymize = |strictness| {
+            let mut any = Anonymizer::from_bytes(&factory()).unwrap();
+            let mut builder = Anonymizer::meta_builder();
+            builder.patient_name(TagAction::Change(name.clone()));
+            builder.value_strictness(strictness);
+            any.meta(builder.build().unwrap());
+            any.anonymize().map(|_| any)
+        };
+
+        let error = anonymize(ValueStrictness::Reject).unwrap_err();
+        assert!(format!("{:#}", error).contains("at most 64 characters per component group"));
+
+        let any = anonymize(ValueStrictness::Coerce).unwrap();
+        assert_eq!(patient_name(&any.to_bytes().unwrap()), "D".repeat(64));
+        assert_eq!(any.warnings().len(), 1);
+        assert!(any.warnings()[0].starts_with("The new PatientName was written as `DDD"));
+    }
+
+    mod character_sets {
+        use super::Anonymizer;
+        use crate::TagAction;
+        use dicom_core::{DataElement, PrimitiveValue, Tag, VR};
+        use dicom_dictionary_std::tags;
+        use dicom_object::{FileMetaTableBuilder, InMemDicomObject};
+
+        /// A file with the name and description in `charset`, written as raw bytes
+        fn factory(charset: &str, name: &[u8], description: &[u8]) -> Vec<u8> {
+            let latin1 = |bytes: &[u8]| bytes.iter().map(|b| *b as char).collect::<String>();
+            let mut obj = InMemDicomObject::new_empty();
+            obj.put(DataElement::new(
+                tags::SPECIFIC_CHARACTER_SET,
+                VR::CS,
+                PrimitiveValue::Strs(charset.split('\\').map(str::to_string).collect()),
+            ));
+            obj.put(DataElement::new(
+                tags::PATIENT_NAME,
+                VR::PN,
+                PrimitiveValue::from(latin1(name)),
+            ));
+            obj.put(DataElement::new(
+                tags::STUDY_DESCRIPTION,
+                VR::LO,
+                PrimitiveValue::from(latin1(description)),
+            ));
+
+            let mut data = Vec::new();
+            // Unknown sets and ISO_IR 100 are both written as Latin-1, so the bytes stay as they are
+            obj.with_meta(
+                FileMetaTableBuilder::new()
+                    .transfer_syntax("1.2.840.10008.1.2.1")
+                    .media_storage_sop_class_uid("1.2.840.10008.5.1.4.1.1.2")
+                    .media_storage_sop_instance_uid("1.2.3.4"),
+            )
+            .unwrap()
+            .write_all(&mut data)
+            .unwrap();
+            data
+        }
+
+        fn anonymize(data: &[u8], name: &str) -> Anonymizer {
+            let mut any = Anonymizer::from_bytes(data).unwrap();
+            let mut builder = Anonymizer::meta_builder();
+            builder.patient_name(TagAction::Change(name.to_string()));
+            any.meta(builder.build().unwrap());
+            any.anonymize().unwrap();
+
+            Anonymizer::from_bytes(&any.to_bytes().unwrap()).unwrap()
+        }
+
+        fn text(any: &Anonymizer, tag: Tag) -> String {
+            let obj = &any.file.as_ref().unwrap().obj;
+            obj.element(tag)
+                .unwrap()
+                .to_str()
+                .unwrap()
+                .trim_end()
+                .to_string()
+        }
+
+        #[test]
+        fn rewrites_iso_2022_files_in_utf8() {
+            let data = factory(
+                "\\ISO 2022 IR 87",
+                b"Yamada^Tarou=\x1b$B;3ED\x1b(B^\x1b$BB@O:\x1b(B",
+                b"\x1b$B;3ED\x1b(B CT",
+            );
+
+            let any = anonymize(&data, "Sato^Hanako=佐藤^花子");
+            assert_eq!(text(&any, tags::SPECIFIC_CHARACTER_SET), "ISO_IR 192");
+            assert_eq!(text(&any, tags::PATIENT_NAME), "Sato^Hanako=佐藤^花子");
+            assert_eq!(text(&any, tags::STUDY_DESCRIPTION), "山田 CT");
+        }
+
+        #[test]
+        fn keeps_the_set_while_the_values_fit() {
+            let data = factory("ISO_IR 100", b"M\xfcller^J\xf6rg", b"Kn\xe4ul");
+
+            let any = anonymize(&data, "Jäger");
+            assert_eq!(text(&any, tags::SPECIFIC_CHARACTER_SET), "ISO_IR 100");
+            assert_eq!(text(&any, tags::PATIENT_NAME), "Jäger");
+
+            let any = anonymize(&data, "山田^花子");
+            assert_eq!(text(&any, tags::SPECIFIC_CHARACTER_SET), "ISO_IR 192");
+            assert_eq!(text(&any, tags::PATIENT_NAME), "山田^花子");
+            assert_eq!(text(&any, tags::STUDY_DESCRIPTION), "Knäul");
+        }
+    }
+
+    mod patient_sex {
+        use super::{factory, Anonymizer};
+        use crate::types::SexMapping;
+        use crate::{PatientSex, TagAction};
+        use dicom_core::{DataElement, PrimitiveValue, VR};
+        use dicom_dictionary_std::tags;
+        use std::str::FromStr;
+
+        fn anonymize(sex: &str, action: TagAction<PatientSex>, map: &str) -> Anonymizer {
+            let mut any = Anonymizer::from_bytes(&factory()).unwrap();
+            any.file.as_mut().unwrap().obj.put(DataElement::new(
+                tags::PATIENT_SEX,
+                VR::CS,
+                PrimitiveValue::from(sex),
+            ));
+            let mut builder = Anonymizer::meta_builder();
+            builder.patient_sex(action);
+            builder.patient_sex_map(SexMapping::from_str(map).unwrap());
+            any.meta(builder.build().unwrap());
+            any.anonymize().unwrap();
+            any
+        }
+
+        fn sex(any: &Anonymizer) -> String {
+            let obj = &any.file.as_ref().unwrap().obj;
+            let value = obj.element(tags::PATIENT_SEX).unwrap().to_str().unwrap();
+            value.trim_end().to_string()
+        }
+
+        #[test]
+        fn maps_kept_values() {
+            assert_eq!(sex(&anonymize("F", TagAction::Keep, "*=O")), "O");
+            assert_eq!(sex(&anonymize("F", TagAction::Keep, "M=M,F=F,*=")), "F");
+            assert_eq!(sex(&anonymize("X", TagAction::Keep, "M=M,F=F,*=")), "");
+            assert_eq!(sex(&anonymize("X", TagAction::Keep, "M=M")), "X");
+        }
+
+        #[test]
+        fn changes_win_over_the_mapping() {
+            let any = anonymize("F", TagAction::Change(PatientSex::Empty), "*=O");
+
+            assert_eq!(sex(&any), "");
+            assert!(any.warnings().is_empty());
+        }
+
+        #[test]
+        fn adds_missing_values() {
+            let mut any = Anonymizer::from_bytes(&factory()).unwrap();
+            let mut builder = Anonymizer::meta_builder();
+            builder.patient_sex(TagAction::Change(PatientSex::Empty));
+            any.meta(builder.build().unwrap());
+            any.anonymize().unwrap();
+
+            assert_eq!(sex(&any), "");
+        }
+
+        #[test]
+        fn warns_about_non_standard_values() {
+            let any = anonymize("male", TagAction::Keep, "");
+
+            assert_eq!(
+                any.warnings(),
+                ["Patient Sex `male` isn't one of the values M, F, O or empty"]
+            );
+        }
+    }
+
+    mod patient_age {
+        use super::{factory, Anonymizer};
+        use crate::{BirthDateGeneralization, PatientAgeAction};
+        use dicom_core::{DataElement, PrimitiveValue, Tag, VR};
+        use dicom_dictionary_std::tags;
+
+        fn anonymize(
+            values: &[(Tag, VR, &str)],
+            generalization: BirthDateGeneralization,
+            age: PatientAgeAction,
+            max: Option<u16>,
+        ) -> Anonymizer {
+            let mut any = Anonymizer::from_bytes(&factory()).unwrap();
+            for (tag, vr, value) in values {
+                any.file.as_mut().unwrap().obj.put(DataElement::new(
+                    *tag,
+                    *vr,
+                    PrimitiveValue::from(*value),
+                ));
+            }
+            let mut builder = Anonymizer::meta_builder();
+            builder.generalize_birth_date(generalization);
+            builder.patient_age(age);
+            builder.max_patient_age(max);
+            any.meta(builder.build().unwrap());
+            any.anonymize().unwrap();
+            any
+        }
+
+        fn value(any: &Anonymizer, tag: Tag) -> Option<String> {
+            let obj = &any.file.as_ref().unwrap().obj;
+            obj.element_opt(tag)
+                .unwrap()
+                .map(|element| element.to_str().unwrap().trim_end().to_string())
+        }
+
+        const BIRTH: (Tag, VR, &str) = (tags::PATIENT_BIRTH_DATE, VR::DA, "19800612");
+        const STUDY: (Tag, VR, &str) = (tags::STUDY_DATE, VR::DA, "20200301");
+
+        #[test]
+        fn generalizes_the_birth_date() {
+            let any = anonymize(
+                &[BIRTH],
+                BirthDateGeneralization::Year,
+                PatientAgeAction::Keep,
+                None,
+            );
+            assert_eq!(value(&any, tags::PATIENT_BIRTH_DATE).unwrap(), "1980");
+
+            let any = anonymize(
+                &[BIRTH],
+                BirthDateGeneralization::JanuaryFirst,
+                PatientAgeAction::Keep,
+                None,
+            );
+            assert_eq!(value(&any, tags::PATIENT_BIRTH_DATE).unwrap(), "19800101");
+        }
+
+        #[test]
+        fn recomputes_the_age_from_the_original_birth_date() {
+            let any = anonymize(
+                &[BIRTH, STUDY, (tags::PATIENT_AGE, VR::AS, "041Y")],
+                BirthDateGeneralization::JanuaryFirst,
+                PatientAgeAction::Recompute,
+                None,
+            );
+
+            assert_eq!(value(&any, tags::PATIENT_BIRTH_DATE).unwrap(), "19800101");
+            assert_eq!(value(&any, tags::PATIENT_AGE).unwrap(), "039Y");
+        }
+
+        #[test]
+        fn keeps_the_age_without_a_study_date() {
+            let any = anonymize(
+                &[BIRTH, (tags::PATIENT_AGE, VR::AS, "041Y")],
+                BirthDateGeneralization::Full,
+                PatientAgeAction::Recompute,
+                None,
+            );
+
+            assert_eq!(value(&any, tags::PATIENT_AGE).unwrap(), "041Y");
+            assert!(any.warnings().is_empty());
+        }
+
+        #[test]
+        fn caps_high_ages() {
+            let any = anonymize(
+                &[
+                    (tags::PATIENT_BIRTH_DATE, VR::DA, "19200612"),
+                    STUDY,
+                    (tags::PATIENT_AGE, VR::AS, "099Y"),
+                ],
+                BirthDateGeneralization::Full,
+                PatientAgeAction::Keep,
+                Some(89),
+            );
+
+            assert_eq!(value(&any, tags::PATIENT_AGE).unwrap(), "090Y");
+            assert_eq!(value(&any, tags::PATIENT_BIRTH_DATE).unwrap(), "19300101");
+        }
+
+        #[test]
+        fn removes_capped_birth_dates_without_a_study_date() {
+            let any = anonymize(
+                &[
+                    (tags::PATIENT_BIRTH_DATE, VR::DA, "19200612"),
+                    (tags::PATIENT_AGE, VR::AS, "095Y"),
+                ],
+                BirthDateGeneralization::Year,
+                PatientAgeAction::Keep,
+                Some(89),
+            );
+
+            assert_eq!(value(&any, tags::PATIENT_AGE).unwrap(), "090Y");
+            assert_eq!(value(&any, tags::PATIENT_BIRTH_DATE), None);
+        }
+
+        #[test]
+        fn leaves_younger_patients_alone() {
+            let any = anonymize(
+                &[BIRTH, STUDY, (tags::PATIENT_AGE, VR::AS, "039Y")],
+                BirthDateGeneralization::Full,
+                PatientAgeAction::Keep,
+                Some(89),
+            );
+
+            assert_eq!(value(&any, tags::PATIENT_BIRTH_DATE).unwrap(), "19800612");
+            assert_eq!(value(&any, tags::PATIENT_AGE).unwrap(), "039Y");
+        }
+
+        #[test]
+        fn warns_about_invalid_values() {
+            let any = anonymize(
+                &[(tags::PATIENT_AGE, VR::AS, "39 years")],
+                BirthDateGeneralization::Full,
+                PatientAgeAction::Recompute,
+                None,
+            );
+
+            assert_eq!(
+                any.warnings(),
+                ["Patient Age `39 years` isn't a DICOM age like 045Y"]
+            );
+        }
+    }
+
+    mod hooks {
+        use super::{factory, patient_name};
+        use crate::hook::{Decision, ElementContext, ElementHook};
+        use crate::types::ScriptRule;
+        use crate::{Anonymizer, Error, TagAction};
+        use dicom_core::value::Value;
+        use dicom_core::{DataElement, Length, PrimitiveValue, Tag, VR};
+        use dicom_dictionary_std::tags;
+        use dicom_object::InMemDicomObject;
+        use std::collections::HashMap;
+
+        /// Keeps the name for one site and takes patient IDs from a patient index
+        struct Site {
+            index: HashMap<&'static str, String>,
+        }
+
+        impl ElementHook for Site {
+            fn decide(&self, element: &ElementContext) -> Decision {
+                let text = |tag| {
+                    let element = element.original.element(tag).ok()?;
+                    Some(element.to_str().ok()?.trim_end().to_string())
+                };
+
+                match element.element.header().tag {
+                    tags::PATIENT_NAME if text(tags::INSTITUTION_NAME).as_deref() == Some("A") => {
+                        Decision::Keep
+                    }
+                    tags::PATIENT_ID => {
+                        let id = text(tags::PATIENT_ID).unwrap_or_default();
+                        match self.index.get(id.as_str()) {
+                            Some(id) => Decision::Change(id.clone()),
+                            None => Decision::Remove,
+                        }
+                    }
+                    _ => Decision::Pass,
+                }
+            }
+        }
+
+        struct RemovePath(&'static str);
+
+        impl ElementHook for RemovePath {
+            fn decide(&self, element: &ElementContext) -> Decision {
+                match element.path == self.0 {
+                    true => Decision::Remove,
+                    false => Decision::Pass,
+                }
+            }
+        }
+
+        fn anonymize(institution: &str, hook: impl ElementHook + 'static) -> Anonymizer {
+            let mut any = Anonymizer::from_bytes(&factory()).unwrap();
+            let obj = &mut any.file.as_mut().unwrap().obj;
+            obj.put(DataElement::new(
+                tags::INSTITUTION_NAME,
+                VR::LO,
+                PrimitiveValue::from(institution),
+            ));
+            obj.put(DataElement::new(
+                tags::PATIENT_ID,
+                VR::LO,
+                PrimitiveValue::from("12345"),
+            ));
+            let mut builder = Anonymizer::meta_builder();
+            builder.patient_name(TagAction::Change("Anonymous".to_string()));
+            any.meta(builder.build().unwrap());
+            any.add_hook(hook);
+            any.anonymize().map(|_| any).unwrap()
+        }
+
+        fn text(any: &Anonymizer, tag: Tag) -> Option<String> {
+            let obj = &any.file.as_ref().unwrap().obj;
+            Some(
+                obj.element(tag)
+                    .ok()?
+                    .to_str()
+                    .unwrap()
+                    .trim_end()
+                    .to_string(),
+            )
+        }
+
+        fn site() -> Site {
+            Site {
+                index: HashMap::from([("12345", "MPI-7".to_string())]),
+            }
+        }
+
+        #[test]
+        fn decisions_win_over_the_built_in_rules() {
+            let any = anonymize("A", site());
+            assert_eq!(patient_name(&any.to_bytes().unwrap()), "Doe^John");
+            assert_eq!(text(&any, tags::PATIENT_ID).unwrap(), "MPI-7");
+
+            let any = anonymize("B", site());
+            assert_eq!(patient_name(&any.to_bytes().unwrap()), "Anonymous");
+        }
+
+        #[test]
+        fn sees_the_elements_inside_sequences() {
+            let mut item = InMemDicomObject::new_empty();
+            item.put(DataElement::new(
+                tags::REFERENCED_SOP_INSTANCE_UID,
+                VR::UI,
+                PrimitiveValue::from("1.2.3.4.5"),
+            ));
+            let mut any = Anonymizer::from_bytes(&factory()).unwrap();
+            any.file.as_mut().unwrap().obj.put(DataElement::new(
+                tags::REFERENCED_IMAGE_SEQUENCE,
+                VR::SQ,
+                Value::Sequence {
+                    items: vec![item].into(),
+                    size: Length::UNDEFINED,
+                },
+            ));
+            let path = "ReferencedImageSequence[0].ReferencedSOPInstanceUID";
+            any.add_hook(RemovePath(path));
+            any.anonymize().unwrap();
+
+            let elements = any.elements().unwrap();
+            assert!(elements.iter().any(|e| e.path == "ReferencedImageSequence"));
+            assert!(!elements.iter().any(|e| e.path == path));
+        }
+
+        #[test]
+        fn changed_values_have_to_fit_their_vr() {
+            let mut site = site();
+            site.index.insert("12345", "7".repeat(70));
+
+            let mut any = Anonymizer::from_bytes(&factory()).unwrap();
+            any.file.as_mut().unwrap().obj.put(DataElement::new(
+                tags::PATIENT_ID,
+                VR::LO,
+                PrimitiveValue::from("12345"),
+            ));
+            any.add_hook(site);
+
+            let error = any.anonymize().unwrap_err();
+            assert!(matches!(error, Error::VrViolation { .. }));
+        }
+
+        #[test]
+        fn runs_the_scripts_of_the_meta() {
+            let file = std::env::temp_dir().join("dicom-tools-institution.rhai");
+            std::fs::write(&file, r#"if PatientID == "12345" { "Site " + value }"#).unwrap();
+
+            let mut any = Anonymizer::from_bytes(&factory()).unwrap();
+            let obj = &mut any.file.as_mut().unwrap().obj;
+            obj.put(DataElement::new(
+                tags::PATIENT_ID,
+                VR::LO,
+                PrimitiveValue::from("12345"),
+            ));
+            obj.put(DataElement::new(
+                tags::INSTITUTION_NAME,
+                VR::LO,
+                PrimitiveValue::from("A"),
+            ));
+            let mut builder = Anonymizer::meta_builder();
+            builder.scripts(vec![ScriptRule {
+                attribute: "InstitutionName".to_string(),
+                file,
+            }]);
+            any.meta(builder.build().unwrap());
+            any.anonymize().unwrap();
+
+            assert_eq!(text(&any, tags::INSTITUTION_NAME).unwrap(), "Site A");
+        }
+    }
+}
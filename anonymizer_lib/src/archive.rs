@@ -0,0 +1,401 @@
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::io::{Cursor, Read, Write};
+use tracing::debug_span;
+use zip::write::FileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+use crate::{
+    Anonymizer, AnonymizerMeta, Error, FileLayout, FilterAction, NonDicomPolicy, Pseudonyms, Result,
+};
+
+/// How many files of a batch run ended up with which decision.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+pub struct BatchReport {
+    pub anonymized: usize,
+    pub copied: usize,
+    pub skipped: usize,
+    pub not_dicom: usize,
+    /// DICOM files that couldn't be read or anonymized, they are left out of the output
+    pub failed: usize,
+    /// Problems found in the files, prefixed with the file they were found in
+    pub warnings: Vec<String>,
+}
+
+impl BatchReport {
+    pub fn record(&mut self, action: FilterAction) {
+        match action {
+            FilterAction::Anonymize => self.anonymized += 1,
+            FilterAction::Copy => self.copied += 1,
+            FilterAction::Skip => self.skipped += 1,
+        }
+    }
+
+    /// Counts a DICOM file that couldn't be read or anonymized and keeps the reason as a warning.
+    pub fn fail(&mut self, file: &str, error: &dyn fmt::Display) {
+        self.failed += 1;
+        self.warn(file, &[format!("not anonymized, {}", error)]);
+    }
+
+    pub fn warn(&mut self, file: &str, warnings: &[String]) {
+        self.warnings.extend(
+            warnings
+                .iter()
+                .map(|warning| format!("{}: {}", file, warning)),
+        );
+    }
+}
+
+impl fmt::Display for BatchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} anonymized, {} copied, {} skipped, {} not DICOM, {} failed",
+            self.anonymized, self.copied, self.skipped, self.not_dicom, self.failed
+        )
+    }
+}
+
+/// Anonymizes every DICOM file of a zip archive and returns the new archive.
+///
+/// Instance UIDs and patient IDs are replaced consistently across the archive, so references
+/// between its files stay intact. The replacements are keyed with the `pseudonym_salt` of `meta`,
+/// without one with a hash of the archive. The filters of `meta` decide per file, entries that
+/// aren't DICOM are handled by `non_dicom`. Entries that look like DICOM but can't be read or
+/// anonymized are left out and reported, never copied. Folders are kept, entries keep their names.
+pub fn anonymize_zip(
+    data: &[u8],
+    meta: &AnonymizerMeta,
+    non_dicom: NonDicomPolicy,
+) -> Result<(Vec<u8>, BatchReport)> {
+    let mut archive = ZipArchive::new(Cursor::new(data))?;
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    let mut pseudonyms = Pseudonyms::new(&salt(data, meta));
+    let mut report = BatchReport::default();
+
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index)?;
+        let name = entry.name().to_string();
+        let options = FileOptions::default().compression_method(entry.compression());
+        let _entry = debug_span!("entry", name = %name).entered();
+
+        if entry.is_dir() {
+            writer.add_directory(name, options)?;
+            continue;
+        }
+
+        let mut content = Vec::new();
+        entry
+            .read_to_end(&mut content)
+            .map_err(|source| Error::Io {
+                message: format!("Could not read {}", name),
+                source,
+            })?;
+
+        let output = match Anonymizer::from_bytes(&content) {
+            Ok(any) => match process(any, &content, meta, &mut pseudonyms) {
+                Ok((action, output, warnings)) => {
+                    report.record(action);
+                    report.warn(&name, &warnings);
+                    output
+                }
+                Err(error) => {
+                    report.fail(&name, &error);
+                    None
+                }
+            },
+            Err(error) if FileLayout::detect(&content).is_some() => {
+                report.fail(&name, &error);
+                None
+            }
+            Err(_) => {
+                report.not_dicom += 1;
+                match non_dicom {
+                    NonDicomPolicy::Copy => Some(content),
+                    NonDicomPolicy::Skip => None,
+                }
+            }
+        };
+
+        if let Some(output) = output {
+            writer.start_file(name.as_str(), options)?;
+            writer.write_all(&output).map_err(|source| Error::Io {
+                message: format!("Could not write {}", name),
+                source,
+            })?;
+        }
+    }
+
+    Ok((writer.finish()?.into_inner(), report))
+}
+
+/// Applies the filters and the meta to one DICOM entry, returns the decision, the new content of
+/// the entry if it is kept and the warnings.
+fn process(
+    mut any: Anonymizer,
+    content: &[u8],
+    meta: &AnonymizerMeta,
+    pseudonyms: &mut Pseudonyms,
+) -> Result<(FilterAction, Option<Vec<u8>>, Vec<String>)> {
+    any.meta(meta.clone());
+    let action = any.filter_action()?;
+
+    let output = match action {
+        FilterAction::Anonymize => {
+            any.anonymize()?;
+            any.pseudonymize(pseudonyms)?;
+            Some(any.to_bytes()?)
+        }
+        FilterAction::Copy => Some(content.to_vec()),
+        FilterAction::Skip => None,
+    };
+
+    Ok((action, output, any.warnings().to_vec()))
+}
+
+/// Salt for the pseudonyms. The configured one gives the same replacements across archives, the
+/// SHA-256 of the archive only within it, and anyone with the original archive can recompute it.
+fn salt(data: &[u8], meta: &AnonymizerMeta) -> Vec<u8> {
+    match meta.pseudonym_salt() {
+        Some(salt) => salt.as_bytes().to_vec(),
+        None => Sha256::digest(data).to_vec(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{anonymize_zip, BatchReport};
+    use crate::types::FilterRule;
+    use crate::OutputTransferSyntax;
+    use crate::{Anonymizer, AnonymizerMeta, Error, NonDicomPolicy, TagAction};
+    use dicom_core::value::Value;
+    use dicom_core::{DataElement, PrimitiveValue, VR};
+    use dicom_dictionary_std::tags;
+    use dicom_object::{FileMetaTableBuilder, InMemDicomObject};
+    use std::io::{Cursor, Read, Write};
+    use std::str::FromStr;
+    use zip::write::FileOptions;
+    use zip::{ZipArchive, ZipWriter};
+
+    fn dcm(modality: &str, sop_instance_uid: &str) -> Vec<u8> {
+        write(
+            dataset(modality, sop_instance_uid),
+            "1.2.840.10008.1.2.1",
+            sop_instance_uid,
+        )
+    }
+
+    /// A file with JPEG compressed pixel data, which can't be converted to another transfer syntax.
+    fn jpeg(sop_instance_uid: &str) -> Vec<u8> {
+        let mut obj = dataset("CT", sop_instance_uid);
+        obj.put(DataElement::new(
+            tags::PIXEL_DATA,
+            VR::OB,
+            Value::PixelSequence {
+                offset_table: vec![].into(),
+                fragments: vec![vec![0xFF, 0xD8]].into(),
+            },
+        ));
+        write(obj, "1.2.840.10008.1.2.4.50", sop_instance_uid)
+    }
+
+    fn dataset(modality: &str, sop_instance_uid: &str) -> InMemDicomObject {
+        let mut obj = InMemDicomObject::new_empty();
+        for (tag, vr, value) in [
+            (tags::PATIENT_NAME, VR::PN, "Doe^John"),
+            (tags::PATIENT_ID, VR::LO, "12345"),
+            (tags::MODALITY, VR::CS, modality),
+            (tags::SOP_CLASS_UID, VR::UI, "1.2.840.10008.5.1.4.1.1.2"),
+            (tags::SOP_INSTANCE_UID, VR::UI, sop_instance_uid),
+            (tags::STUDY_INSTANCE_UID, VR::UI, "1.2.3"),
+        ] {
+            obj.put(DataElement::new(tag, vr, PrimitiveValue::from(value)));
+        }
+        obj
+    }
+
+    fn write(obj: InMemDicomObject, transfer_syntax: &str, sop_instance_uid: &str) -> Vec<u8> {
+        let mut data = Vec::new();
+        obj.with_meta(
+            FileMetaTableBuilder::new()
+                .transfer_syntax(transfer_syntax)
+                .media_storage_sop_class_uid("1.2.840.10008.5.1.4.1.1.2")
+                .media_storage_sop_instance_uid(sop_instance_uid),
+        )
+        .unwrap()
+        .write_all(&mut data)
+        .unwrap();
+        data
+    }
+
+    fn zip(entries: &[(&str, Vec<u8>)]) -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .add_directory("study/", FileOptions::default())
+            .unwrap();
+        for (name, content) in entries {
+            writer.start_file(*name, FileOptions::default()).unwrap();
+            writer.write_all(content).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    fn entries(data: &[u8]) -> Vec<(String, Vec<u8>)> {
+        let mut archive = ZipArchive::new(Cursor::new(data)).unwrap();
+        (0..archive.len())
+            .map(|index| {
+                let mut entry = archive.by_index(index).unwrap();
+                let mut content = Vec::new();
+                entry.read_to_end(&mut content).unwrap();
+                (entry.name().to_string(), content)
+            })
+            .collect()
+    }
+
+    fn element(data: &[u8], tag: dicom_core::Tag) -> String {
+        let any = Anonymizer::from_bytes(data).unwrap();
+        any.elements()
+            .unwrap()
+            .into_iter()
+            .find(|element| element.tag == format!("({:04X},{:04X})", tag.0, tag.1))
+            .map(|element| element.value)
+            .unwrap()
+    }
+
+    fn meta() -> AnonymizerMeta {
+        let mut builder = Anonymizer::meta_builder();
+        builder.patient_name(TagAction::Change("Anonymous".to_string()));
+        builder.filters(vec![FilterRule::from_str("copy:modality=PR").unwrap()]);
+        builder.build().unwrap()
+    }
+
+    #[test]
+    fn anonymizes_every_entry_with_consistent_uids() {
+        let input = zip(&[
+            ("study/1.dcm", dcm("CT", "1.2.3.1")),
+            ("study/2.dcm", dcm("CT", "1.2.3.2")),
+            ("study/pr.dcm", dcm("PR", "1.2.3.3")),
+            ("study/notes.txt", b"not a DICOM file".to_vec()),
+        ]);
+
+        let (output, report) = anonymize_zip(&input, &meta(), NonDicomPolicy::Skip).unwrap();
+
+        assert_eq!(
+            report,
+            BatchReport {
+                anonymized: 2,
+                copied: 1,
+                skipped: 0,
+                not_dicom: 1,
+                failed: 0,
+                warnings: vec![],
+            }
+        );
+        let output = entries(&output);
+        let names = output
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec!["study/", "study/1.dcm", "study/2.dcm", "study/pr.dcm"]
+        );
+
+        let (first, second) = (&output[1].1, &output[2].1);
+        assert_eq!(element(first, tags::PATIENT_NAME), "Anonymous");
+        assert_eq!(
+            element(first, tags::STUDY_INSTANCE_UID),
+            element(second, tags::STUDY_INSTANCE_UID)
+        );
+        assert!(element(first, tags::STUDY_INSTANCE_UID).starts_with("2.25."));
+        assert_ne!(
+            element(first, tags::SOP_INSTANCE_UID),
+            element(second, tags::SOP_INSTANCE_UID)
+        );
+        assert_eq!(
+            element(first, tags::SOP_CLASS_UID),
+            "1.2.840.10008.5.1.4.1.1.2"
+        );
+        assert_eq!(
+            element(first, tags::PATIENT_ID),
+            element(second, tags::PATIENT_ID)
+        );
+        assert_ne!(element(first, tags::PATIENT_ID), "12345");
+
+        assert_eq!(output[3].1, dcm("PR", "1.2.3.3"));
+    }
+
+    #[test]
+    fn copies_other_entries_by_policy() {
+        let input = zip(&[("notes.txt", b"not a DICOM file".to_vec())]);
+
+        let (output, report) = anonymize_zip(&input, &meta(), NonDicomPolicy::Copy).unwrap();
+
+        assert_eq!(report.not_dicom, 1);
+        assert_eq!(
+            entries(&output)[1],
+            ("notes.txt".to_string(), b"not a DICOM file".to_vec())
+        );
+    }
+
+    #[test]
+    fn configured_salt_gives_the_same_replacements_in_every_archive() {
+        let mut builder = Anonymizer::meta_builder();
+        builder.pseudonym_salt(Some("secret".to_string()));
+        let salted = builder.build().unwrap();
+        let first = zip(&[("1.dcm", dcm("CT", "1.2.3.1"))]);
+        let second = zip(&[
+            ("2.dcm", dcm("CT", "1.2.3.1")),
+            ("3.dcm", dcm("MR", "1.2.3.3")),
+        ]);
+
+        let uid = |input: &[u8], meta: &AnonymizerMeta| {
+            let (output, _) = anonymize_zip(input, meta, NonDicomPolicy::Skip).unwrap();
+            element(&entries(&output)[1].1, tags::SOP_INSTANCE_UID)
+        };
+
+        assert_eq!(uid(&first, &salted), uid(&second, &salted));
+        assert_ne!(uid(&first, &meta()), uid(&second, &meta()));
+    }
+
+    #[test]
+    fn leaves_out_dicom_entries_that_cant_be_read() {
+        let mut truncated = dcm("CT", "1.2.3.1");
+        truncated.truncate(truncated.len() - 10);
+        let input = zip(&[("study/1.dcm", truncated)]);
+
+        let (output, report) = anonymize_zip(&input, &meta(), NonDicomPolicy::Copy).unwrap();
+
+        assert_eq!((report.failed, report.not_dicom), (1, 0));
+        assert!(report.warnings[0].starts_with("study/1.dcm: not anonymized"));
+        assert_eq!(entries(&output).len(), 1);
+    }
+
+    #[test]
+    fn leaves_out_dicom_entries_that_cant_be_anonymized() {
+        let mut builder = Anonymizer::meta_builder();
+        builder.transfer_syntax(OutputTransferSyntax::ExplicitVrLittleEndian);
+        let meta = builder.build().unwrap();
+        let input = zip(&[
+            ("study/1.dcm", dcm("CT", "1.2.3.1")),
+            ("study/2.dcm", jpeg("1.2.3.2")),
+        ]);
+
+        let (output, report) = anonymize_zip(&input, &meta, NonDicomPolicy::Copy).unwrap();
+
+        assert_eq!((report.anonymized, report.failed), (1, 1));
+        assert!(report.warnings[0].starts_with("study/2.dcm: not anonymized, The transfer syntax"));
+        let names = entries(&output)
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["study/", "study/1.dcm"]);
+    }
+
+    #[test]
+    fn rejects_other_data() {
+        let error = anonymize_zip(b"not a zip", &meta(), NonDicomPolicy::Skip).unwrap_err();
+        assert!(matches!(error, Error::Zip(_)));
+    }
+}
@@ -0,0 +1,479 @@
+use dicom_core::value::Value;
+use dicom_core::{DataElement, PrimitiveValue, VR};
+use dicom_dictionary_std::tags;
+use dicom_encoding::text::{SpecificCharacterSet, TextCodec};
+use dicom_object::mem::InMemElement;
+use dicom_object::InMemDicomObject;
+use encoding::all::{
+    EUC_JP, GBK, ISO_8859_1, ISO_8859_2, ISO_8859_3, ISO_8859_4, ISO_8859_5, ISO_8859_6,
+    ISO_8859_7, ISO_8859_8, WINDOWS_1254, WINDOWS_874, WINDOWS_949,
+};
+use encoding::{DecoderTrap, EncodingRef};
+
+/// Declared when a value doesn't fit the character set of the file anymore
+pub const UTF_8: &str = "ISO_IR 192";
+
+/// VRs whose values are encoded with the Specific Character Set, all others only use ASCII
+const TEXT_VRS: [VR; 7] = [VR::SH, VR::LO, VR::UC, VR::ST, VR::LT, VR::UT, VR::PN];
+
+const ESC: u8 = 0x1b;
+
+/// The set in G0, used for bytes below 0x80.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum G0 {
+    Ascii,
+    /// JIS X 0201 Romaji, ASCII with `¥` and `‾`
+    Romaji,
+    /// JIS X 0208 kanji and kana, two bytes per character
+    Jis0208,
+    /// JIS X 0212 supplementary kanji, two bytes per character
+    Jis0212,
+}
+
+/// The set in G1, used for bytes from 0x80.
+#[derive(Clone, Copy)]
+enum G1 {
+    None,
+    /// JIS X 0201 half-width katakana
+    Katakana,
+    /// KS X 1001 Korean, two bytes per character
+    Ksx1001,
+    /// GB 2312 Simplified Chinese, two bytes per character
+    Gb2312,
+    /// The right half of an ISO 8859 set
+    SingleByte(EncodingRef),
+}
+
+#[derive(Clone, Copy)]
+struct Designations {
+    g0: G0,
+    g1: G1,
+}
+
+/// The Specific Character Set (0008,0005) of a dataset.
+#[derive(Clone, Copy)]
+enum CharacterSet {
+    /// One set dicom-rs decodes and encodes itself
+    Plain(SpecificCharacterSet),
+    /// Code extensions like `ISO 2022 IR 6\ISO 2022 IR 87` or sets dicom-rs doesn't know, their
+    /// bytes are read with `read_as`, which is Latin-1 unless the first set is a known one
+    Iso2022 {
+        read_as: SpecificCharacterSet,
+        initial: Designations,
+    },
+}
+
+impl CharacterSet {
+    /// `None` if `dataset` doesn't declare a set of its own.
+    fn of(dataset: &InMemDicomObject) -> Option<Self> {
+        let terms = dataset
+            .element_opt(tags::SPECIFIC_CHARACTER_SET)
+            .ok()
+            .flatten()?
+            .to_multi_str()
+            .ok()?
+            .iter()
+            .map(|term| term.trim().to_string())
+            .collect::<Vec<_>>();
+        let first = terms.first().map(String::as_str).unwrap_or_default();
+        let known = SpecificCharacterSet::from_code(first);
+
+        Some(match known {
+            Some(set) if terms.len() == 1 && !first.starts_with("ISO 2022") => {
+                CharacterSet::Plain(set)
+            }
+            _ => CharacterSet::Iso2022 {
+                read_as: known.unwrap_or_default(),
+                initial: initial(first),
+            },
+        })
+    }
+
+    /// Whether `text` can be written in this set. dicom-rs writes only ASCII for code extensions
+    /// and would write Latin-1 for the default set.
+    fn fits(&self, text: &str) -> bool {
+        match self {
+            CharacterSet::Plain(SpecificCharacterSet::Default) | CharacterSet::Iso2022 { .. } => {
+                text.is_ascii()
+            }
+            CharacterSet::Plain(set) => set.encode(text).is_ok(),
+        }
+    }
+}
+
+/// The sets of a first value without escape sequences, e.g. `ISO_IR 13` or `ISO 2022 IR 100`.
+fn initial(term: &str) -> Designations {
+    let number = term
+        .strip_prefix("ISO 2022 IR ")
+        .or_else(|| term.strip_prefix("ISO_IR "))
+        .unwrap_or_default();
+    let single = |encoding| Designations {
+        g0: G0::Ascii,
+        g1: G1::SingleByte(encoding),
+    };
+
+    match number {
+        "13" => Designations {
+            g0: G0::Romaji,
+            g1: G1::Katakana,
+        },
+        "100" => single(ISO_8859_1),
+        "101" => single(ISO_8859_2),
+        "109" => single(ISO_8859_3),
+        "110" => single(ISO_8859_4),
+        "144" => single(ISO_8859_5),
+        "127" => single(ISO_8859_6),
+        "126" => single(ISO_8859_7),
+        "138" => single(ISO_8859_8),
+        "148" => single(WINDOWS_1254),
+        "166" => single(WINDOWS_874),
+        _ => Designations {
+            g0: G0::Ascii,
+            g1: G1::None,
+        },
+    }
+}
+
+/// The escape sequence at the start of `bytes` and its length.
+fn escape(bytes: &[u8], designations: &mut Designations) -> Option<usize> {
+    let (len, g0, g1) = match bytes.get(1..)? {
+        [b'(', b'B', ..] => (3, Some(G0::Ascii), None),
+        [b'(', b'J', ..] => (3, Some(G0::Romaji), None),
+        [b'$', b'B', ..] | [b'$', b'@', ..] => (3, Some(G0::Jis0208), None),
+        [b'$', b'(', b'D', ..] => (4, Some(G0::Jis0212), None),
+        [b')', b'I', ..] => (3, None, Some(G1::Katakana)),
+        [b'$', b')', b'C', ..] => (4, None, Some(G1::Ksx1001)),
+        [b'$', b')', b'A', ..] => (4, None, Some(G1::Gb2312)),
+        [b'-', set, ..] => {
+            let encoding = match set {
+                b'A' => ISO_8859_1,
+                b'B' => ISO_8859_2,
+                b'C' => ISO_8859_3,
+                b'D' => ISO_8859_4,
+                b'L' => ISO_8859_5,
+                b'G' => ISO_8859_6,
+                b'F' => ISO_8859_7,
+                b'H' => ISO_8859_8,
+                b'M' => WINDOWS_1254,
+                b'T' => WINDOWS_874,
+                _ => return None,
+            };
+            (3, None, Some(G1::SingleByte(encoding)))
+        }
+        _ => return None,
+    };
+
+    if let Some(g0) = g0 {
+        designations.g0 = g0;
+    }
+    if let Some(g1) = g1 {
+        designations.g1 = g1;
+    }
+    Some(len)
+}
+
+fn decode_with(encoding: EncodingRef, bytes: &[u8]) -> String {
+    encoding
+        .decode(bytes, DecoderTrap::Replace)
+        .unwrap_or_default()
+}
+
+/// Decodes the bytes of one value with ISO 2022 escape sequences. The sets return to `initial`
+/// after delimiters, for person names also after each component.
+fn decode_iso_2022(bytes: &[u8], initial: Designations, pn: bool) -> String {
+    let mut designations = initial;
+    let mut text = String::new();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let byte = bytes[i];
+        if byte == ESC {
+            if let Some(len) = escape(&bytes[i..], &mut designations) {
+                i += len;
+                continue;
+            }
+        }
+
+        let next = bytes.get(i + 1).copied();
+        let double = match (designations.g0, designations.g1, next) {
+            (G0::Jis0208, _, Some(next)) if byte > 0x20 && byte < 0x7f && next > 0x20 => {
+                Some(decode_with(EUC_JP, &[byte | 0x80, next | 0x80]))
+            }
+            (G0::Jis0212, _, Some(next)) if byte > 0x20 && byte < 0x7f && next > 0x20 => {
+                Some(decode_with(EUC_JP, &[0x8f, byte | 0x80, next | 0x80]))
+            }
+            (_, G1::Ksx1001, Some(next)) if byte >= 0xa1 && next >= 0xa1 => {
+                Some(decode_with(WINDOWS_949, &[byte, next]))
+            }
+            (_, G1::Gb2312, Some(next)) if byte >= 0xa1 && next >= 0xa1 => {
+                Some(decode_with(GBK, &[byte, next]))
+            }
+            _ => None,
+        };
+        if let Some(double) = double {
+            text.push_str(&double);
+            i += 2;
+            continue;
+        }
+
+        match (byte, designations.g0, designations.g1) {
+            (0x5c, G0::Romaji, _) => text.push('¥'),
+            (0x7e, G0::Romaji, _) => text.push('‾'),
+            (0x80.., _, G1::Katakana) => text.push_str(&decode_with(EUC_JP, &[0x8e, byte])),
+            (0x80.., _, G1::SingleByte(encoding)) => text.push_str(&decode_with(encoding, &[byte])),
+            // Left as dicom-rs read it
+            _ => text.push(byte as char),
+        }
+        let delimiter = matches!(byte, b'\\' | b'\r' | b'\n' | b'\t' | 0x0c)
+            || (pn && matches!(byte, b'^' | b'='));
+        if delimiter {
+            designations = initial;
+        }
+        i += 1;
+    }
+
+    text
+}
+
+/// Decodes the values of character sets dicom-rs reads as Latin-1, e.g. the Japanese
+/// `ISO 2022 IR 87`, so they can be searched and replaced as text. Items inherit the set of their
+/// dataset unless they declare their own.
+pub(crate) fn decode(dataset: InMemDicomObject) -> InMemDicomObject {
+    decode_dataset(dataset, CharacterSet::Plain(SpecificCharacterSet::Default))
+}
+
+fn decode_dataset(dataset: InMemDicomObject, inherited: CharacterSet) -> InMemDicomObject {
+    let charset = CharacterSet::of(&dataset).unwrap_or(inherited);
+    let elements = dataset
+        .into_iter()
+        .map(|element| decode_element(element, charset))
+        .collect::<Vec<_>>();
+
+    InMemDicomObject::from_element_iter(elements)
+}
+
+fn decode_element(element: InMemElement, charset: CharacterSet) -> InMemElement {
+    let (header, value) = (*element.header(), element.into_value());
+
+    let value = match (value, charset) {
+        (Value::Sequence { items, size }, _) => Value::Sequence {
+            items: items
+                .into_iter()
+                .map(|item| decode_dataset(item, charset))
+                .collect(),
+            size,
+        },
+        (Value::Primitive(primitive), CharacterSet::Iso2022 { read_as, initial })
+            if TEXT_VRS.contains(&header.vr) =>
+        {
+            // The values were split at every `\`, even inside of two byte characters
+            let read = primitive.to_str();
+            match read_as.encode(&read) {
+                Ok(bytes) => {
+                    let text = decode_iso_2022(&bytes, initial, header.vr == VR::PN);
+                    Value::Primitive(PrimitiveValue::Strs(
+                        text.split('\\').map(str::to_string).collect(),
+                    ))
+                }
+                Err(_) => Value::Primitive(primitive),
+            }
+        }
+        (value, _) => value,
+    };
+
+    DataElement::new(header.tag, header.vr, value)
+}
+
+/// Declares ISO_IR 192 in the dataset and its items if a text value doesn't fit the declared
+/// character set anymore, e.g. a Japanese name in a Latin-1 file. The values are held as text,
+/// so dicom-rs encodes them all in UTF-8 then.
+pub(crate) fn declare_utf8_if_needed(dataset: InMemDicomObject) -> InMemDicomObject {
+    match fits(&dataset, CharacterSet::Plain(SpecificCharacterSet::Default)) {
+        true => dataset,
+        false => {
+            let mut dataset = declare_utf8(dataset);
+            dataset.put(DataElement::new(
+                tags::SPECIFIC_CHARACTER_SET,
+                VR::CS,
+                PrimitiveValue::from(UTF_8),
+            ));
+            dataset
+        }
+    }
+}
+
+fn fits(dataset: &InMemDicomObject, inherited: CharacterSet) -> bool {
+    let charset = CharacterSet::of(dataset).unwrap_or(inherited);
+
+    dataset.into_iter().all(|element| match element.value() {
+        Value::Sequence { items, .. } => items.iter().all(|item| fits(item, charset)),
+        Value::Primitive(primitive) if TEXT_VRS.contains(&element.vr()) => {
+            charset.fits(&primitive.to_str())
+        }
+        _ => true,
+    })
+}
+
+/// Replaces the character sets items declare themselves.
+fn declare_utf8(dataset: InMemDicomObject) -> InMemDicomObject {
+    let elements = dataset
+        .into_iter()
+        .map(|element| {
+            let (header, value) = (*element.header(), element.into_value());
+            let value = match value {
+                Value::Sequence { items, size } => Value::Sequence {
+                    items: items.into_iter().map(declare_utf8).collect(),
+                    size,
+                },
+                _ if header.tag == tags::SPECIFIC_CHARACTER_SET => {
+                    Value::Primitive(PrimitiveValue::from(UTF_8))
+                }
+                value => value,
+            };
+            DataElement::new(header.tag, header.vr, value)
+        })
+        .collect::<Vec<_>>();
+
+    InMemDicomObject::from_element_iter(elements)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{declare_utf8_if_needed, decode, UTF_8};
+    use dicom_core::value::Value;
+    use dicom_core::{DataElement, Length, PrimitiveValue, VR};
+    use dicom_dictionary_std::tags;
+    use dicom_object::InMemDicomObject;
+
+    /// What dicom-rs reads for sets it doesn't know: every byte as the Latin-1 character
+    fn latin1(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| *byte as char).collect()
+    }
+
+    fn dataset(charset: &str, name: &str) -> InMemDicomObject {
+        let mut obj = InMemDicomObject::new_empty();
+        obj.put(DataElement::new(
+            tags::SPECIFIC_CHARACTER_SET,
+            VR::CS,
+            PrimitiveValue::Strs(charset.split('\\').map(str::to_string).collect()),
+        ));
+        obj.put(DataElement::new(
+            tags::PATIENT_NAME,
+            VR::PN,
+            PrimitiveValue::from(name),
+        ));
+        obj
+    }
+
+    fn text(obj: &InMemDicomObject, tag: dicom_core::Tag) -> String {
+        obj.element(tag).unwrap().to_str().unwrap().to_string()
+    }
+
+    // The examples of PS3.5 Annex H, I and J
+    #[test]
+    fn decodes_japanese_kanji_and_hiragana() {
+        let bytes =
+            b"Yamada^Tarou=\x1b$B;3ED\x1b(B^\x1b$BB@O:\x1b(B=\x1b$B$d$^$@\x1b(B^\x1b$B$?$m$&\x1b(B";
+        let obj = decode(dataset("\\ISO 2022 IR 87", &latin1(bytes)));
+
+        assert_eq!(
+            text(&obj, tags::PATIENT_NAME),
+            "Yamada^Tarou=山田^太郎=やまだ^たろう"
+        );
+    }
+
+    #[test]
+    fn decodes_japanese_katakana_in_g1() {
+        let bytes = b"\xd4\xcf\xc0\xde^\xc0\xdb\xb3=\x1b$B;3ED\x1b(J^\x1b$BB@O:\x1b(J=\x1b$B$d$^$@\x1b(J^\x1b$B$?$m$&\x1b(J";
+        let obj = decode(dataset("ISO 2022 IR 13\\ISO 2022 IR 87", &latin1(bytes)));
+
+        assert_eq!(
+            text(&obj, tags::PATIENT_NAME),
+            "ﾔﾏﾀﾞ^ﾀﾛｳ=山田^太郎=やまだ^たろう"
+        );
+    }
+
+    #[test]
+    fn decodes_korean() {
+        let bytes = b"Hong^Gildong=\x1b$)C\xfb\xf3^\x1b$)C\xd1\xce\xd4\xd7=\x1b$)C\xc8\xab^\x1b$)C\xb1\xe6\xb5\xbf";
+        let obj = decode(dataset("\\ISO 2022 IR 149", &latin1(bytes)));
+
+        assert_eq!(
+            text(&obj, tags::PATIENT_NAME),
+            "Hong^Gildong=洪^吉洞=홍^길동"
+        );
+    }
+
+    #[test]
+    fn decodes_chinese() {
+        let bytes = b"Zhang^XiaoDong=\x1b$)A\xd5\xc5^\x1b$)A\xd0\xa1\xb6\xab=";
+        let obj = decode(dataset("\\ISO 2022 IR 58", &latin1(bytes)));
+
+        assert_eq!(text(&obj, tags::PATIENT_NAME), "Zhang^XiaoDong=张^小东=");
+    }
+
+    #[test]
+    fn keeps_sets_dicom_rs_decodes_itself() {
+        let obj = dataset("ISO_IR 100", "Müller^Jörg");
+        assert_eq!(
+            text(&decode(obj.clone()), tags::PATIENT_NAME),
+            "Müller^Jörg"
+        );
+
+        let obj = declare_utf8_if_needed(obj);
+        assert_eq!(text(&obj, tags::SPECIFIC_CHARACTER_SET), "ISO_IR 100");
+    }
+
+    #[test]
+    fn declares_utf8_for_values_that_dont_fit() {
+        let obj = declare_utf8_if_needed(dataset("ISO_IR 100", "山田^花子"));
+        assert_eq!(text(&obj, tags::SPECIFIC_CHARACTER_SET), UTF_8);
+
+        // The default set is ASCII only
+        let obj = declare_utf8_if_needed(dataset("", "Müller"));
+        assert_eq!(text(&obj, tags::SPECIFIC_CHARACTER_SET), UTF_8);
+
+        // ASCII fits code extensions, dicom-rs can't write anything else for them
+        let obj = declare_utf8_if_needed(dataset("\\ISO 2022 IR 87", "Anonymous"));
+        assert_eq!(text(&obj, tags::SPECIFIC_CHARACTER_SET), "\\ISO 2022 IR 87");
+        let obj = declare_utf8_if_needed(dataset("\\ISO 2022 IR 87", "山田"));
+        assert_eq!(text(&obj, tags::SPECIFIC_CHARACTER_SET), UTF_8);
+    }
+
+    #[test]
+    fn items_use_and_override_the_set_of_their_dataset() {
+        let bytes = b"\x1b$B;3ED\x1b(B";
+        let mut item = InMemDicomObject::new_empty();
+        item.put(DataElement::new(
+            tags::PERSON_NAME,
+            VR::PN,
+            PrimitiveValue::from(latin1(bytes)),
+        ));
+        let mut own = dataset("ISO_IR 100", "Müller");
+        own.put(DataElement::new(
+            tags::PERSON_NAME,
+            VR::PN,
+            PrimitiveValue::from("山田"),
+        ));
+        let mut obj = dataset("\\ISO 2022 IR 87", "Anonymous");
+        obj.put(DataElement::new(
+            tags::CONTENT_SEQUENCE,
+            VR::SQ,
+            Value::Sequence {
+                items: vec![item, own].into(),
+                size: Length::UNDEFINED,
+            },
+        ));
+
+        let obj = declare_utf8_if_needed(decode(obj));
+        let items = obj
+            .element(tags::CONTENT_SEQUENCE)
+            .unwrap()
+            .value()
+            .items()
+            .unwrap();
+        assert_eq!(text(&items[0], tags::PERSON_NAME), "山田");
+        assert_eq!(text(&items[1], tags::SPECIFIC_CHARACTER_SET), UTF_8);
+        assert_eq!(text(&obj, tags::SPECIFIC_CHARACTER_SET), UTF_8);
+    }
+}
@@ -0,0 +1,511 @@
+use serde::{Deserialize, Serialize};
+use strum::EnumCount;
+
+use crate::types::{CustomTag, DaValue, FilterRule, Pattern, ScriptRule, SexMapping};
+use crate::{
+    AnonymizerMeta, AnonymizerMetaBuilder, BirthDateGeneralization, OutputTransferSyntax,
+    PatientAgeAction, PatientSex, Preset, RemoveTagsInput, TagAction, ValueStrictness,
+};
+
+mod parse;
+
+pub use parse::{join_index, join_key, FieldError};
+
+/// Versioned schema of a config file.
+///
+/// The same document is accepted as YAML, TOML or JSON by the CLI and as a JS object by the wasm
+/// package, e.g. `{"version": "2.0", "config": {"patient_name": {"Change": "Anonymous"}}}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, EnumCount)]
+#[serde(tag = "version", deny_unknown_fields)]
+pub enum ConfigFileVersions {
+    #[serde(rename = "1.0")]
+    V1_0 { config: ConfigFileV1 },
+    #[serde(rename = "1.1")]
+    V1_1 { config: ConfigFileV1 },
+    /// The config section is an `AnonymizerMeta`, exactly as the wasm package expects it.
+    #[serde(rename = "2.0")]
+    V2_0 { config: AnonymizerMeta },
+}
+
+impl ConfigFileVersions {
+    pub const LATEST: &'static str = "2.0";
+    /// Used for files that don't specify a version.
+    pub const DEFAULT: &'static str = "1.0";
+    pub const VERSIONS: [&'static str; ConfigFileVersions::COUNT] = ["1.0", "1.1", "2.0"];
+
+    pub fn version(&self) -> &'static str {
+        match self {
+            ConfigFileVersions::V1_0 { .. } => "1.0",
+            ConfigFileVersions::V1_1 { .. } => "1.1",
+            ConfigFileVersions::V2_0 { .. } => "2.0",
+        }
+    }
+
+    pub fn meta(&self) -> AnonymizerMeta {
+        match self {
+            ConfigFileVersions::V1_0 { config } | ConfigFileVersions::V1_1 { config } => {
+                config.meta()
+            }
+            ConfigFileVersions::V2_0 { config } => config.clone(),
+        }
+    }
+}
+
+impl From<AnonymizerMeta> for ConfigFileVersions {
+    fn from(config: AnonymizerMeta) -> Self {
+        ConfigFileVersions::V2_0 { config }
+    }
+}
+
+/// The `config` section of the `1.0` and `1.1` versions. Every setting is optional and written in
+/// the same notation as the CLI flags.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigFileV1 {
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "v1_format::value"
+    )]
+    pub preset: Option<Preset>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub patient_name: Option<String>,
+
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "v1_format::birth_day"
+    )]
+    pub patient_birth_day: Option<DaValue>,
+
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "v1_format::value"
+    )]
+    pub generalize_birth_date: Option<BirthDateGeneralization>,
+
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "v1_format::value"
+    )]
+    pub patient_age: Option<PatientAgeAction>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_patient_age: Option<u16>,
+
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "v1_format::patient_sex"
+    )]
+    pub patient_sex: Option<PatientSex>,
+
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "v1_format::value"
+    )]
+    pub patient_sex_map: Option<SexMapping>,
+
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "v1_format::value"
+    )]
+    pub value_strictness: Option<ValueStrictness>,
+
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "v1_format::value"
+    )]
+    pub transfer_syntax: Option<OutputTransferSyntax>,
+
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "v1_format::list"
+    )]
+    pub remove_tags: Option<Vec<CustomTag>>,
+
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "v1_format::list"
+    )]
+    pub filters: Option<Vec<FilterRule>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scrub_text: Option<bool>,
+
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "v1_format::list"
+    )]
+    pub scrub_patterns: Option<Vec<Pattern>>,
+
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "v1_format::list"
+    )]
+    pub scripts: Option<Vec<ScriptRule>>,
+}
+
+impl ConfigFileV1 {
+    pub fn meta(&self) -> AnonymizerMeta {
+        let mut builder = AnonymizerMetaBuilder::default();
+
+        // Only the given keys are set, a missing one leaves lower layers and the preset in effect
+        if let Some(value) = self.preset {
+            builder.preset(value);
+        }
+        if let Some(value) = &self.patient_name {
+            builder.patient_name(TagAction::Change(value.clone()));
+        }
+        if let Some(value) = &self.patient_birth_day {
+            builder.patient_birth_date(TagAction::Change(value.clone()));
+        }
+        if let Some(value) = self.generalize_birth_date {
+            builder.generalize_birth_date(value);
+        }
+        if let Some(value) = self.patient_age {
+            builder.patient_age(value);
+        }
+        if let Some(value) = self.max_patient_age {
+            builder.max_patient_age(value);
+        }
+        if let Some(value) = self.patient_sex {
+            builder.patient_sex(TagAction::Change(value));
+        }
+        if let Some(value) = &self.patient_sex_map {
+            builder.patient_sex_map(value.clone());
+        }
+        if let Some(value) = self.value_strictness {
+            builder.value_strictness(value);
+        }
+        if let Some(value) = &self.remove_tags {
+            builder.remove_tags(RemoveTagsInput::from(value.clone()));
+        }
+        if let Some(value) = &self.filters {
+            builder.filters(value.clone());
+        }
+        if let Some(value) = self.scrub_text {
+            builder.scrub_text(value);
+        }
+        if let Some(value) = &self.scrub_patterns {
+            builder.scrub_patterns(value.clone());
+        }
+        if let Some(value) = &self.scripts {
+            builder.scripts(value.clone());
+        }
+        if let Some(value) = self.transfer_syntax {
+            builder.transfer_syntax(value);
+        }
+
+        builder.finish()
+    }
+}
+
+mod v1_format {
+    pub mod birth_day {
+        use serde::{de, Deserialize, Deserializer, Serializer};
+
+        use crate::types::DaValue;
+
+        pub fn serialize<S>(value: &Option<DaValue>, s: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            match value {
+                Some(value) => s.serialize_str(&value.to_date_string()),
+                None => s.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D>(d: D) -> Result<Option<DaValue>, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            let raw = String::deserialize(d)?;
+            DaValue::parse_date(&raw)
+                .map(Some)
+                .map_err(|e| de::Error::custom(e.to_string()))
+        }
+    }
+
+    pub mod patient_sex {
+        use serde::{de, Deserialize, Deserializer, Serializer};
+        use std::str::FromStr;
+
+        use crate::PatientSex;
+
+        pub fn serialize<S>(value: &Option<PatientSex>, s: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            match value {
+                Some(value) => s.serialize_str(value.value()),
+                None => s.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D>(d: D) -> Result<Option<PatientSex>, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            let raw = String::deserialize(d)?;
+            PatientSex::from_str(&raw).map(Some).map_err(|_| {
+                de::Error::custom(format!(
+                    "`{}` isn't one of the values M, F, O or empty",
+                    raw
+                ))
+            })
+        }
+    }
+
+    /// Values that are written in their `FromStr`/`Display` notation.
+    pub mod value {
+        use serde::{de, Deserialize, Deserializer, Serializer};
+        use std::fmt::Display;
+        use std::str::FromStr;
+
+        pub fn serialize<S, T>(value: &Option<T>, s: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+            T: Display,
+        {
+            match value {
+                Some(value) => s.serialize_str(&value.to_string()),
+                None => s.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D, T>(d: D) -> Result<Option<T>, D::Error>
+        where
+            D: Deserializer<'de>,
+            T: FromStr,
+            T::Err: Display,
+        {
+            let raw = String::deserialize(d)?;
+            T::from_str(&raw).map(Some).map_err(de::Error::custom)
+        }
+    }
+
+    /// Lists of values that are written in their `FromStr`/`Display` notation.
+    pub mod list {
+        use serde::ser::SerializeSeq;
+        use serde::{de, Deserialize, Deserializer, Serializer};
+        use std::fmt::Display;
+        use std::str::FromStr;
+
+        pub fn serialize<S, T>(value: &Option<Vec<T>>, s: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+            T: Display,
+        {
+            match value {
+                Some(items) => {
+                    let mut seq = s.serialize_seq(Some(items.len()))?;
+                    for item in items {
+                        seq.serialize_element(&item.to_string())?;
+                    }
+                    seq.end()
+                }
+                None => s.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D, T>(d: D) -> Result<Option<Vec<T>>, D::Error>
+        where
+            D: Deserializer<'de>,
+            T: FromStr,
+            T::Err: Display,
+        {
+            // Parsed item by item, so errors point at the index of the invalid entry
+            struct Item<T>(T);
+
+            impl<'de, T> Deserialize<'de> for Item<T>
+            where
+                T: FromStr,
+                T::Err: Display,
+            {
+                fn deserialize<D>(d: D) -> Result<Self, D::Error>
+                where
+                    D: Deserializer<'de>,
+                {
+                    let raw = String::deserialize(d)?;
+                    T::from_str(&raw).map(Item).map_err(de::Error::custom)
+                }
+            }
+
+            let items = Vec::<Item<T>>::deserialize(d)?;
+            Ok(Some(items.into_iter().map(|item| item.0).collect()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ConfigFileV1, ConfigFileVersions};
+    use crate::types::CustomTag;
+    use crate::{AnonymizerMeta, PatientSex, Preset, TagAction};
+
+    #[test]
+    fn v1_uses_the_cli_notation() {
+        let cfv: ConfigFileVersions = serde_json::from_str(
+            r#"{"version":"1.1","config":{"patient_sex":"f","patient_birth_day":"2000-01-31","remove_tags":["0x0010-0x0020"]}}"#,
+        )
+        .unwrap();
+
+        let config = match &cfv {
+            ConfigFileVersions::V1_1 { config } => config,
+            _ => panic!("Should be version 1.1"),
+        };
+        assert_eq!(config.patient_sex, Some(PatientSex::F));
+        assert_eq!(
+            config.remove_tags,
+            Some(vec![CustomTag::new(0x0010, 0x0020)])
+        );
+        assert_eq!(
+            serde_json::to_string(&cfv).unwrap(),
+            r#"{"version":"1.1","config":{"patient_birth_day":"2000-01-31","patient_sex":"F","remove_tags":["0x0010-0x0020"]}}"#
+        );
+    }
+
+    #[test]
+    fn v2_is_an_anonymizer_meta() {
+        let cfv: ConfigFileVersions = serde_json::from_str(
+            r#"{"version":"2.0","config":{"patient_name":{"Change":"Anonymous"}}}"#,
+        )
+        .unwrap();
+
+        let meta: AnonymizerMeta =
+            serde_json::from_str(r#"{"patient_name":{"Change":"Anonymous"}}"#).unwrap();
+        assert_eq!(cfv, ConfigFileVersions::from(meta.clone()));
+        assert_eq!(cfv.meta(), meta);
+        assert_eq!(cfv.version(), "2.0");
+    }
+
+    #[test]
+    fn v1_maps_to_anonymizer_meta() {
+        let config = ConfigFileV1 {
+            patient_name: Some("Anonymous".to_string()),
+            ..Default::default()
+        };
+
+        let meta = ConfigFileVersions::V1_0 { config }.meta();
+        assert_eq!(
+            meta.patient_name,
+            TagAction::Change("Anonymous".to_string())
+        );
+        assert_eq!(meta.patient_sex, TagAction::Keep);
+    }
+
+    #[test]
+    fn filters_use_the_cli_notation_in_v1() {
+        let v1: ConfigFileVersions = serde_json::from_str(
+            r#"{"version":"1.1","config":{"filters":["skip:modality=SR,PR","copy"]}}"#,
+        )
+        .unwrap();
+        let v2: ConfigFileVersions = serde_json::from_str(
+            r#"{"version":"2.0","config":{"filters":[{"action":"Skip","modality":["SR","PR"]},{"action":"Copy"}]}}"#,
+        )
+        .unwrap();
+
+        assert_eq!(v1.meta(), v2.meta());
+        assert_eq!(v1.meta().filters()[0].to_string(), "skip:modality=SR,PR");
+    }
+
+    #[test]
+    fn rejects_unknown_fields() {
+        assert!(serde_json::from_str::<ConfigFileVersions>(
+            r#"{"version":"1.0","config":{"patient_nme":"Anonymous"}}"#
+        )
+        .is_err());
+        assert!(serde_json::from_str::<AnonymizerMeta>(r#"{"patient_nme":"Anonymous"}"#).is_err());
+    }
+
+    #[test]
+    fn patient_sex_map_uses_the_cli_notation_in_v1() {
+        let v1: ConfigFileVersions = serde_json::from_str(
+            r#"{"version":"1.1","config":{"patient_sex":"","patient_sex_map":"M=M,F=F,*="}}"#,
+        )
+        .unwrap();
+        let v2: ConfigFileVersions = serde_json::from_str(
+            r#"{"version":"2.0","config":{"patient_sex":{"Change":"Empty"},"patient_sex_map":{"M":"M","F":"F","*":"Empty"}}}"#,
+        )
+        .unwrap();
+
+        assert_eq!(v1.meta(), v2.meta());
+        assert_eq!(v1.meta().patient_sex_map().to_string(), "*=,F=F,M=M");
+    }
+
+    #[test]
+    fn age_settings_use_the_cli_notation_in_v1() {
+        let v1: ConfigFileVersions = serde_json::from_str(
+            r#"{"version":"1.1","config":{"generalize_birth_date":"january-first","patient_age":"recompute","max_patient_age":89}}"#,
+        )
+        .unwrap();
+        let v2: ConfigFileVersions = serde_json::from_str(
+            r#"{"version":"2.0","config":{"generalize_birth_date":"JanuaryFirst","patient_age":"Recompute","max_patient_age":89}}"#,
+        )
+        .unwrap();
+
+        assert_eq!(v1.meta(), v2.meta());
+        assert_eq!(v1.meta().max_patient_age(), Some(89));
+    }
+
+    #[test]
+    fn preset_uses_the_cli_notation_in_v1() {
+        let v1: ConfigFileVersions = serde_json::from_str(
+            r#"{"version":"1.1","config":{"preset":"hipaa-safe-harbor","patient_name":"Anonymous"}}"#,
+        )
+        .unwrap();
+        let v2: ConfigFileVersions = serde_json::from_str(
+            r#"{"version":"2.0","config":{"preset":"HipaaSafeHarbor","patient_name":{"Change":"Anonymous"}}}"#,
+        )
+        .unwrap();
+
+        assert_eq!(v1.meta(), v2.meta());
+        assert_eq!(v1.meta().preset(), Some(Preset::HipaaSafeHarbor));
+    }
+
+    #[test]
+    fn scrub_patterns_are_a_list_in_v1() {
+        let v1: ConfigFileVersions = serde_json::from_str(
+            r#"{"version":"1.1","config":{"scrub_text":true,"scrub_patterns":["\\d{3}-\\d{4}"]}}"#,
+        )
+        .unwrap();
+
+        let meta = v1.meta();
+        assert!(meta.scrub_text());
+        assert_eq!(meta.scrub_patterns()[0].to_string(), r"\d{3}-\d{4}");
+    }
+
+    #[test]
+    fn scripts_use_the_cli_notation_in_v1() {
+        let v1: ConfigFileVersions = serde_json::from_str(
+            r#"{"version":"1.1","config":{"scripts":["PatientID=rules/patient_id.rhai"]}}"#,
+        )
+        .unwrap();
+        let v2: ConfigFileVersions = serde_json::from_str(
+            r#"{"version":"2.0","config":{"scripts":[{"attribute":"PatientID","file":"rules/patient_id.rhai"}]}}"#,
+        )
+        .unwrap();
+
+        assert_eq!(v1.meta(), v2.meta());
+        assert_eq!(
+            v1.meta().scripts()[0].to_string(),
+            "PatientID=rules/patient_id.rhai"
+        );
+        assert!(serde_json::from_str::<ConfigFileVersions>(
+            r#"{"version":"1.1","config":{"scripts":["PatientIdentifier=id.rhai"]}}"#,
+        )
+        .is_err());
+    }
+}
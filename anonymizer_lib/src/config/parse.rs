@@ -0,0 +1,328 @@
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use serde_path_to_error::Segment;
+use std::fmt;
+
+use crate::config::{ConfigFileV1, ConfigFileVersions};
+use crate::AnonymizerMeta;
+
+const ROOT_KEYS: [&str; 2] = ["version", "config"];
+const CONFIG_KEY: &str = "config";
+
+/// A problem with one setting, `key` is its path in the document, e.g. `config.remove_tags[1]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldError {
+    pub key: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new<K, M>(key: K, message: M) -> Self
+    where
+        K: Into<String>,
+        M: Into<String>,
+    {
+        Self {
+            key: key.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.key.is_empty() {
+            true => write!(f, "{}", self.message),
+            false => write!(f, "`{}`: {}", self.key, self.message),
+        }
+    }
+}
+
+pub fn join_key(parent: &str, key: &str) -> String {
+    match parent.is_empty() {
+        true => key.to_string(),
+        false => format!("{}.{}", parent, key),
+    }
+}
+
+pub fn join_index(parent: &str, index: usize) -> String {
+    format!("{}[{}]", parent, index)
+}
+
+impl ConfigFileVersions {
+    /// Validates a parsed document against the versioned schema and reports every invalid
+    /// setting with its path, instead of stopping at the first one.
+    pub fn from_value(root: Value) -> Result<Self, Vec<FieldError>> {
+        let root = match root {
+            Value::Object(root) => root,
+            _ => {
+                return Err(vec![FieldError::new(
+                    "",
+                    "Has to be a map with a `config` section",
+                )])
+            }
+        };
+
+        let mut errors = Vec::new();
+        for key in root.keys() {
+            if !ROOT_KEYS.contains(&key.as_str()) {
+                errors.push(FieldError::new(
+                    key,
+                    format!("Unknown key, expected one of: {}", ROOT_KEYS.join(", ")),
+                ));
+            }
+        }
+
+        let version = match root.get("version") {
+            None => Some(ConfigFileVersions::DEFAULT),
+            Some(Value::String(version)) => Some(version.as_str()),
+            Some(_) => {
+                errors.push(FieldError::new(
+                    "version",
+                    "Has to be a string, e.g. \"1.1\"",
+                ));
+                None
+            }
+        };
+
+        let config = match root.get(CONFIG_KEY) {
+            Some(config @ Value::Object(_)) => Some(config.clone()),
+            Some(_) => {
+                errors.push(FieldError::new(CONFIG_KEY, "Has to be a map of settings"));
+                None
+            }
+            None => {
+                errors.push(FieldError::new("", "Missing `config` section"));
+                None
+            }
+        };
+
+        let parsed = match (version, config) {
+            (Some("1.0"), Some(config)) => {
+                deserialize::<ConfigFileV1>(CONFIG_KEY, config, &mut errors)
+                    .map(|config| ConfigFileVersions::V1_0 { config })
+            }
+            (Some("1.1"), Some(config)) => {
+                deserialize::<ConfigFileV1>(CONFIG_KEY, config, &mut errors)
+                    .map(|config| ConfigFileVersions::V1_1 { config })
+            }
+            (Some("2.0"), Some(config)) => {
+                deserialize::<AnonymizerMeta>(CONFIG_KEY, config, &mut errors)
+                    .map(|config| ConfigFileVersions::V2_0 { config })
+            }
+            (Some(other), _) if !ConfigFileVersions::VERSIONS.contains(&other) => {
+                errors.push(FieldError::new(
+                    "version",
+                    format!(
+                        "Unsupported version `{}`, expected one of: {}",
+                        other,
+                        ConfigFileVersions::VERSIONS.join(", ")
+                    ),
+                ));
+                None
+            }
+            _ => None,
+        };
+
+        match (parsed, errors.is_empty()) {
+            (Some(cfv), true) => Ok(cfv),
+            _ => Err(errors),
+        }
+    }
+}
+
+impl ConfigFileVersions {
+    /// `AnonymizerMeta::value_errors` with the paths of this version. Not part of `from_value`,
+    /// a later config layer may still change the values or the strictness.
+    pub fn value_errors(&self) -> Vec<FieldError> {
+        let errors = self.meta().value_errors();
+
+        match self {
+            // The values of version 1 are plain strings, named like the CLI flags
+            ConfigFileVersions::V1_0 { .. } | ConfigFileVersions::V1_1 { .. } => errors
+                .into_iter()
+                .map(|error| {
+                    let key = match error.key.trim_end_matches(".Change") {
+                        "patient_birth_date" => "patient_birth_day",
+                        key => key,
+                    };
+                    FieldError::new(join_key(CONFIG_KEY, key), error.message)
+                })
+                .collect(),
+            ConfigFileVersions::V2_0 { .. } => errors
+                .into_iter()
+                .map(|error| FieldError::new(join_key(CONFIG_KEY, &error.key), error.message))
+                .collect(),
+        }
+    }
+}
+
+impl AnonymizerMeta {
+    /// Like `ConfigFileVersions::from_value`, for a bare `AnonymizerMeta`.
+    pub fn from_value(value: Value) -> Result<Self, Vec<FieldError>> {
+        let mut errors = Vec::new();
+
+        match deserialize::<AnonymizerMeta>("", value, &mut errors) {
+            Some(meta) if errors.is_empty() => Ok(meta),
+            _ => Err(errors),
+        }
+    }
+}
+
+/// Deserializes `value` and keeps going after an error by dropping the offending entry, so every
+/// problem is reported at once.
+fn deserialize<T>(prefix: &str, mut value: Value, errors: &mut Vec<FieldError>) -> Option<T>
+where
+    T: DeserializeOwned,
+{
+    let mut failed = false;
+
+    loop {
+        match serde_path_to_error::deserialize::<_, T>(&value) {
+            Ok(parsed) => return (!failed).then_some(parsed),
+            Err(e) => {
+                failed = true;
+
+                let segments = e.path().iter().cloned().collect::<Vec<_>>();
+                let key = segments
+                    .iter()
+                    .fold(prefix.to_string(), |key, segment| match segment {
+                        Segment::Seq { index } => join_index(&key, *index),
+                        Segment::Map { key: name } => join_key(&key, name),
+                        Segment::Enum { variant } => join_key(&key, variant),
+                        Segment::Unknown => join_key(&key, "?"),
+                    });
+                // Removing an invalid field can make its parent incomplete, that follow-up error
+                // isn't worth reporting
+                let follow_up = errors.iter().any(|error| {
+                    error
+                        .key
+                        .strip_prefix(&key)
+                        .map(|rest| rest.starts_with('.') || rest.starts_with('['))
+                        .unwrap_or(false)
+                });
+                if !follow_up {
+                    errors.push(FieldError::new(key, e.inner().to_string()));
+                }
+
+                // An enum variant is part of the value it is found in, dropping that value is enough
+                let segments = segments
+                    .into_iter()
+                    .filter(|segment| !matches!(segment, Segment::Enum { .. }))
+                    .collect::<Vec<_>>();
+                if !remove(&mut value, &segments) {
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+/// Removes the entry at `segments`, returns `false` if there is nothing left to remove.
+fn remove(value: &mut Value, segments: &[Segment]) -> bool {
+    match segments {
+        [] => false,
+        [last] => match (value, last) {
+            (Value::Object(map), Segment::Map { key }) => map.remove(key).is_some(),
+            (Value::Array(items), Segment::Seq { index }) if *index < items.len() => {
+                items.remove(*index);
+                true
+            }
+            _ => false,
+        },
+        [first, rest @ ..] => {
+            let child = match (value, first) {
+                (Value::Object(map), Segment::Map { key }) => map.get_mut(key),
+                (Value::Array(items), Segment::Seq { index }) => items.get_mut(*index),
+                _ => None,
+            };
+            match child {
+                Some(child) => remove(child, rest),
+                None => false,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FieldError;
+    use crate::config::ConfigFileVersions;
+    use crate::{AnonymizerMeta, PatientSex, TagAction};
+    use serde_json::json;
+
+    #[test]
+    fn defaults_to_version_1_0() {
+        let cfv =
+            ConfigFileVersions::from_value(json!({"config": {"patient_name": "Name"}})).unwrap();
+
+        assert_eq!(cfv.version(), "1.0");
+    }
+
+    #[test]
+    fn rejects_unknown_versions_and_keys() {
+        let errors =
+            ConfigFileVersions::from_value(json!({"version": "9.9", "confgi": {}, "config": {}}))
+                .unwrap_err();
+
+        let keys = errors.iter().map(|e| e.key.as_str()).collect::<Vec<_>>();
+        assert_eq!(keys, vec!["confgi", "version"]);
+    }
+
+    #[test]
+    fn collects_all_errors_with_their_path() {
+        let errors = ConfigFileVersions::from_value(json!({
+            "version": "2.0",
+            "config": {
+                "patient_sex": {"Change": "X"},
+                "remove_tags": [{"group": 16, "element": 32}, {"group": "16"}]
+            }
+        }))
+        .unwrap_err();
+
+        let keys = errors.iter().map(|e| e.key.as_str()).collect::<Vec<_>>();
+        assert_eq!(
+            keys,
+            vec!["config.patient_sex.Change", "config.remove_tags[1].group"]
+        );
+    }
+
+    #[test]
+    fn parses_a_bare_meta() {
+        let meta = AnonymizerMeta::from_value(json!({"patient_sex": {"Change": "O"}})).unwrap();
+        assert_eq!(meta.patient_sex(), &TagAction::Change(PatientSex::O));
+
+        let errors = AnonymizerMeta::from_value(json!({"patient_nme": "Name"})).unwrap_err();
+        assert_eq!(errors[0].key, "patient_nme");
+        assert_eq!(
+            errors[0].to_string(),
+            format!("`patient_nme`: {}", errors[0].message)
+        );
+        assert_eq!(FieldError::new("", "Broken").to_string(), "Broken");
+    }
+
+    #[test]
+    fn rejects_values_that_break_their_vr() {
+        let name = "N".repeat(65);
+        let cfv = ConfigFileVersions::from_value(json!({
+            "version": "1.1",
+            "config": {"patient_name": name}
+        }))
+        .unwrap();
+        assert_eq!(cfv.value_errors()[0].key, "config.patient_name");
+
+        let cfv = ConfigFileVersions::from_value(json!({
+            "version": "2.0",
+            "config": {"patient_name": {"Change": "a^b^c^d^e^f"}}
+        }))
+        .unwrap();
+        assert_eq!(cfv.value_errors()[0].key, "config.patient_name.Change");
+
+        let meta = AnonymizerMeta::from_value(json!({
+            "patient_name": {"Change": name},
+            "value_strictness": "Coerce"
+        }))
+        .unwrap();
+        assert!(meta.value_errors().is_empty());
+    }
+}
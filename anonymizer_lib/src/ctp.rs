@@ -0,0 +1,331 @@
+//! Imports the scripts of the RSNA CTP DicomAnonymizer, so rules don't have to be written again.
+//!
+//! ```xml
+//! <script>
+//!   <e en="T" t="00100010" n="PatientName">ANONYMOUS</e>
+//!   <e en="T" t="00100030" n="PatientBirthDate">@remove()</e>
+//!   <e en="T" t="00100020" n="PatientID">@hash(this,10)</e>
+//! </script>
+//! ```
+//!
+//! Element rules that remove, empty or set a fixed value map to settings, `@keep()` only for
+//! Patient Name, Patient Birth Date and Patient Sex. Everything else, e.g. `@keep()` of other
+//! elements, `@hash()`, `@hashuid()`, `@incrementdate()` or the removal of private groups, is listed
+//! in `CtpImport::unmapped` and left out of the meta.
+use anyhow::{anyhow, bail, Result};
+use dicom_core::Tag;
+use dicom_dictionary_std::tags;
+use roxmltree::{Document, Node};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::enums::PatientSex;
+use crate::meta::{AnonymizerMeta, AnonymizerMetaBuilder};
+use crate::types::{CustomTag, DaValue};
+use crate::TagAction;
+
+/// A CTP script translated to a meta.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CtpImport {
+    pub meta: AnonymizerMeta,
+    /// Enabled rules of the script without an equivalent setting, in the order of the script
+    pub unmapped: Vec<Unmapped>,
+}
+
+/// A rule of the script that isn't part of the imported meta.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Unmapped {
+    /// The rule as a short text, e.g. `PatientID (0010,0020): @hash(this,10)`
+    pub rule: String,
+    pub reason: String,
+}
+
+impl fmt::Display for Unmapped {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.rule, self.reason)
+    }
+}
+
+/// What an element script does, as far as a setting can express it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Action {
+    Keep,
+    Remove,
+    Empty,
+    Value(String),
+    /// A function call or a combination of them, with the name of the first function
+    Function(String),
+}
+
+impl Action {
+    fn parse(script: &str) -> Self {
+        match script.trim() {
+            "@keep()" => Action::Keep,
+            "@remove()" => Action::Remove,
+            // An enabled rule without a script empties the element
+            "" | "@empty()" => Action::Empty,
+            script if !script.contains('@') => Action::Value(script.to_string()),
+            script => {
+                let name = script
+                    .split('@')
+                    .nth(1)
+                    .and_then(|call| call.split('(').next())
+                    .unwrap_or_default();
+                Action::Function(name.to_string())
+            }
+        }
+    }
+}
+
+/// Translates the CTP DicomAnonymizer script `xml`. Fails only if `xml` isn't a script, rules that
+/// can't be translated end up in `CtpImport::unmapped`.
+pub fn import(xml: &str) -> Result<CtpImport> {
+    let document = Document::parse(xml).map_err(|e| anyhow!("Invalid XML: {}", e))?;
+    let root = document.root_element();
+    if !root.has_tag_name("script") {
+        bail!(
+            "A CTP anonymizer script starts with <script>, not <{}>",
+            root.tag_name().name()
+        );
+    }
+
+    let mut builder = AnonymizerMetaBuilder::default();
+    let mut unmapped = Vec::new();
+
+    for node in root.children().filter(|node| node.is_element()) {
+        if node.attribute("en") == Some("F") {
+            continue;
+        }
+
+        let result = match node.tag_name().name() {
+            "e" => element(&node, &mut builder),
+            // Parameters are only used by functions, and every other group is kept anyway
+            "p" | "k" => Ok(()),
+            "r" => Err(format!(
+                "No setting removes {}",
+                node.attribute("t").unwrap_or("these elements")
+            )),
+            name => Err(format!("<{}> isn't a rule of CTP scripts", name)),
+        };
+
+        if let Err(reason) = result {
+            unmapped.push(Unmapped {
+                rule: describe(&node),
+                reason,
+            });
+        }
+    }
+
+    let meta = builder.build().map_err(|e| anyhow!(e))?;
+    Ok(CtpImport { meta, unmapped })
+}
+
+/// Applies an `<e>` rule to `builder`, or returns why it can't.
+fn element(node: &Node, builder: &mut AnonymizerMetaBuilder) -> Result<(), String> {
+    let tag = node
+        .attribute("t")
+        .and_then(parse_tag)
+        .ok_or("Only single tags like 00100010 are supported, no private or nested ones")?;
+    let action = Action::parse(node.text().unwrap_or_default());
+
+    match (tag, action) {
+        (_, Action::Function(name)) => Err(format!(
+            "@{}() has no equivalent setting, a script rule can compute the value",
+            name
+        )),
+
+        (tags::PATIENT_NAME, Action::Keep) => {
+            builder.patient_name(TagAction::Keep);
+            Ok(())
+        }
+        (tags::PATIENT_NAME, Action::Remove) => {
+            builder.patient_name(TagAction::Remove);
+            Ok(())
+        }
+        (tags::PATIENT_NAME, Action::Empty) => {
+            builder.patient_name(TagAction::Change(String::new()));
+            Ok(())
+        }
+        (tags::PATIENT_NAME, Action::Value(value)) => {
+            builder.patient_name(TagAction::Change(value));
+            Ok(())
+        }
+
+        (tags::PATIENT_SEX, Action::Keep) => {
+            builder.patient_sex(TagAction::Keep);
+            Ok(())
+        }
+        (tags::PATIENT_SEX, Action::Remove) => {
+            builder.patient_sex(TagAction::Remove);
+            Ok(())
+        }
+        (tags::PATIENT_SEX, Action::Empty) => {
+            builder.patient_sex(TagAction::Change(PatientSex::Empty));
+            Ok(())
+        }
+        (tags::PATIENT_SEX, Action::Value(value)) => {
+            let sex = PatientSex::from_str(&value).map_err(|e| e.to_string())?;
+            builder.patient_sex(TagAction::Change(sex));
+            Ok(())
+        }
+
+        (tags::PATIENT_BIRTH_DATE, Action::Keep) => {
+            builder.patient_birth_date(TagAction::Keep);
+            Ok(())
+        }
+        (tags::PATIENT_BIRTH_DATE, Action::Remove) => {
+            builder.patient_birth_date(TagAction::Remove);
+            Ok(())
+        }
+        (tags::PATIENT_BIRTH_DATE, Action::Empty) => {
+            Err("The birth date can't be emptied, only removed".to_string())
+        }
+        (tags::PATIENT_BIRTH_DATE, Action::Value(value)) => {
+            let date = DaValue::from_str(&value).map_err(|e| e.to_string())?;
+            builder.patient_birth_date(TagAction::Change(date));
+            Ok(())
+        }
+
+        (tag, Action::Remove) => {
+            builder.remove_tag(CustomTag::from(tag));
+            Ok(())
+        }
+        (_, Action::Keep) => Err(
+            "No setting keeps other elements, they are kept unless a setting changes them"
+                .to_string(),
+        ),
+        (_, Action::Empty | Action::Value(_)) => Err(
+            "Only Patient Name, Patient Birth Date and Patient Sex can get a new value".to_string(),
+        ),
+    }
+}
+
+/// `00100010` as a tag.
+fn parse_tag(value: &str) -> Option<Tag> {
+    let value = value.trim();
+    if value.len() != 8 {
+        return None;
+    }
+    let group = u16::from_str_radix(&value[..4], 16).ok()?;
+    let element = u16::from_str_radix(&value[4..], 16).ok()?;
+    Some(Tag(group, element))
+}
+
+fn describe(node: &Node) -> String {
+    let script = node.text().unwrap_or_default().trim();
+    match (node.tag_name().name(), node.attribute("t")) {
+        ("e", Some(t)) => {
+            let name = node.attribute("n").unwrap_or("Element");
+            match parse_tag(t) {
+                Some(tag) => format!("{} {}: {}", name, tag, script),
+                None => format!("{} {}: {}", name, t, script),
+            }
+        }
+        ("r", Some(t)) => format!("<r t=\"{}\">", t),
+        (name, _) => format!("<{}>", name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{import, Action};
+    use crate::enums::PatientSex;
+    use crate::types::{CustomTag, DaValue};
+    use crate::{MetaKey, TagAction};
+    use std::str::FromStr;
+
+    const SCRIPT: &str = r#"<script>
+  <p t="UIDROOT">1.2.840.4267.23</p>
+  <e en="T" t="00100010" n="PatientName">ANONYMOUS</e>
+  <e en="T" t="00100020" n="PatientID">@hash(this,10)</e>
+  <e en="T" t="00100030" n="PatientBirthDate">@remove()</e>
+  <e en="T" t="00100040" n="PatientSex"></e>
+  <e en="T" t="00080050" n="AccessionNumber">@remove()</e>
+  <e en="F" t="00081030" n="StudyDescription">@remove()</e>
+  <e en="T" t="00080060" n="Modality">@keep()</e>
+  <e en="T" t="0020000D" n="StudyInstanceUID">@hashuid(@UIDROOT,this)</e>
+  <e en="T" t="00080090" n="ReferringPhysicianName">@empty()</e>
+  <k en="T" t="0018">Keep group 0018</k>
+  <r en="T" t="privategroups">Remove private groups</r>
+</script>"#;
+
+    #[test]
+    fn maps_the_rules_with_an_equivalent_setting() {
+        let import = import(SCRIPT).unwrap();
+
+        assert_eq!(
+            import.meta.patient_name(),
+            &TagAction::Change("ANONYMOUS".to_string())
+        );
+        assert_eq!(import.meta.patient_birth_date(), &TagAction::Remove);
+        assert_eq!(
+            import.meta.patient_sex(),
+            &TagAction::Change(PatientSex::Empty)
+        );
+        assert_eq!(import.meta.remove_tags(), &[CustomTag::new(0x0008, 0x0050)]);
+    }
+
+    #[test]
+    fn reports_the_other_rules() {
+        let unmapped = import(SCRIPT)
+            .unwrap()
+            .unmapped
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            unmapped,
+            vec![
+                "PatientID (0010,0020): @hash(this,10): @hash() has no equivalent setting, a script rule can compute the value",
+                "Modality (0008,0060): @keep(): No setting keeps other elements, they are kept unless a setting changes them",
+                "StudyInstanceUID (0020,000D): @hashuid(@UIDROOT,this): @hashuid() has no equivalent setting, a script rule can compute the value",
+                "ReferringPhysicianName (0008,0090): @empty(): Only Patient Name, Patient Birth Date and Patient Sex can get a new value",
+                "<r t=\"privategroups\">: No setting removes privategroups",
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_the_patient_settings() {
+        let import = import(
+            r#"<script>
+  <e en="T" t="00100010" n="PatientName">@keep()</e>
+  <e en="T" t="00100030" n="PatientBirthDate">@keep()</e>
+  <e en="T" t="00100040" n="PatientSex">@keep()</e>
+</script>"#,
+        )
+        .unwrap();
+
+        assert_eq!(import.meta.patient_name(), &TagAction::Keep);
+        assert_eq!(import.meta.patient_birth_date(), &TagAction::Keep);
+        assert_eq!(import.meta.patient_sex(), &TagAction::Keep);
+        assert!(import.meta.is_set(MetaKey::PatientName));
+        assert!(import.unmapped.is_empty());
+    }
+
+    #[test]
+    fn parses_the_scripts_of_elements() {
+        assert_eq!(Action::parse(" @remove() "), Action::Remove);
+        assert_eq!(
+            Action::parse("Anonymous"),
+            Action::Value("Anonymous".to_string())
+        );
+        assert_eq!(
+            Action::parse("@always()@empty()"),
+            Action::Function("always".to_string())
+        );
+
+        let import = import(r#"<script><e en="T" t="00100030">19800101</e></script>"#).unwrap();
+        assert_eq!(
+            import.meta.patient_birth_date(),
+            &TagAction::Change(DaValue::from_str("19800101").unwrap())
+        );
+    }
+
+    #[test]
+    fn rejects_other_documents() {
+        assert!(import("<anonymizer/>").is_err());
+        assert!(import("<script>").is_err());
+    }
+}
@@ -0,0 +1,68 @@
+use derive_more::Display;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+use crate::types::PartialDate;
+
+/// How much of a kept Patient Birth Date is written back. Coarser dates still allow to compute
+/// an approximate age.
+#[derive(Display, Copy, Clone, Debug, Default, Serialize, Deserialize, Eq, PartialEq)]
+pub enum BirthDateGeneralization {
+    /// The date as it is
+    #[default]
+    Full,
+    /// Only the year, `19800612` becomes `1980`
+    Year,
+    /// January 1st of the year, `19800612` becomes `19800101`
+    JanuaryFirst,
+}
+
+impl BirthDateGeneralization {
+    pub fn is_full(&self) -> bool {
+        matches!(self, BirthDateGeneralization::Full)
+    }
+
+    pub fn apply(&self, date: PartialDate) -> PartialDate {
+        match self {
+            BirthDateGeneralization::Full => date,
+            BirthDateGeneralization::Year => date.year_only(),
+            BirthDateGeneralization::JanuaryFirst => date.january_first(),
+        }
+    }
+}
+
+impl FromStr for BirthDateGeneralization {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "full" => Ok(BirthDateGeneralization::Full),
+            "year" => Ok(BirthDateGeneralization::Year),
+            "january-first" | "januaryfirst" => Ok(BirthDateGeneralization::JanuaryFirst),
+            _ => Err(anyhow::anyhow!(
+                "`{}` isn't one of the generalizations full, year or january-first",
+                s
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BirthDateGeneralization;
+    use std::str::FromStr;
+
+    #[test]
+    fn from_str() {
+        assert_eq!(
+            BirthDateGeneralization::from_str("january-first").unwrap(),
+            BirthDateGeneralization::JanuaryFirst
+        );
+        assert_eq!(
+            BirthDateGeneralization::from_str(&BirthDateGeneralization::JanuaryFirst.to_string())
+                .unwrap(),
+            BirthDateGeneralization::JanuaryFirst
+        );
+        assert!(BirthDateGeneralization::from_str("month").is_err());
+    }
+}
@@ -0,0 +1,44 @@
+use derive_more::Display;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// What happens to a file in a batch run, decided by the first matching `FilterRule`.
+#[derive(Display, Copy, Clone, Debug, Default, Serialize, Deserialize, Eq, PartialEq)]
+pub enum FilterAction {
+    /// Anonymize the file and write it to the output
+    #[default]
+    Anonymize,
+    /// Write the file to the output unchanged
+    Copy,
+    /// Leave the file out of the output
+    Skip,
+}
+
+impl FromStr for FilterAction {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "anonymize" => Ok(FilterAction::Anonymize),
+            "copy" => Ok(FilterAction::Copy),
+            "skip" => Ok(FilterAction::Skip),
+            _ => Err(anyhow::anyhow!(
+                "`{}` isn't one of the actions anonymize, copy or skip",
+                s
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FilterAction;
+    use std::str::FromStr;
+
+    #[test]
+    fn from_str() {
+        assert_eq!(FilterAction::from_str("skip").unwrap(), FilterAction::Skip);
+        assert_eq!(FilterAction::from_str("Copy").unwrap(), FilterAction::Copy);
+        assert!(FilterAction::from_str("delete").is_err());
+    }
+}
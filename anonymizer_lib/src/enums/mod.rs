@@ -1,7 +1,19 @@
+mod birth_date_generalization;
+mod filter_action;
+mod non_dicom_policy;
+mod output_transfer_syntax;
+mod patient_age_action;
 mod patient_sex;
 mod remove_tags_input;
 mod tag_action;
+mod value_strictness;
 
+pub use birth_date_generalization::BirthDateGeneralization;
+pub use filter_action::FilterAction;
+pub use non_dicom_policy::NonDicomPolicy;
+pub use output_transfer_syntax::OutputTransferSyntax;
+pub use patient_age_action::PatientAgeAction;
 pub use patient_sex::PatientSex;
 pub use remove_tags_input::RemoveTagsInput;
 pub use tag_action::TagAction;
+pub use value_strictness::ValueStrictness;
@@ -0,0 +1,43 @@
+use derive_more::Display;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// What happens to the entries of an archive that aren't DICOM files.
+#[derive(Display, Copy, Clone, Debug, Default, Serialize, Deserialize, Eq, PartialEq)]
+pub enum NonDicomPolicy {
+    /// Leave the entry out of the output
+    #[default]
+    Skip,
+    /// Write the entry to the output unchanged
+    Copy,
+}
+
+impl FromStr for NonDicomPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "skip" => Ok(NonDicomPolicy::Skip),
+            "copy" => Ok(NonDicomPolicy::Copy),
+            _ => Err(anyhow::anyhow!(
+                "`{}` isn't one of the policies skip or copy",
+                s
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NonDicomPolicy;
+    use std::str::FromStr;
+
+    #[test]
+    fn from_str() {
+        assert_eq!(
+            NonDicomPolicy::from_str("Copy").unwrap(),
+            NonDicomPolicy::Copy
+        );
+        assert!(NonDicomPolicy::from_str("anonymize").is_err());
+    }
+}
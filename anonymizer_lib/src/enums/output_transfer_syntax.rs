@@ -0,0 +1,85 @@
+use derive_more::Display;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+use crate::transfer_syntax::{
+    DEFLATED_EXPLICIT_VR_LITTLE_ENDIAN, EXPLICIT_VR_LITTLE_ENDIAN, IMPLICIT_VR_LITTLE_ENDIAN,
+};
+
+/// The transfer syntax anonymized files are written with.
+#[derive(Display, Copy, Clone, Debug, Default, Serialize, Deserialize, Eq, PartialEq)]
+pub enum OutputTransferSyntax {
+    /// The transfer syntax of the input file
+    #[default]
+    Keep,
+    ExplicitVrLittleEndian,
+    ImplicitVrLittleEndian,
+    /// Explicit VR Little Endian with the dataset compressed by deflate
+    DeflatedExplicitVrLittleEndian,
+}
+
+impl OutputTransferSyntax {
+    pub fn is_keep(&self) -> bool {
+        matches!(self, OutputTransferSyntax::Keep)
+    }
+
+    /// The UID of the transfer syntax, `None` for `Keep`.
+    pub fn uid(&self) -> Option<&'static str> {
+        match self {
+            OutputTransferSyntax::Keep => None,
+            OutputTransferSyntax::ExplicitVrLittleEndian => Some(EXPLICIT_VR_LITTLE_ENDIAN),
+            OutputTransferSyntax::ImplicitVrLittleEndian => Some(IMPLICIT_VR_LITTLE_ENDIAN),
+            OutputTransferSyntax::DeflatedExplicitVrLittleEndian => {
+                Some(DEFLATED_EXPLICIT_VR_LITTLE_ENDIAN)
+            }
+        }
+    }
+}
+
+/// Parses the names of the CLI, `keep`, `explicit`, `implicit` or `deflated`, or a UID.
+impl FromStr for OutputTransferSyntax {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "keep" => Ok(OutputTransferSyntax::Keep),
+            "explicit" | "explicitvrlittleendian" | EXPLICIT_VR_LITTLE_ENDIAN => {
+                Ok(OutputTransferSyntax::ExplicitVrLittleEndian)
+            }
+            "implicit" | "implicitvrlittleendian" | IMPLICIT_VR_LITTLE_ENDIAN => {
+                Ok(OutputTransferSyntax::ImplicitVrLittleEndian)
+            }
+            "deflated" | "deflatedexplicitvrlittleendian" | DEFLATED_EXPLICIT_VR_LITTLE_ENDIAN => {
+                Ok(OutputTransferSyntax::DeflatedExplicitVrLittleEndian)
+            }
+            _ => Err(anyhow::anyhow!(
+                "`{}` isn't one of the transfer syntaxes keep, explicit, implicit or deflated",
+                s
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OutputTransferSyntax;
+    use std::str::FromStr;
+
+    #[test]
+    fn from_str() {
+        assert_eq!(
+            OutputTransferSyntax::from_str("Deflated").unwrap(),
+            OutputTransferSyntax::DeflatedExplicitVrLittleEndian
+        );
+        assert_eq!(
+            OutputTransferSyntax::from_str("1.2.840.10008.1.2").unwrap(),
+            OutputTransferSyntax::ImplicitVrLittleEndian
+        );
+        let syntax = OutputTransferSyntax::ExplicitVrLittleEndian;
+        assert_eq!(
+            OutputTransferSyntax::from_str(&syntax.to_string()).unwrap(),
+            syntax
+        );
+        assert!(OutputTransferSyntax::from_str("jpeg").is_err());
+    }
+}
@@ -0,0 +1,50 @@
+use derive_more::Display;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// What happens to Patient Age (0010,1010).
+#[derive(Display, Copy, Clone, Debug, Default, Serialize, Deserialize, Eq, PartialEq)]
+pub enum PatientAgeAction {
+    /// Leave the value of the file
+    #[default]
+    Keep,
+    /// Compute it from the original birth date and the study date, so it stays right when the
+    /// birth date is generalized or changed
+    Recompute,
+}
+
+impl PatientAgeAction {
+    pub fn is_keep(&self) -> bool {
+        matches!(self, PatientAgeAction::Keep)
+    }
+}
+
+impl FromStr for PatientAgeAction {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "keep" => Ok(PatientAgeAction::Keep),
+            "recompute" => Ok(PatientAgeAction::Recompute),
+            _ => Err(anyhow::anyhow!(
+                "`{}` isn't one of the actions keep or recompute",
+                s
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PatientAgeAction;
+    use std::str::FromStr;
+
+    #[test]
+    fn from_str() {
+        assert_eq!(
+            PatientAgeAction::from_str("Recompute").unwrap(),
+            PatientAgeAction::Recompute
+        );
+        assert!(PatientAgeAction::from_str("remove").is_err());
+    }
+}
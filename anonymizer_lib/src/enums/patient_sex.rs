@@ -3,25 +3,28 @@ use serde::{Deserialize, Serialize};
 use std::str::FromStr;
 use strum::EnumCount;
 
-#[derive(Display, Copy, Clone, Debug, EnumCount, Serialize, Deserialize, Eq, PartialEq)]
+#[derive(
+    Display, Copy, Clone, Debug, Default, EnumCount, Serialize, Deserialize, Eq, PartialEq,
+)]
 pub enum PatientSex {
     M,
     F,
     O,
+    /// Patient Sex is type 2, so it may be present without a value
+    #[default]
+    #[display(fmt = "")]
+    Empty,
 }
 
 impl FromStr for PatientSex {
     type Err = ::strum::ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        assert!(
-            PatientSex::COUNT == 3,
-            "TODO: implement new patient sex value"
-        );
-        match s.to_ascii_lowercase().as_str() {
+        match s.trim().to_ascii_lowercase().as_str() {
             "m" => Ok(PatientSex::M),
             "f" => Ok(PatientSex::F),
             "o" => Ok(PatientSex::O),
+            "" => Ok(PatientSex::Empty),
             _ => Err(::strum::ParseError::VariantNotFound),
         }
     }
@@ -33,13 +36,13 @@ impl PatientSex {
             PatientSex::M => "M",
             PatientSex::F => "F",
             PatientSex::O => "O",
+            PatientSex::Empty => "",
         }
     }
-}
 
-impl Default for PatientSex {
-    fn default() -> Self {
-        PatientSex::M
+    /// Whether a value of a file is one the standard defines, exactly as it is written there.
+    pub fn is_standard(value: &str) -> bool {
+        matches!(value.trim_end(), "M" | "F" | "O" | "")
     }
 }
 
@@ -60,21 +63,31 @@ mod tests {
             assert_eq!(PatientSex::M, PatientSex::from_str("m").unwrap());
             assert_eq!(PatientSex::F, PatientSex::from_str("f").unwrap());
             assert_eq!(PatientSex::O, PatientSex::from_str("o").unwrap());
+            assert_eq!(PatientSex::Empty, PatientSex::from_str("").unwrap());
         }
 
         #[test]
         fn error() {
-            let ps = PatientSex::from_str("MM");
-
-            match ps {
-                Ok(_) => assert!(false, "Should throw an error"),
-                Err(_) => assert!(true),
-            };
+            assert!(PatientSex::from_str("MM").is_err());
         }
     }
 
     #[test]
     fn enum_count() {
-        assert_eq!(PatientSex::COUNT, 3);
+        assert_eq!(PatientSex::COUNT, 4);
+    }
+
+    #[test]
+    fn default_is_empty() {
+        assert_eq!(PatientSex::default(), PatientSex::Empty);
+        assert_eq!(PatientSex::Empty.to_string(), "");
+    }
+
+    #[test]
+    fn is_standard() {
+        assert!(PatientSex::is_standard("M "));
+        assert!(PatientSex::is_standard(""));
+        assert!(!PatientSex::is_standard("m"));
+        assert!(!PatientSex::is_standard("MALE"));
     }
 }
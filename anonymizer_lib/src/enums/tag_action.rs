@@ -1,10 +1,11 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
 pub enum TagAction<T> {
     /// Change the tag with the given value, similar to `Option::Some(T)`
     Change(T),
     /// Changes nothing, similar to `Option::None`
+    #[default]
     Keep,
     /// Remove the tag
     Remove,
@@ -26,12 +27,6 @@ impl<T> TagAction<T> {
     }
 }
 
-impl<T> Default for TagAction<T> {
-    fn default() -> Self {
-        TagAction::Keep
-    }
-}
-
 impl<T> From<Option<T>> for TagAction<T> {
     fn from(opt: Option<T>) -> Self {
         match opt {
@@ -0,0 +1,50 @@
+use derive_more::Display;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// What happens to a `Change` value that breaks the rules of its VR, e.g. a person name with more
+/// than 64 characters.
+#[derive(Display, Copy, Clone, Debug, Default, Serialize, Deserialize, Eq, PartialEq)]
+pub enum ValueStrictness {
+    /// The config is refused before any file is written
+    #[default]
+    Reject,
+    /// The value is cut or fixed to fit and a warning tells what was written instead
+    Coerce,
+}
+
+impl ValueStrictness {
+    pub fn is_reject(&self) -> bool {
+        matches!(self, ValueStrictness::Reject)
+    }
+}
+
+impl FromStr for ValueStrictness {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "reject" => Ok(ValueStrictness::Reject),
+            "coerce" => Ok(ValueStrictness::Coerce),
+            _ => Err(anyhow::anyhow!(
+                "`{}` isn't one of the strictness levels reject or coerce",
+                s
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ValueStrictness;
+    use std::str::FromStr;
+
+    #[test]
+    fn from_str() {
+        assert_eq!(
+            ValueStrictness::from_str("Coerce").unwrap(),
+            ValueStrictness::Coerce
+        );
+        assert!(ValueStrictness::from_str("truncate").is_err());
+    }
+}
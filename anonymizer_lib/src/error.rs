@@ -0,0 +1,100 @@
+use dicom_core::value::CastValueError;
+use dicom_core::VR;
+use std::io;
+use thiserror::Error;
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Why an `Anonymizer` failed, to tell e.g. files that aren't DICOM from a full disk.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    /// Reading or writing the file failed.
+    #[error("{message}")]
+    Io {
+        message: String,
+        #[source]
+        source: io::Error,
+    },
+    /// The data isn't DICOM or can't be decoded or encoded.
+    #[error("Invalid DICOM data")]
+    Parse(#[source] Box<dyn std::error::Error + Send + Sync>),
+    #[error("Invalid config: {0}")]
+    InvalidConfig(String),
+    /// A method that needs a file was called on an `Anonymizer` without one.
+    #[error("No DICOM file is loaded")]
+    MissingObject,
+    /// A changed value doesn't follow the rules of the VR of its attribute.
+    #[error("The new {name} `{value}` isn't a valid {vr}: {problem}")]
+    VrViolation {
+        name: String,
+        value: String,
+        vr: VR,
+        problem: String,
+    },
+    #[error("Transfer syntax {0} isn't supported")]
+    UnsupportedTransferSyntax(String),
+    /// The file can't be written with the transfer syntax of the meta, e.g. compressed pixel data.
+    #[error("The transfer syntax {from} can't be converted to {to}: {reason}")]
+    TransferSyntaxConversion {
+        from: String,
+        to: String,
+        reason: String,
+    },
+    /// The input of a `StreamingAnonymizer` can't be streamed, or the stream was already finished.
+    #[error("The stream can't be anonymized: {0}")]
+    Stream(String),
+    /// The zip archive can't be read or written.
+    #[error("Invalid zip archive")]
+    Zip(#[source] zip::result::ZipError),
+}
+
+impl From<dicom_object::Error> for Error {
+    fn from(error: dicom_object::Error) -> Self {
+        use dicom_object::Error::*;
+
+        let message = error.to_string();
+        match error {
+            OpenFile { source, .. }
+            | ReadFile { source, .. }
+            | ReadPreambleBytes { source, .. }
+            | WriteFile { source, .. }
+            | WritePreamble { source, .. }
+            | WriteMagicCode { source, .. } => Error::Io { message, source },
+            UnsupportedTransferSyntax { uid, .. } => {
+                Error::UnsupportedTransferSyntax(uid.trim_end_matches('\0').to_string())
+            }
+            error => Error::Parse(Box::new(error)),
+        }
+    }
+}
+
+impl From<zip::result::ZipError> for Error {
+    fn from(error: zip::result::ZipError) -> Self {
+        Error::Zip(error)
+    }
+}
+
+impl From<CastValueError> for Error {
+    fn from(error: CastValueError) -> Self {
+        Error::Parse(Box::new(error))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Error;
+    use crate::Anonymizer;
+
+    #[test]
+    fn tells_missing_files_from_invalid_data() {
+        let error = Anonymizer::from_file("/does/not/exist.dcm").unwrap_err();
+        assert!(matches!(error, Error::Io { .. }));
+
+        let error = Anonymizer::from_bytes(b"not a DICOM file").unwrap_err();
+        assert!(matches!(error, Error::Parse(_)));
+
+        let error = Anonymizer::new().unwrap().anonymize().unwrap_err();
+        assert!(matches!(error, Error::MissingObject));
+    }
+}
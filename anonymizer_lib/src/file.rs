@@ -1,7 +1,368 @@
-use dicom_object::DefaultDicomObject;
+use dicom_core::{Tag, VR};
+use dicom_dictionary_std::tags;
+use dicom_encoding::transfer_syntax::TransferSyntaxIndex;
+use dicom_object::file::ReadPreamble;
+use dicom_object::meta::FileMetaTableBuilder;
+use dicom_object::{DefaultDicomObject, FileMetaTable, InMemDicomObject, OpenFileOptions};
+use dicom_transfer_syntax_registry::TransferSyntaxRegistry;
+use std::io::{Cursor, Read};
+
+use crate::error::{Error, Result};
+use crate::transfer_syntax::{EXPLICIT_VR_LITTLE_ENDIAN, IMPLICIT_VR_LITTLE_ENDIAN};
+
+/// Identifies dicom-tools as the implementation that wrote a file
+pub const IMPLEMENTATION_CLASS_UID: &str = "2.25.93759761208193703170254818482098682985";
+/// At most 16 characters, the VR is SH
+pub const IMPLEMENTATION_VERSION_NAME: &str = concat!("DICOMTOOLS_", env!("CARGO_PKG_VERSION"));
+
+/// The number of bytes `FileLayout::detect` needs at most: the preamble and the magic code.
+pub const LAYOUT_HEAD_LENGTH: usize = 132;
+
+/// How the DICOM data of a file starts. Modalities and old archives don't always write the
+/// preamble and the file meta group of part 10.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum FileLayout {
+    /// A 128 byte preamble with any content, `DICM` and the file meta group
+    Preamble,
+    /// `DICM` and the file meta group without the preamble
+    NoPreamble,
+    /// The file meta group without preamble and `DICM`
+    NoMagicCode,
+    /// A bare dataset in Little Endian without file meta group
+    Dataset { explicit_vr: bool },
+}
+
+impl FileLayout {
+    /// Detects the layout from the first `LAYOUT_HEAD_LENGTH` bytes of a file, `None` if it
+    /// doesn't look like DICOM.
+    ///
+    /// Elements are sorted by tag and every IOD has a SOP Class UID (0008,0016), so the first
+    /// element of a bare dataset is in group 0002 or 0008.
+    pub fn detect(head: &[u8]) -> Option<Self> {
+        if head.get(128..132) == Some(b"DICM") {
+            return Some(FileLayout::Preamble);
+        }
+        if head.starts_with(b"DICM") {
+            return Some(FileLayout::NoPreamble);
+        }
+
+        let header = head.get(..8)?;
+        let group = u16::from_le_bytes([header[0], header[1]]);
+        let explicit_vr = VR::from_binary([header[4], header[5]]).is_some();
+        match (group, explicit_vr) {
+            (0x0002, true) => Some(FileLayout::NoMagicCode),
+            (0x0008, true) => Some(FileLayout::Dataset { explicit_vr: true }),
+            (0x0008, false) => {
+                // The first elements of group 0008 are short, and values have an even length
+                let length = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+                (length % 2 == 0 && length <= 0xFFFF)
+                    .then_some(FileLayout::Dataset { explicit_vr: false })
+            }
+            _ => None,
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct AnonymizerFile {
     pub(crate) obj: DefaultDicomObject,
     pub(crate) updated_obj: bool,
 }
+
+/// Reads a DICOM file in any `FileLayout`. A bare dataset gets a file meta group with the SOP
+/// class and instance UIDs of the dataset.
+pub(crate) fn read(data: &[u8]) -> Result<DefaultDicomObject> {
+    let open = |preamble, from: &mut dyn Read| {
+        OpenFileOptions::new()
+            .read_preamble(preamble)
+            .from_reader(from)
+            .map_err(Error::from)
+    };
+
+    match FileLayout::detect(data) {
+        Some(FileLayout::Preamble) => open(ReadPreamble::Always, &mut Cursor::new(data)),
+        Some(FileLayout::NoMagicCode) => {
+            open(ReadPreamble::Never, &mut Cursor::new(b"DICM").chain(data))
+        }
+        Some(FileLayout::Dataset { explicit_vr }) => {
+            let uid = match explicit_vr {
+                true => EXPLICIT_VR_LITTLE_ENDIAN,
+                false => IMPLICIT_VR_LITTLE_ENDIAN,
+            };
+            let ts = TransferSyntaxRegistry
+                .get(uid)
+                .expect("Little Endian transfer syntaxes are built in");
+            let dataset = InMemDicomObject::read_dataset_with_ts(data, ts)?;
+            let uid_of = |tag: Tag| {
+                dataset
+                    .element(tag)
+                    .ok()
+                    .and_then(|element| element.to_str().ok())
+                    .map(|value| trim(&value))
+                    .unwrap_or_default()
+            };
+            let meta = FileMetaTableBuilder::new()
+                .media_storage_sop_class_uid(uid_of(tags::SOP_CLASS_UID))
+                .media_storage_sop_instance_uid(uid_of(tags::SOP_INSTANCE_UID))
+                .transfer_syntax(uid)
+                .implementation_class_uid(IMPLEMENTATION_CLASS_UID);
+            Ok(dataset.with_meta(meta)?)
+        }
+        // Without a layout the parser reports what's wrong
+        Some(FileLayout::NoPreamble) | None => open(ReadPreamble::Never, &mut Cursor::new(data)),
+    }
+}
+
+/// A builder with every value of `meta` but the group length, which is computed again when
+/// the table is built, so values can change their length.
+pub(crate) fn meta_builder(meta: &FileMetaTable) -> FileMetaTableBuilder {
+    let mut builder = FileMetaTableBuilder::new()
+        .information_version(meta.information_version)
+        .media_storage_sop_class_uid(trim(&meta.media_storage_sop_class_uid))
+        .media_storage_sop_instance_uid(trim(&meta.media_storage_sop_instance_uid))
+        .transfer_syntax(trim(&meta.transfer_syntax))
+        .implementation_class_uid(trim(&meta.implementation_class_uid));
+
+    if let Some(value) = &meta.implementation_version_name {
+        builder = builder.implementation_version_name(trim(value));
+    }
+    if let Some(value) = &meta.source_application_entity_title {
+        builder = builder.source_application_entity_title(trim(value));
+    }
+    if let Some(value) = &meta.sending_application_entity_title {
+        builder = builder.sending_application_entity_title(trim(value));
+    }
+    if let Some(value) = &meta.receiving_application_entity_title {
+        builder = builder.receiving_application_entity_title(trim(value));
+    }
+    if let Some(value) = &meta.private_information_creator_uid {
+        builder = builder.private_information_creator_uid(trim(value));
+    }
+    if let Some(value) = &meta.private_information {
+        builder = builder.private_information(value.clone());
+    }
+
+    builder
+}
+
+/// The file meta group a file is written with. The SOP class and instance UIDs are taken from
+/// the dataset, which may have been anonymized, and the implementation is dicom-tools. AE titles
+/// and private information describe the systems the original file passed through and are left
+/// out.
+pub(crate) fn written_meta(obj: &DefaultDicomObject) -> Result<FileMetaTable> {
+    let meta = obj.meta();
+    let uid = |tag: Tag, original: &str| {
+        obj.element(tag)
+            .ok()
+            .and_then(|element| element.to_str().ok())
+            .map(|value| trim(&value))
+            .filter(|value| !value.is_empty())
+            .unwrap_or_else(|| trim(original))
+    };
+
+    FileMetaTableBuilder::new()
+        .information_version(meta.information_version)
+        .media_storage_sop_class_uid(uid(tags::SOP_CLASS_UID, &meta.media_storage_sop_class_uid))
+        .media_storage_sop_instance_uid(uid(
+            tags::SOP_INSTANCE_UID,
+            &meta.media_storage_sop_instance_uid,
+        ))
+        .transfer_syntax(trim(&meta.transfer_syntax))
+        .implementation_class_uid(IMPLEMENTATION_CLASS_UID)
+        .implementation_version_name(IMPLEMENTATION_VERSION_NAME)
+        .build()
+        .map_err(|e| Error::Parse(Box::new(e)))
+}
+
+fn trim(value: &str) -> String {
+    value.trim_end_matches(['\0', ' ']).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FileLayout, IMPLEMENTATION_CLASS_UID, IMPLEMENTATION_VERSION_NAME};
+    use crate::hook::{Decision, ElementContext, ElementHook};
+    use crate::Anonymizer;
+    use dicom_core::{DataElement, PrimitiveValue, VR};
+    use dicom_dictionary_std::tags;
+    use dicom_encoding::transfer_syntax::TransferSyntaxIndex;
+    use dicom_object::file::ReadPreamble;
+    use dicom_object::{FileMetaTableBuilder, InMemDicomObject, OpenFileOptions};
+    use dicom_transfer_syntax_registry::TransferSyntaxRegistry;
+    use std::io::Cursor;
+
+    fn dataset() -> InMemDicomObject {
+        let mut obj = InMemDicomObject::new_empty();
+        obj.put(DataElement::new(
+            tags::SOP_CLASS_UID,
+            VR::UI,
+            PrimitiveValue::from("1.2.840.10008.5.1.4.1.1.2"),
+        ));
+        obj.put(DataElement::new(
+            tags::SOP_INSTANCE_UID,
+            VR::UI,
+            PrimitiveValue::from("1.2.3.4"),
+        ));
+        obj.put(DataElement::new(
+            tags::PATIENT_NAME,
+            VR::PN,
+            PrimitiveValue::from("Doe^John"),
+        ));
+        obj
+    }
+
+    fn bare_dataset(transfer_syntax: &str) -> Vec<u8> {
+        let ts = TransferSyntaxRegistry.get(transfer_syntax).unwrap();
+        let mut data = Vec::new();
+        dataset().write_dataset_with_ts(&mut data, ts).unwrap();
+        data
+    }
+
+    /// The patient name and the transfer syntax the file was read with
+    fn read(data: &[u8]) -> (String, String) {
+        let any = Anonymizer::from_bytes(data).unwrap();
+        let name = any
+            .elements()
+            .unwrap()
+            .into_iter()
+            .find(|element| element.keyword == "PatientName")
+            .unwrap();
+        (name.value, any.transfer_syntax().unwrap().to_string())
+    }
+
+    /// Remaps the SOP Instance UID, which leaves the one in the file meta group behind
+    struct NewInstanceUid;
+
+    impl ElementHook for NewInstanceUid {
+        fn decide(&self, element: &ElementContext) -> Decision {
+            match element.element.header().tag {
+                tags::SOP_INSTANCE_UID => Decision::Change("2.25.42".to_string()),
+                _ => Decision::Pass,
+            }
+        }
+    }
+
+    #[test]
+    fn writes_a_meta_group_for_the_anonymized_file() {
+        let mut obj = InMemDicomObject::new_empty();
+        obj.put(DataElement::new(
+            tags::SOP_INSTANCE_UID,
+            VR::UI,
+            PrimitiveValue::from("1.2.3.4"),
+        ));
+        let obj = obj
+            .with_meta(
+                FileMetaTableBuilder::new()
+                    .transfer_syntax("1.2.840.10008.1.2.1")
+                    .media_storage_sop_class_uid("1.2.840.10008.5.1.4.1.1.2")
+                    .implementation_class_uid("1.2.276.0.7230010.3.0.3.6.4")
+                    .implementation_version_name("OFFIS_DCMTK_364")
+                    .source_application_entity_title("HOSPITAL_CT1")
+                    .sending_application_entity_title("HOSPITAL_PACS"),
+            )
+            .unwrap();
+        let mut any = Anonymizer::from_object(obj).unwrap();
+        any.add_hook(NewInstanceUid);
+        any.anonymize().unwrap();
+
+        let written = OpenFileOptions::new()
+            .read_preamble(ReadPreamble::Always)
+            .from_reader(Cursor::new(any.to_bytes().unwrap()))
+            .unwrap();
+        let meta = written.meta();
+        let trim = |value: &str| value.trim_end_matches(['\0', ' ']).to_string();
+
+        assert_eq!(trim(&meta.media_storage_sop_instance_uid), "2.25.42");
+        assert_eq!(
+            trim(&meta.media_storage_sop_class_uid),
+            "1.2.840.10008.5.1.4.1.1.2"
+        );
+        assert_eq!(
+            trim(&meta.implementation_class_uid),
+            IMPLEMENTATION_CLASS_UID
+        );
+        assert_eq!(
+            meta.implementation_version_name.as_deref().map(trim),
+            Some(IMPLEMENTATION_VERSION_NAME.to_string())
+        );
+        assert_eq!(meta.source_application_entity_title, None);
+        assert_eq!(meta.sending_application_entity_title, None);
+    }
+
+    #[test]
+    fn detects_the_layout() {
+        let mut head = vec![0xFF; 128];
+        head.extend_from_slice(b"DICM");
+        assert_eq!(FileLayout::detect(&head), Some(FileLayout::Preamble));
+        assert_eq!(
+            FileLayout::detect(b"DICM\x02\x00\x00\x00UL"),
+            Some(FileLayout::NoPreamble)
+        );
+        assert_eq!(
+            FileLayout::detect(b"\x02\x00\x00\x00UL\x04\x00"),
+            Some(FileLayout::NoMagicCode)
+        );
+        assert_eq!(
+            FileLayout::detect(b"\x08\x00\x05\x00CS\x0A\x00"),
+            Some(FileLayout::Dataset { explicit_vr: true })
+        );
+        assert_eq!(
+            FileLayout::detect(b"\x08\x00\x16\x00\x1A\x00\x00\x00"),
+            Some(FileLayout::Dataset { explicit_vr: false })
+        );
+
+        assert_eq!(FileLayout::detect(b"PK\x03\x04\x14\x00\x00\x00"), None);
+        assert_eq!(
+            FileLayout::detect(b"\x08\x00\x16\x00\x1B\x00\x00\x00"),
+            None
+        );
+        assert_eq!(FileLayout::detect(b"\x08\x00"), None);
+    }
+
+    #[test]
+    fn reads_every_layout() {
+        let obj = dataset()
+            .with_meta(
+                FileMetaTableBuilder::new()
+                    .transfer_syntax("1.2.840.10008.1.2.1")
+                    .media_storage_sop_class_uid("1.2.840.10008.5.1.4.1.1.2")
+                    .media_storage_sop_instance_uid("1.2.3.4"),
+            )
+            .unwrap();
+        let mut data = Anonymizer::from_object(obj).unwrap().to_bytes().unwrap();
+        // TIFF compatible files keep a TIFF header in the preamble
+        data[..4].copy_from_slice(b"II*\0");
+        let explicit = ("Doe^John".to_string(), "1.2.840.10008.1.2.1".to_string());
+
+        assert_eq!(read(&data), explicit);
+        assert_eq!(read(&data[128..]), explicit);
+        assert_eq!(read(&data[132..]), explicit);
+        assert_eq!(read(&bare_dataset("1.2.840.10008.1.2.1")), explicit);
+        assert_eq!(
+            read(&bare_dataset("1.2.840.10008.1.2")),
+            ("Doe^John".to_string(), "1.2.840.10008.1.2".to_string())
+        );
+
+        // The file meta group of a bare dataset comes from its UIDs
+        let data = Anonymizer::from_bytes(&bare_dataset("1.2.840.10008.1.2"))
+            .unwrap()
+            .to_bytes()
+            .unwrap();
+        let written = OpenFileOptions::new()
+            .read_preamble(ReadPreamble::Always)
+            .from_reader(Cursor::new(data))
+            .unwrap();
+        assert_eq!(
+            written
+                .meta()
+                .media_storage_sop_instance_uid
+                .trim_end_matches('\0'),
+            "1.2.3.4"
+        );
+    }
+
+    #[test]
+    fn version_name_fits_its_vr() {
+        assert!(IMPLEMENTATION_VERSION_NAME.len() <= 16);
+    }
+}
@@ -0,0 +1,150 @@
+use dicom_core::value::Value;
+use dicom_core::{DataElement, Tag};
+use dicom_object::mem::InMemElement;
+use dicom_object::InMemDicomObject;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::inspect::path;
+
+/// What a hook wants to happen to an element.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Decision {
+    /// Leave the element to the next hook and the built-in rules
+    Pass,
+    /// Keep the original element, even if a built-in rule changes or removes it
+    Keep,
+    /// Write this value instead, it has to follow the rules of the VR of the element
+    Change(String),
+    Remove,
+}
+
+/// An element of the loaded dataset as the hooks see it, before any change.
+#[derive(Debug, Clone, Copy)]
+pub struct ElementContext<'a> {
+    /// Unique position in the dataset, e.g. `ReferencedImageSequence[0].ReferencedSOPInstanceUID`
+    pub path: &'a str,
+    pub element: &'a InMemElement,
+    /// The whole dataset, e.g. to look at the institution of the file
+    pub original: &'a InMemDicomObject,
+}
+
+/// Custom logic for rules that can't be written in a config, e.g. a lookup in a patient index.
+/// `decide` is called for every element of the dataset, including the ones inside sequences.
+/// The first hook that doesn't pass decides, its decision wins over the built-in rules.
+pub trait ElementHook {
+    fn decide(&self, element: &ElementContext) -> Decision;
+}
+
+/// The hooks registered on an `Anonymizer`, in order.
+#[derive(Clone, Default)]
+pub(crate) struct Hooks(Vec<Arc<dyn ElementHook>>);
+
+impl fmt::Debug for Hooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Hooks({})", self.0.len())
+    }
+}
+
+/// A decision for one element, taken on the original dataset and applied after the built-in
+/// rules.
+#[derive(Debug)]
+pub(crate) struct Planned {
+    /// The sequences and items leading to the element
+    pub(crate) steps: Vec<(Tag, usize)>,
+    pub(crate) path: String,
+    /// The original element
+    pub(crate) element: InMemElement,
+    pub(crate) decision: Decision,
+}
+
+impl Hooks {
+    pub(crate) fn push(&mut self, hook: Arc<dyn ElementHook>) {
+        self.0.push(hook);
+    }
+
+    /// The decisions of the hooks for every element of `original` that one of them doesn't pass.
+    pub(crate) fn plan(&self, original: &InMemDicomObject) -> Vec<Planned> {
+        let mut plan = Vec::new();
+        if !self.0.is_empty() {
+            self.walk(original, original, &[], "", &mut plan);
+        }
+        plan
+    }
+
+    fn walk(
+        &self,
+        dataset: &InMemDicomObject,
+        original: &InMemDicomObject,
+        steps: &[(Tag, usize)],
+        parent: &str,
+        plan: &mut Vec<Planned>,
+    ) {
+        for element in dataset {
+            let tag = element.header().tag;
+            let path = path(parent, tag);
+            let context = ElementContext {
+                path: &path,
+                element,
+                original,
+            };
+
+            let decision = self
+                .0
+                .iter()
+                .map(|hook| hook.decide(&context))
+                .find(|decision| *decision != Decision::Pass);
+            if let Some(decision) = decision {
+                plan.push(Planned {
+                    steps: steps.to_vec(),
+                    path: path.clone(),
+                    element: element.clone(),
+                    decision,
+                });
+            }
+
+            if let Value::Sequence { items, .. } = element.value() {
+                for (index, item) in items.iter().enumerate() {
+                    let steps = [steps, &[(tag, index)]].concat();
+                    let parent = format!("{}[{}]", path, index);
+                    self.walk(item, original, &steps, &parent, plan);
+                }
+            }
+        }
+    }
+}
+
+/// Runs `change` on the item `steps` lead to. Returns false if the built-in rules removed the
+/// item or one of its parents.
+pub(crate) fn edit(
+    dataset: &mut InMemDicomObject,
+    steps: &[(Tag, usize)],
+    change: impl FnOnce(&mut InMemDicomObject),
+) -> bool {
+    let (&(tag, index), rest) = match steps.split_first() {
+        Some(first) => first,
+        None => {
+            change(dataset);
+            return true;
+        }
+    };
+
+    let element = match dataset.take_element(tag) {
+        Ok(element) => element,
+        Err(_) => return false,
+    };
+    let (header, value) = (*element.header(), element.into_value());
+    let (value, found) = match value {
+        Value::Sequence { mut items, size } => {
+            let found = match items.get_mut(index) {
+                Some(item) => edit(item, rest, change),
+                None => false,
+            };
+            (Value::Sequence { items, size }, found)
+        }
+        value => (value, false),
+    };
+    dataset.put(DataElement::new(tag, header.vr, value));
+
+    found
+}
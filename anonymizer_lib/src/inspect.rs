@@ -0,0 +1,257 @@
+use dicom_core::dictionary::{DataDictionary, DictionaryEntry};
+use dicom_core::{DicomValue, Tag, VR};
+use dicom_dictionary_std::StandardDataDictionary;
+use dicom_object::InMemDicomObject;
+use serde::Serialize;
+
+/// One element of a dataset, flattened for display.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ElementInfo {
+    /// e.g. `(0010,0010)`
+    pub tag: String,
+    /// e.g. `PatientName`, empty for private and unknown tags
+    pub keyword: String,
+    pub vr: String,
+    /// The value as text, binary values are summarized by their length
+    pub value: String,
+    /// Unique position in the dataset, e.g. `ReferencedImageSequence[0].ReferencedSOPInstanceUID`
+    pub path: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ChangeKind {
+    Changed,
+    Removed,
+    Added,
+}
+
+/// Difference of one element between the original and the anonymized dataset.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ElementChange {
+    pub tag: String,
+    pub keyword: String,
+    pub path: String,
+    pub kind: ChangeKind,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+/// Every element of `obj` including the ones inside sequences, in dataset order.
+pub fn elements(obj: &InMemDicomObject) -> Vec<ElementInfo> {
+    let mut list = Vec::new();
+    collect(obj, "", &mut list);
+    list
+}
+
+fn collect(obj: &InMemDicomObject, parent: &str, list: &mut Vec<ElementInfo>) {
+    for element in obj {
+        let tag = element.header().tag;
+        let path = path(parent, tag);
+
+        let value = match element.value() {
+            DicomValue::Primitive(value) => match element.vr() {
+                VR::OB | VR::OD | VR::OF | VR::OL | VR::OV | VR::OW | VR::UN => {
+                    format!("<{} bytes>", value.calculate_byte_len())
+                }
+                _ => value.to_str().trim_end_matches([' ', '\0']).to_string(),
+            },
+            DicomValue::Sequence { items, .. } => format!("<{} items>", items.len()),
+            DicomValue::PixelSequence { fragments, .. } => {
+                format!("<{} fragments>", fragments.len())
+            }
+        };
+
+        list.push(ElementInfo {
+            tag: tag.to_string(),
+            keyword: keyword(tag),
+            vr: String::from(element.vr().to_string()),
+            value,
+            path: path.clone(),
+        });
+
+        if let DicomValue::Sequence { items, .. } = element.value() {
+            for (index, item) in items.iter().enumerate() {
+                collect(item, &format!("{}[{}]", path, index), list);
+            }
+        }
+    }
+}
+
+/// Path of `tag` below `parent`, by keyword or by tag for private and unknown tags.
+pub(crate) fn path(parent: &str, tag: Tag) -> String {
+    let keyword = keyword(tag);
+    let name = match keyword.is_empty() {
+        true => tag.to_string(),
+        false => keyword,
+    };
+    match parent.is_empty() {
+        true => name,
+        false => format!("{}.{}", parent, name),
+    }
+}
+
+fn keyword(tag: Tag) -> String {
+    StandardDataDictionary
+        .by_tag(tag)
+        .map(|entry| entry.alias().to_string())
+        .unwrap_or_default()
+}
+
+/// Every element that differs between `before` and `after`, matched by path.
+pub fn diff(before: &[ElementInfo], after: &[ElementInfo]) -> Vec<ElementChange> {
+    let change =
+        |info: &ElementInfo, kind, before: Option<&ElementInfo>, after: Option<&ElementInfo>| {
+            ElementChange {
+                tag: info.tag.clone(),
+                keyword: info.keyword.clone(),
+                path: info.path.clone(),
+                kind,
+                before: before.map(|item| item.value.clone()),
+                after: after.map(|item| item.value.clone()),
+            }
+        };
+    let find =
+        |list: &'_ [ElementInfo], path: &str| list.iter().find(|item| item.path == path).cloned();
+
+    let mut changes = Vec::new();
+    for old in before {
+        match find(after, &old.path) {
+            None => changes.push(change(old, ChangeKind::Removed, Some(old), None)),
+            Some(new) if new.value != old.value || new.vr != old.vr => {
+                changes.push(change(old, ChangeKind::Changed, Some(old), Some(&new)))
+            }
+            Some(_) => (),
+        }
+    }
+    for new in after {
+        if find(before, &new.path).is_none() {
+            changes.push(change(new, ChangeKind::Added, None, Some(new)));
+        }
+    }
+
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff, elements, ChangeKind};
+    use dicom_core::value::Value;
+    use dicom_core::{DataElement, Length, PrimitiveValue, VR};
+    use dicom_dictionary_std::tags;
+    use dicom_object::InMemDicomObject;
+
+    fn factory() -> InMemDicomObject {
+        let mut item = InMemDicomObject::new_empty();
+        item.put(DataElement::new(
+            tags::REFERENCED_SOP_INSTANCE_UID,
+            VR::UI,
+            PrimitiveValue::from("1.2.3\0"),
+        ));
+
+        let mut obj = InMemDicomObject::new_empty();
+        obj.put(DataElement::new(
+            tags::PATIENT_NAME,
+            VR::PN,
+            PrimitiveValue::from("Doe^John"),
+        ));
+        obj.put(DataElement::new(
+            tags::PATIENT_ID,
+            VR::LO,
+            PrimitiveValue::from("12345 "),
+        ));
+        obj.put(DataElement::new(
+            tags::REFERENCED_IMAGE_SEQUENCE,
+            VR::SQ,
+            Value::Sequence {
+                items: vec![item].into(),
+                size: Length::UNDEFINED,
+            },
+        ));
+        obj.put(DataElement::new(
+            tags::PIXEL_DATA,
+            VR::OW,
+            PrimitiveValue::U16(vec![0; 8].into()),
+        ));
+        obj
+    }
+
+    #[test]
+    fn lists_nested_elements() {
+        let list = elements(&factory());
+        let rows = list
+            .iter()
+            .map(|e| (e.path.as_str(), e.vr.as_str(), e.value.as_str()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            rows,
+            vec![
+                ("ReferencedImageSequence", "SQ", "<1 items>"),
+                (
+                    "ReferencedImageSequence[0].ReferencedSOPInstanceUID",
+                    "UI",
+                    "1.2.3"
+                ),
+                ("PatientName", "PN", "Doe^John"),
+                ("PatientID", "LO", "12345"),
+                ("PixelData", "OW", "<16 bytes>"),
+            ]
+        );
+        assert_eq!(list[2].tag, "(0010,0010)");
+    }
+
+    #[test]
+    fn diffs_by_path() {
+        let before = factory();
+        let mut after = factory();
+        after.put(DataElement::new(
+            tags::PATIENT_NAME,
+            VR::PN,
+            PrimitiveValue::from("Anonymous"),
+        ));
+        after.remove_element(tags::PATIENT_ID);
+        after.put(DataElement::new(
+            tags::PATIENT_IDENTITY_REMOVED,
+            VR::CS,
+            PrimitiveValue::from("YES"),
+        ));
+
+        let changes = diff(&elements(&before), &elements(&after));
+        let rows = changes
+            .iter()
+            .map(|c| {
+                (
+                    c.keyword.as_str(),
+                    c.kind,
+                    c.before.as_deref(),
+                    c.after.as_deref(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            rows,
+            vec![
+                (
+                    "PatientName",
+                    ChangeKind::Changed,
+                    Some("Doe^John"),
+                    Some("Anonymous")
+                ),
+                ("PatientID", ChangeKind::Removed, Some("12345"), None),
+                (
+                    "PatientIdentityRemoved",
+                    ChangeKind::Added,
+                    None,
+                    Some("YES")
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn unchanged_datasets_have_no_diff() {
+        let list = elements(&factory());
+        assert!(diff(&list, &list).is_empty());
+    }
+}
@@ -4,13 +4,31 @@ extern crate derive_builder;
 extern crate derive_more;
 
 mod anonymizer;
+mod charset;
 mod enums;
+mod error;
 mod file;
 mod meta;
+mod pseudonyms;
+mod script;
+mod scrub;
+mod stream;
+mod transfer_syntax;
+mod vr_rules;
 
+pub mod archive;
+pub mod config;
+pub mod ctp;
+pub mod hook;
+pub mod inspect;
+pub mod presets;
 pub mod types;
 
 pub use anonymizer::Anonymizer;
 pub use enums::*;
+pub use error::{Error, Result};
 pub use file::*;
 pub use meta::*;
+pub use presets::Preset;
+pub use pseudonyms::Pseudonyms;
+pub use stream::StreamingAnonymizer;
@@ -1,28 +1,467 @@
-use serde::{Deserialize, Serialize};
+use dicom_dictionary_std::tags;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::BTreeSet;
+use strum::{EnumIter, IntoEnumIterator, IntoStaticStr};
 
-use crate::enums::{PatientSex, RemoveTagsInput};
-use crate::types::{CustomDicomDateTime, CustomTag};
-use crate::TagAction;
+use crate::config::FieldError;
+use crate::enums::{
+    BirthDateGeneralization, OutputTransferSyntax, PatientAgeAction, PatientSex, RemoveTagsInput,
+    ValueStrictness,
+};
+use crate::presets::Preset;
+use crate::types::{CustomTag, DaValue, FilterRule, Pattern, ScriptRule, SexMapping};
+use crate::{vr_rules, TagAction};
 
+/// The settings of an `AnonymizerMeta`, named like their keys in config files.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, EnumIter, IntoStaticStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum MetaKey {
+    Preset,
+    PatientName,
+    PatientBirthDate,
+    GeneralizeBirthDate,
+    PatientAge,
+    MaxPatientAge,
+    RemoveTags,
+    PatientSex,
+    PatientSexMap,
+    ValueStrictness,
+    ScrubText,
+    ScrubPatterns,
+    Filters,
+    Scripts,
+    TransferSyntax,
+    PseudonymSalt,
+}
+
+impl MetaKey {
+    pub fn name(&self) -> &'static str {
+        self.into()
+    }
+}
+
+/// The settings of an `Anonymizer`.
+///
+/// Equality also compares which settings are set (see `is_set`): a meta that sets `scrub_text` to
+/// `false` isn't equal to one that leaves it unset, since only the first one overrides a preset.
 #[derive(Debug, Builder, Clone, Serialize, Deserialize, Eq, PartialEq)]
-#[builder(derive(Debug))]
+#[builder(derive(Debug), build_fn(private, name = "build_fields"))]
+#[serde(from = "MetaFields", into = "MetaFields")]
 pub struct AnonymizerMeta {
+    /// Settings the others are applied on top of, see `with_preset`
+    #[builder(setter(into), default)]
+    pub(crate) preset: Option<Preset>,
+
     #[builder(setter(into, strip_option), default)]
     pub(crate) patient_name: TagAction<String>,
 
     #[builder(setter(into, strip_option), default)]
-    pub(crate) patient_birth_date: TagAction<CustomDicomDateTime>,
+    pub(crate) patient_birth_date: TagAction<DaValue>,
+
+    /// Coarsens the birth date when `patient_birth_date` is `Keep`
+    #[builder(setter(into), default)]
+    pub(crate) generalize_birth_date: BirthDateGeneralization,
+
+    #[builder(setter(into), default)]
+    pub(crate) patient_age: PatientAgeAction,
+
+    /// Older patients get the age `max_patient_age + 1` years and a birth date that matches it,
+    /// HIPAA Safe Harbor uses 89
+    #[builder(setter(into), default)]
+    pub(crate) max_patient_age: Option<u16>,
 
     #[builder(setter(custom, into, strip_option), default)]
     pub(crate) remove_tags: Vec<CustomTag>,
 
     #[builder(setter(into, strip_option), default)]
     pub(crate) patient_sex: TagAction<PatientSex>,
+
+    /// Replaces the values found in files when `patient_sex` is `Keep`
+    #[builder(setter(into), default)]
+    pub(crate) patient_sex_map: SexMapping,
+
+    /// Whether `Change` values that break the rules of their VR are refused or fixed
+    #[builder(setter(into), default)]
+    pub(crate) value_strictness: ValueStrictness,
+
+    /// Redacts the name, IDs and birth date of the patient from descriptions and comments
+    #[builder(setter(into), default)]
+    pub(crate) scrub_text: bool,
+
+    /// Redacted from descriptions and comments, whether `scrub_text` is set or not
+    #[builder(setter(into), default)]
+    pub(crate) scrub_patterns: Vec<Pattern>,
+
+    /// Decide per file of a batch run whether it is anonymized, copied or skipped
+    #[builder(setter(into), default)]
+    pub(crate) filters: Vec<FilterRule>,
+
+    /// Rhai scripts that compute the new value of an attribute, the first one that decides wins
+    #[builder(setter(into), default)]
+    pub(crate) scripts: Vec<ScriptRule>,
+
+    /// The transfer syntax anonymized files are written with
+    #[builder(setter(into), default)]
+    pub(crate) transfer_syntax: OutputTransferSyntax,
+
+    /// Key of the hash that replaces instance UIDs and patient IDs in archives and of `hash()` in
+    /// scripts. Without a key, the hash of an ID could be reversed by trying IDs, and anyone who
+    /// knows it can do the same, so it has to be kept secret.
+    #[builder(setter(into), default)]
+    pub(crate) pseudonym_salt: Option<String>,
+
+    /// Settings given by a setter of the builder or a key of the config, even with their default
+    /// value, see `is_set`
+    #[builder(setter(skip), default)]
+    pub(crate) explicit: BTreeSet<MetaKey>,
+}
+
+/// How an `AnonymizerMeta` is written in config files. A missing key leaves the setting unset,
+/// a key with the default value sets it to the default.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MetaFields {
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "nullable"
+    )]
+    preset: Option<Option<Preset>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    patient_name: Option<TagAction<String>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    patient_birth_date: Option<TagAction<DaValue>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    generalize_birth_date: Option<BirthDateGeneralization>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    patient_age: Option<PatientAgeAction>,
+
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "nullable"
+    )]
+    max_patient_age: Option<Option<u16>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    remove_tags: Option<Vec<CustomTag>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    patient_sex: Option<TagAction<PatientSex>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    patient_sex_map: Option<SexMapping>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    value_strictness: Option<ValueStrictness>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    scrub_text: Option<bool>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    scrub_patterns: Option<Vec<Pattern>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    filters: Option<Vec<FilterRule>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    scripts: Option<Vec<ScriptRule>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    transfer_syntax: Option<OutputTransferSyntax>,
+
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "nullable"
+    )]
+    pseudonym_salt: Option<Option<String>>,
+}
+
+/// Tells `null` from a missing key, which `#[serde(default)]` leaves at `None`.
+fn nullable<'de, D, T>(d: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(d).map(Some)
+}
+
+impl From<MetaFields> for AnonymizerMeta {
+    fn from(fields: MetaFields) -> Self {
+        let mut explicit = BTreeSet::new();
+        let mut given = |key: MetaKey, is_given: bool| {
+            if is_given {
+                explicit.insert(key);
+            }
+        };
+        given(MetaKey::Preset, fields.preset.is_some());
+        given(MetaKey::PatientName, fields.patient_name.is_some());
+        given(
+            MetaKey::PatientBirthDate,
+            fields.patient_birth_date.is_some(),
+        );
+        given(
+            MetaKey::GeneralizeBirthDate,
+            fields.generalize_birth_date.is_some(),
+        );
+        given(MetaKey::PatientAge, fields.patient_age.is_some());
+        given(MetaKey::MaxPatientAge, fields.max_patient_age.is_some());
+        given(MetaKey::RemoveTags, fields.remove_tags.is_some());
+        given(MetaKey::PatientSex, fields.patient_sex.is_some());
+        given(MetaKey::PatientSexMap, fields.patient_sex_map.is_some());
+        given(MetaKey::ValueStrictness, fields.value_strictness.is_some());
+        given(MetaKey::ScrubText, fields.scrub_text.is_some());
+        given(MetaKey::ScrubPatterns, fields.scrub_patterns.is_some());
+        given(MetaKey::Filters, fields.filters.is_some());
+        given(MetaKey::Scripts, fields.scripts.is_some());
+        given(MetaKey::TransferSyntax, fields.transfer_syntax.is_some());
+        given(MetaKey::PseudonymSalt, fields.pseudonym_salt.is_some());
+
+        AnonymizerMeta {
+            preset: fields.preset.flatten(),
+            patient_name: fields.patient_name.unwrap_or_default(),
+            patient_birth_date: fields.patient_birth_date.unwrap_or_default(),
+            generalize_birth_date: fields.generalize_birth_date.unwrap_or_default(),
+            patient_age: fields.patient_age.unwrap_or_default(),
+            max_patient_age: fields.max_patient_age.flatten(),
+            remove_tags: fields.remove_tags.unwrap_or_default(),
+            patient_sex: fields.patient_sex.unwrap_or_default(),
+            patient_sex_map: fields.patient_sex_map.unwrap_or_default(),
+            value_strictness: fields.value_strictness.unwrap_or_default(),
+            scrub_text: fields.scrub_text.unwrap_or_default(),
+            scrub_patterns: fields.scrub_patterns.unwrap_or_default(),
+            filters: fields.filters.unwrap_or_default(),
+            scripts: fields.scripts.unwrap_or_default(),
+            transfer_syntax: fields.transfer_syntax.unwrap_or_default(),
+            pseudonym_salt: fields.pseudonym_salt.flatten(),
+            explicit,
+        }
+    }
+}
+
+/// Writes the settings that are set, so reading them back gives the same meta.
+impl From<AnonymizerMeta> for MetaFields {
+    fn from(meta: AnonymizerMeta) -> Self {
+        let set = MetaKey::iter()
+            .filter(|key| meta.is_set(*key))
+            .collect::<BTreeSet<_>>();
+        fn given<T>(set: &BTreeSet<MetaKey>, key: MetaKey, value: T) -> Option<T> {
+            set.contains(&key).then_some(value)
+        }
+
+        MetaFields {
+            preset: given(&set, MetaKey::Preset, meta.preset),
+            patient_name: given(&set, MetaKey::PatientName, meta.patient_name),
+            patient_birth_date: given(&set, MetaKey::PatientBirthDate, meta.patient_birth_date),
+            generalize_birth_date: given(
+                &set,
+                MetaKey::GeneralizeBirthDate,
+                meta.generalize_birth_date,
+            ),
+            patient_age: given(&set, MetaKey::PatientAge, meta.patient_age),
+            max_patient_age: given(&set, MetaKey::MaxPatientAge, meta.max_patient_age),
+            remove_tags: given(&set, MetaKey::RemoveTags, meta.remove_tags),
+            patient_sex: given(&set, MetaKey::PatientSex, meta.patient_sex),
+            patient_sex_map: given(&set, MetaKey::PatientSexMap, meta.patient_sex_map),
+            value_strictness: given(&set, MetaKey::ValueStrictness, meta.value_strictness),
+            scrub_text: given(&set, MetaKey::ScrubText, meta.scrub_text),
+            scrub_patterns: given(&set, MetaKey::ScrubPatterns, meta.scrub_patterns),
+            filters: given(&set, MetaKey::Filters, meta.filters),
+            scripts: given(&set, MetaKey::Scripts, meta.scripts),
+            transfer_syntax: given(&set, MetaKey::TransferSyntax, meta.transfer_syntax),
+            pseudonym_salt: given(&set, MetaKey::PseudonymSalt, meta.pseudonym_salt),
+        }
+    }
+}
+
+impl AnonymizerMeta {
+    /// Whether the setting `key` was given explicitly or differs from its default. Settings that
+    /// aren't set are taken from the preset and, in the CLI, from lower config layers.
+    pub fn is_set(&self, key: MetaKey) -> bool {
+        if self.explicit.contains(&key) {
+            return true;
+        }
+
+        match key {
+            MetaKey::Preset => self.preset.is_some(),
+            MetaKey::PatientName => !matches!(self.patient_name, TagAction::Keep),
+            MetaKey::PatientBirthDate => !matches!(self.patient_birth_date, TagAction::Keep),
+            MetaKey::GeneralizeBirthDate => !self.generalize_birth_date.is_full(),
+            MetaKey::PatientAge => !self.patient_age.is_keep(),
+            MetaKey::MaxPatientAge => self.max_patient_age.is_some(),
+            MetaKey::RemoveTags => !self.remove_tags.is_empty(),
+            MetaKey::PatientSex => !matches!(self.patient_sex, TagAction::Keep),
+            MetaKey::PatientSexMap => !self.patient_sex_map.is_empty(),
+            MetaKey::ValueStrictness => !self.value_strictness.is_reject(),
+            MetaKey::ScrubText => self.scrub_text,
+            MetaKey::ScrubPatterns => !self.scrub_patterns.is_empty(),
+            MetaKey::Filters => !self.filters.is_empty(),
+            MetaKey::Scripts => !self.scripts.is_empty(),
+            MetaKey::TransferSyntax => !self.transfer_syntax.is_keep(),
+            MetaKey::PseudonymSalt => self.pseudonym_salt.is_some(),
+        }
+    }
+
+    /// Sets every setting to the value it has, so each one is written. The values of a preset are
+    /// taken over first, the settings then mean the same as before.
+    pub fn with_every_key(self) -> Self {
+        let mut meta = self.with_preset();
+        meta.explicit = MetaKey::iter().collect();
+        meta
+    }
+
+    pub fn preset(&self) -> Option<Preset> {
+        self.preset
+    }
+
+    pub fn patient_name(&self) -> &TagAction<String> {
+        &self.patient_name
+    }
+
+    pub fn patient_birth_date(&self) -> &TagAction<DaValue> {
+        &self.patient_birth_date
+    }
+
+    pub fn generalize_birth_date(&self) -> BirthDateGeneralization {
+        self.generalize_birth_date
+    }
+
+    pub fn patient_age(&self) -> PatientAgeAction {
+        self.patient_age
+    }
+
+    pub fn max_patient_age(&self) -> Option<u16> {
+        self.max_patient_age
+    }
+
+    pub fn remove_tags(&self) -> &[CustomTag] {
+        &self.remove_tags
+    }
+
+    pub fn patient_sex(&self) -> &TagAction<PatientSex> {
+        &self.patient_sex
+    }
+
+    pub fn patient_sex_map(&self) -> &SexMapping {
+        &self.patient_sex_map
+    }
+
+    pub fn value_strictness(&self) -> ValueStrictness {
+        self.value_strictness
+    }
+
+    pub fn scrub_text(&self) -> bool {
+        self.scrub_text
+    }
+
+    pub fn scrub_patterns(&self) -> &[Pattern] {
+        &self.scrub_patterns
+    }
+
+    pub fn filters(&self) -> &[FilterRule] {
+        &self.filters
+    }
+
+    pub fn transfer_syntax(&self) -> OutputTransferSyntax {
+        self.transfer_syntax
+    }
+
+    pub fn scripts(&self) -> &[ScriptRule] {
+        &self.scripts
+    }
+
+    pub fn pseudonym_salt(&self) -> Option<&str> {
+        self.pseudonym_salt.as_deref()
+    }
+
+    /// `Change` values that break the rules of the VR of their attribute, keyed by their path,
+    /// e.g. `patient_name.Change`. Always empty with `ValueStrictness::Coerce`, those values are
+    /// fixed while anonymizing.
+    pub fn value_errors(&self) -> Vec<FieldError> {
+        if !self.value_strictness.is_reject() {
+            return Vec::new();
+        }
+
+        let changes = [
+            (
+                "patient_name",
+                tags::PATIENT_NAME,
+                Option::from(self.patient_name.clone()),
+            ),
+            (
+                "patient_birth_date",
+                tags::PATIENT_BIRTH_DATE,
+                Option::from(self.patient_birth_date.clone()).map(|date: DaValue| date.to_string()),
+            ),
+            (
+                "patient_sex",
+                tags::PATIENT_SEX,
+                Option::from(self.patient_sex.clone())
+                    .map(|sex: PatientSex| sex.value().to_owned()),
+            ),
+        ];
+        changes
+            .into_iter()
+            .filter_map(|(key, tag, value)| {
+                let value = value?;
+                let error = vr_rules::attribute(tag)
+                    .and_then(|(_, vr)| vr_rules::validate(vr, &value))
+                    .err()?;
+                Some(FieldError::new(
+                    format!("{}.Change", key),
+                    error.to_string(),
+                ))
+            })
+            .collect()
+    }
 }
 
 impl AnonymizerMetaBuilder {
+    /// Builds the meta and remembers which setters were called, see `AnonymizerMeta::is_set`.
+    pub fn build(&self) -> Result<AnonymizerMeta, AnonymizerMetaBuilderError> {
+        let mut meta = self.build_fields()?;
+        let given = [
+            (MetaKey::Preset, self.preset.is_some()),
+            (MetaKey::PatientName, self.patient_name.is_some()),
+            (MetaKey::PatientBirthDate, self.patient_birth_date.is_some()),
+            (
+                MetaKey::GeneralizeBirthDate,
+                self.generalize_birth_date.is_some(),
+            ),
+            (MetaKey::PatientAge, self.patient_age.is_some()),
+            (MetaKey::MaxPatientAge, self.max_patient_age.is_some()),
+            (MetaKey::RemoveTags, self.remove_tags.is_some()),
+            (MetaKey::PatientSex, self.patient_sex.is_some()),
+            (MetaKey::PatientSexMap, self.patient_sex_map.is_some()),
+            (MetaKey::ValueStrictness, self.value_strictness.is_some()),
+            (MetaKey::ScrubText, self.scrub_text.is_some()),
+            (MetaKey::ScrubPatterns, self.scrub_patterns.is_some()),
+            (MetaKey::Filters, self.filters.is_some()),
+            (MetaKey::Scripts, self.scripts.is_some()),
+            (MetaKey::TransferSyntax, self.transfer_syntax.is_some()),
+            (MetaKey::PseudonymSalt, self.pseudonym_salt.is_some()),
+        ];
+        meta.explicit = given
+            .into_iter()
+            .filter_map(|(key, is_given)| is_given.then_some(key))
+            .collect();
+
+        Ok(meta)
+    }
+
+    /// Like `build`, for the code that knows it can't fail: every setting has a default.
+    pub fn finish(&self) -> AnonymizerMeta {
+        self.build()
+            .expect("Every field of AnonymizerMeta has a default")
+    }
+
     pub fn remove_tag(&mut self, value: CustomTag) -> &mut Self {
-        let mut obj = self;
+        let obj = self;
 
         if obj.remove_tags.is_none() {
             obj.remove_tags = Some(Vec::<CustomTag>::new());
@@ -59,18 +498,30 @@ impl AnonymizerMetaBuilder {
 mod tests {
 
     mod serialize {
-        use crate::types::{CustomDicomDateTime, CustomTag};
+        use crate::types::{CustomTag, DaValue};
         use crate::{AnonymizerMeta, PatientSex, TagAction};
-        use chrono::FixedOffset;
-        use dicom_core::value::{DicomDate, DicomDateTime};
+        use std::str::FromStr;
 
         #[test]
         fn default() {
             let am = AnonymizerMeta {
+                preset: None,
                 patient_name: TagAction::default(),
                 patient_birth_date: TagAction::default(),
+                generalize_birth_date: Default::default(),
+                patient_age: Default::default(),
+                max_patient_age: None,
                 remove_tags: vec![],
                 patient_sex: TagAction::default(),
+                patient_sex_map: Default::default(),
+                value_strictness: Default::default(),
+                scrub_text: false,
+                scrub_patterns: vec![],
+                filters: vec![],
+                scripts: vec![],
+                transfer_syntax: Default::default(),
+                pseudonym_salt: None,
+                explicit: Default::default(),
             };
             insta::assert_json_snapshot!(am);
         }
@@ -78,13 +529,26 @@ mod tests {
         #[test]
         fn basic_values() {
             let am = AnonymizerMeta {
+                preset: None,
                 patient_name: TagAction::Remove,
                 patient_birth_date: TagAction::Keep,
+                generalize_birth_date: Default::default(),
+                patient_age: Default::default(),
+                max_patient_age: None,
                 remove_tags: vec![CustomTag {
                     group: 0,
                     element: 0,
                 }],
                 patient_sex: TagAction::Remove,
+                patient_sex_map: Default::default(),
+                value_strictness: Default::default(),
+                scrub_text: false,
+                scrub_patterns: vec![],
+                filters: vec![],
+                scripts: vec![],
+                transfer_syntax: Default::default(),
+                pseudonym_salt: None,
+                explicit: Default::default(),
             };
             insta::assert_json_snapshot!(am);
         }
@@ -92,20 +556,90 @@ mod tests {
         #[test]
         fn complex_values() {
             let am = AnonymizerMeta {
+                preset: None,
                 patient_name: TagAction::Change("New Patient Name".to_string()),
-                patient_birth_date: TagAction::Change(CustomDicomDateTime::new(
-                    DicomDateTime::from_date(
-                        DicomDate::from_ymd(2016, 8, 12).unwrap(),
-                        FixedOffset::east(0),
-                    ),
-                )),
+                patient_birth_date: TagAction::Change(DaValue::from_str("20160812").unwrap()),
+                generalize_birth_date: Default::default(),
+                patient_age: Default::default(),
+                max_patient_age: None,
                 remove_tags: vec![CustomTag {
                     group: 0,
                     element: 0,
                 }],
                 patient_sex: TagAction::Change(PatientSex::O),
+                patient_sex_map: Default::default(),
+                value_strictness: Default::default(),
+                scrub_text: false,
+                scrub_patterns: vec![],
+                filters: vec![],
+                scripts: vec![],
+                transfer_syntax: Default::default(),
+                pseudonym_salt: None,
+                explicit: Default::default(),
             };
             insta::assert_json_snapshot!(am);
         }
     }
+
+    mod is_set {
+        use crate::{AnonymizerMeta, AnonymizerMetaBuilder, MetaKey, TagAction, ValueStrictness};
+        use strum::IntoEnumIterator;
+
+        #[test]
+        fn keys_with_default_values_are_set() {
+            let meta: AnonymizerMeta =
+                serde_json::from_str(r#"{"patient_sex":"Keep","scrub_text":false}"#).unwrap();
+
+            assert!(meta.is_set(MetaKey::PatientSex));
+            assert!(meta.is_set(MetaKey::ScrubText));
+            assert!(!meta.is_set(MetaKey::PatientName));
+            assert_eq!(
+                serde_json::to_string(&meta).unwrap(),
+                r#"{"patient_sex":"Keep","scrub_text":false}"#
+            );
+        }
+
+        #[test]
+        fn setters_of_the_builder_set_keys() {
+            let meta = AnonymizerMetaBuilder::default()
+                .value_strictness(ValueStrictness::Reject)
+                .build()
+                .unwrap();
+
+            assert!(meta.is_set(MetaKey::ValueStrictness));
+            assert!(!meta.is_set(MetaKey::PatientSex));
+
+            let mut builder = AnonymizerMetaBuilder::default();
+            builder.patient_name(TagAction::Change("Anonymous".to_string()));
+            let meta = builder.build().unwrap();
+            assert!(meta.is_set(MetaKey::PatientName));
+        }
+
+        #[test]
+        fn set_keys_are_part_of_equality() {
+            let unset = AnonymizerMetaBuilder::default().finish();
+            let set = AnonymizerMetaBuilder::default().scrub_text(false).finish();
+
+            assert_eq!(unset.scrub_text(), set.scrub_text());
+            assert_ne!(unset, set);
+        }
+
+        #[test]
+        fn every_key_is_written() {
+            let meta = AnonymizerMetaBuilder::default().build().unwrap();
+            let value = serde_json::to_value(meta.with_every_key()).unwrap();
+
+            let keys = value
+                .as_object()
+                .unwrap()
+                .keys()
+                .cloned()
+                .collect::<Vec<_>>();
+            let mut expected = MetaKey::iter()
+                .map(|key| key.name().to_string())
+                .collect::<Vec<_>>();
+            expected.sort();
+            assert_eq!(keys, expected);
+        }
+    }
 }
@@ -0,0 +1,407 @@
+//! Named sets of settings for de-identification standards. A preset is the base of an
+//! `AnonymizerMeta`, the settings of the meta itself win over it.
+
+use derive_more::Display;
+use dicom_core::Tag;
+use dicom_dictionary_std::tags;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::str::FromStr;
+use strum::IntoEnumIterator;
+
+use crate::{AnonymizerMeta, AnonymizerMetaBuilder, BirthDateGeneralization, MetaKey, TagAction};
+
+#[derive(Display, Copy, Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub enum Preset {
+    /// HIPAA Safe Harbor, 45 CFR 164.514(b)(2): removes the attributes that hold its 18 kinds of
+    /// identifiers. It doesn't fully cover the method, e.g. instance UIDs are kept, see
+    /// `limitations`.
+    HipaaSafeHarbor,
+}
+
+impl Preset {
+    pub const NAMES: [&'static str; 1] = ["hipaa-safe-harbor"];
+
+    pub fn meta(&self) -> AnonymizerMeta {
+        let mut builder = AnonymizerMetaBuilder::default();
+
+        match self {
+            Preset::HipaaSafeHarbor => {
+                builder.patient_name(TagAction::Remove);
+                // Only the year of dates may stay, and ages above 89 are aggregated
+                builder.generalize_birth_date(BirthDateGeneralization::Year);
+                builder.max_patient_age(89);
+                builder.scrub_text(true);
+                let mut remove_tags: Vec<Tag> = Vec::new();
+                for tag in SafeHarborIdentifier::ALL.iter().flat_map(|id| id.tags()) {
+                    if !remove_tags.contains(tag) {
+                        remove_tags.push(*tag);
+                    }
+                }
+                builder.remove_tags(remove_tags.into());
+            }
+        }
+
+        builder.finish()
+    }
+
+    /// What the preset doesn't cover and has to be checked otherwise.
+    pub fn limitations(&self) -> Vec<&'static str> {
+        match self {
+            Preset::HipaaSafeHarbor => SafeHarborIdentifier::ALL
+                .iter()
+                .filter_map(|id| id.limitation())
+                .collect(),
+        }
+    }
+}
+
+impl FromStr for Preset {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "hipaa-safe-harbor" | "hipaasafeharbor" => Ok(Preset::HipaaSafeHarbor),
+            _ => Err(anyhow::anyhow!(
+                "`{}` isn't one of the presets {}",
+                s,
+                Preset::NAMES.join(", ")
+            )),
+        }
+    }
+}
+
+/// The identifier categories of HIPAA Safe Harbor and the attributes that hold them.
+#[derive(Display, Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SafeHarborIdentifier {
+    Names,
+    GeographicSubdivisions,
+    Dates,
+    TelephoneNumbers,
+    FaxNumbers,
+    EmailAddresses,
+    SocialSecurityNumbers,
+    MedicalRecordNumbers,
+    HealthPlanBeneficiaryNumbers,
+    AccountNumbers,
+    CertificateLicenseNumbers,
+    VehicleIdentifiers,
+    DeviceIdentifiers,
+    Urls,
+    IpAddresses,
+    BiometricIdentifiers,
+    FacePhotographs,
+    OtherUniqueNumbers,
+}
+
+impl SafeHarborIdentifier {
+    pub const ALL: [SafeHarborIdentifier; 18] = [
+        SafeHarborIdentifier::Names,
+        SafeHarborIdentifier::GeographicSubdivisions,
+        SafeHarborIdentifier::Dates,
+        SafeHarborIdentifier::TelephoneNumbers,
+        SafeHarborIdentifier::FaxNumbers,
+        SafeHarborIdentifier::EmailAddresses,
+        SafeHarborIdentifier::SocialSecurityNumbers,
+        SafeHarborIdentifier::MedicalRecordNumbers,
+        SafeHarborIdentifier::HealthPlanBeneficiaryNumbers,
+        SafeHarborIdentifier::AccountNumbers,
+        SafeHarborIdentifier::CertificateLicenseNumbers,
+        SafeHarborIdentifier::VehicleIdentifiers,
+        SafeHarborIdentifier::DeviceIdentifiers,
+        SafeHarborIdentifier::Urls,
+        SafeHarborIdentifier::IpAddresses,
+        SafeHarborIdentifier::BiometricIdentifiers,
+        SafeHarborIdentifier::FacePhotographs,
+        SafeHarborIdentifier::OtherUniqueNumbers,
+    ];
+
+    /// The attributes that are removed for this category. Patient Name, Patient Birth Date and
+    /// Patient Age aren't listed, the preset changes them with their own settings.
+    // Retired attributes are still found in older files
+    #[allow(deprecated)]
+    pub fn tags(&self) -> &'static [Tag] {
+        match self {
+            SafeHarborIdentifier::Names => &[
+                tags::OTHER_PATIENT_NAMES,
+                tags::PATIENT_BIRTH_NAME,
+                tags::PATIENT_MOTHER_BIRTH_NAME,
+                tags::REFERRING_PHYSICIAN_NAME,
+                tags::PERFORMING_PHYSICIAN_NAME,
+                tags::NAME_OF_PHYSICIANS_READING_STUDY,
+                tags::OPERATORS_NAME,
+                tags::PHYSICIANS_OF_RECORD,
+                tags::REQUESTING_PHYSICIAN,
+                tags::RESPONSIBLE_PERSON,
+            ],
+            SafeHarborIdentifier::GeographicSubdivisions => &[
+                tags::PATIENT_ADDRESS,
+                tags::REGION_OF_RESIDENCE,
+                tags::PERSON_ADDRESS,
+                tags::REFERRING_PHYSICIAN_ADDRESS,
+                tags::INSTITUTION_NAME,
+                tags::INSTITUTION_ADDRESS,
+                tags::INSTITUTIONAL_DEPARTMENT_NAME,
+            ],
+            SafeHarborIdentifier::Dates => &[
+                tags::STUDY_DATE,
+                tags::SERIES_DATE,
+                tags::ACQUISITION_DATE,
+                tags::CONTENT_DATE,
+                tags::ACQUISITION_DATE_TIME,
+                tags::INSTANCE_CREATION_DATE,
+                tags::OVERLAY_DATE,
+                tags::CURVE_DATE,
+                tags::ADMITTING_DATE,
+                tags::LAST_MENSTRUAL_DATE,
+                tags::DATE_OF_SECONDARY_CAPTURE,
+                tags::PERFORMED_PROCEDURE_STEP_START_DATE,
+                tags::PERFORMED_PROCEDURE_STEP_END_DATE,
+                tags::PATIENT_BIRTH_TIME,
+            ],
+            SafeHarborIdentifier::TelephoneNumbers => &[
+                tags::PATIENT_TELEPHONE_NUMBERS,
+                tags::REFERRING_PHYSICIAN_TELEPHONE_NUMBERS,
+                tags::PERSON_TELEPHONE_NUMBERS,
+                tags::ORDER_CALLBACK_PHONE_NUMBER,
+            ],
+            // DICOM has no attribute for fax numbers or email addresses of their own, they are
+            // part of the telecom information
+            SafeHarborIdentifier::FaxNumbers | SafeHarborIdentifier::EmailAddresses => &[
+                tags::PATIENT_TELECOM_INFORMATION,
+                tags::PERSON_TELECOM_INFORMATION,
+                tags::ORDER_CALLBACK_TELECOM_INFORMATION,
+            ],
+            SafeHarborIdentifier::SocialSecurityNumbers
+            | SafeHarborIdentifier::MedicalRecordNumbers => &[
+                tags::PATIENT_ID,
+                tags::ISSUER_OF_PATIENT_ID,
+                tags::OTHER_PATIENT_I_DS,
+                tags::OTHER_PATIENT_I_DS_SEQUENCE,
+                tags::MEDICAL_RECORD_LOCATOR,
+            ],
+            SafeHarborIdentifier::HealthPlanBeneficiaryNumbers => {
+                &[tags::PATIENT_INSURANCE_PLAN_CODE_SEQUENCE]
+            }
+            SafeHarborIdentifier::AccountNumbers => {
+                &[tags::ADMISSION_ID, tags::ACCESSION_NUMBER, tags::STUDY_ID]
+            }
+            SafeHarborIdentifier::DeviceIdentifiers => &[
+                tags::DEVICE_SERIAL_NUMBER,
+                tags::DEVICE_UID,
+                tags::STATION_NAME,
+                tags::PLATE_ID,
+                tags::DETECTOR_ID,
+                tags::GANTRY_ID,
+                tags::CASSETTE_ID,
+            ],
+            SafeHarborIdentifier::Urls => &[tags::RETRIEVE_URL, tags::RETRIEVE_URI],
+            SafeHarborIdentifier::IpAddresses => &[tags::NETWORK_ID, tags::STATION_AE_TITLE],
+            SafeHarborIdentifier::OtherUniqueNumbers => &[
+                tags::REQUESTED_PROCEDURE_ID,
+                tags::PERFORMED_PROCEDURE_STEP_ID,
+                tags::SCHEDULED_PROCEDURE_STEP_ID,
+                tags::FILLER_ORDER_NUMBER_IMAGING_SERVICE_REQUEST,
+                tags::PLACER_ORDER_NUMBER_IMAGING_SERVICE_REQUEST,
+                tags::PATIENT_COMMENTS,
+                tags::ADDITIONAL_PATIENT_HISTORY,
+            ],
+            // Not stored in attributes of their own, or only in the pixel data
+            SafeHarborIdentifier::CertificateLicenseNumbers
+            | SafeHarborIdentifier::VehicleIdentifiers
+            | SafeHarborIdentifier::BiometricIdentifiers
+            | SafeHarborIdentifier::FacePhotographs => &[],
+        }
+    }
+
+    /// What the preset doesn't cover and has to be checked otherwise.
+    pub fn limitation(&self) -> Option<&'static str> {
+        match self {
+            SafeHarborIdentifier::FacePhotographs => {
+                Some("Faces in the pixel data, e.g. of head CTs, aren't removed")
+            }
+            SafeHarborIdentifier::BiometricIdentifiers => {
+                Some("Biometric identifiers in the pixel data aren't removed")
+            }
+            SafeHarborIdentifier::OtherUniqueNumbers => {
+                Some("Instance UIDs are kept, replace them consistently with `Pseudonyms` (done for zip archives)")
+            }
+            SafeHarborIdentifier::VehicleIdentifiers => Some(
+                "Vehicle identifiers are only found in free text, add `scrub_patterns` for the formats of your region",
+            ),
+            SafeHarborIdentifier::CertificateLicenseNumbers => Some(
+                "Certificate and license numbers are only found in free text, add `scrub_patterns` for the formats of your region",
+            ),
+            _ => None,
+        }
+    }
+}
+
+impl AnonymizerMeta {
+    /// The settings of the meta on top of the ones of its preset: every setting the meta doesn't
+    /// set is taken from the preset, also a setting given with its default value wins (see
+    /// `is_set`). Tags to remove, scrub patterns and scripts are combined.
+    pub fn with_preset(self) -> Self {
+        let base = match self.preset {
+            Some(preset) => preset.meta(),
+            None => return self,
+        };
+
+        let given = MetaKey::iter()
+            .filter(|key| self.is_set(*key))
+            .collect::<BTreeSet<_>>();
+        let set = |key: MetaKey| given.contains(&key);
+        let explicit = self.explicit.union(&base.explicit).copied().collect();
+        let mut remove_tags = base.remove_tags;
+        for tag in self.remove_tags {
+            if !remove_tags.contains(&tag) {
+                remove_tags.push(tag);
+            }
+        }
+
+        fn or<T>(set: bool, value: T, base: T) -> T {
+            match set {
+                true => value,
+                false => base,
+            }
+        }
+
+        AnonymizerMeta {
+            preset: self.preset,
+            patient_name: or(
+                set(MetaKey::PatientName),
+                self.patient_name,
+                base.patient_name,
+            ),
+            patient_birth_date: or(
+                set(MetaKey::PatientBirthDate),
+                self.patient_birth_date,
+                base.patient_birth_date,
+            ),
+            generalize_birth_date: or(
+                set(MetaKey::GeneralizeBirthDate),
+                self.generalize_birth_date,
+                base.generalize_birth_date,
+            ),
+            patient_age: or(set(MetaKey::PatientAge), self.patient_age, base.patient_age),
+            max_patient_age: or(
+                set(MetaKey::MaxPatientAge),
+                self.max_patient_age,
+                base.max_patient_age,
+            ),
+            remove_tags,
+            patient_sex: or(set(MetaKey::PatientSex), self.patient_sex, base.patient_sex),
+            patient_sex_map: or(
+                set(MetaKey::PatientSexMap),
+                self.patient_sex_map,
+                base.patient_sex_map,
+            ),
+            value_strictness: or(
+                set(MetaKey::ValueStrictness),
+                self.value_strictness,
+                base.value_strictness,
+            ),
+            scrub_text: or(set(MetaKey::ScrubText), self.scrub_text, base.scrub_text),
+            scrub_patterns: [base.scrub_patterns, self.scrub_patterns].concat(),
+            filters: or(set(MetaKey::Filters), self.filters, base.filters),
+            scripts: [self.scripts, base.scripts].concat(),
+            transfer_syntax: or(
+                set(MetaKey::TransferSyntax),
+                self.transfer_syntax,
+                base.transfer_syntax,
+            ),
+            pseudonym_salt: or(
+                set(MetaKey::PseudonymSalt),
+                self.pseudonym_salt,
+                base.pseudonym_salt,
+            ),
+            explicit,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Preset, SafeHarborIdentifier};
+    use crate::{AnonymizerMetaBuilder, BirthDateGeneralization, TagAction};
+    use dicom_dictionary_std::tags;
+    use std::str::FromStr;
+
+    #[test]
+    fn from_str() {
+        assert_eq!(
+            Preset::from_str("hipaa-safe-harbor").unwrap(),
+            Preset::HipaaSafeHarbor
+        );
+        assert_eq!(
+            Preset::from_str(&Preset::HipaaSafeHarbor.to_string()).unwrap(),
+            Preset::HipaaSafeHarbor
+        );
+        assert!(Preset::from_str("ps3.15").is_err());
+    }
+
+    #[test]
+    fn safe_harbor_covers_every_identifier() {
+        let meta = Preset::HipaaSafeHarbor.meta();
+
+        assert_eq!(meta.patient_name(), &TagAction::Remove);
+        assert_eq!(meta.generalize_birth_date(), BirthDateGeneralization::Year);
+        assert_eq!(meta.max_patient_age(), Some(89));
+        for identifier in SafeHarborIdentifier::ALL {
+            assert!(
+                !identifier.tags().is_empty() || identifier.limitation().is_some(),
+                "{} has neither attributes nor a limitation",
+                identifier
+            );
+            for tag in identifier.tags() {
+                assert!(
+                    meta.remove_tags().iter().any(|item| item == tag),
+                    "{}",
+                    identifier
+                );
+            }
+        }
+        assert!(Preset::HipaaSafeHarbor
+            .limitations()
+            .iter()
+            .any(|limitation| limitation.starts_with("Instance UIDs are kept")));
+    }
+
+    #[test]
+    fn settings_of_the_meta_win_over_the_preset() {
+        let mut builder = AnonymizerMetaBuilder::default();
+        builder.preset(Preset::HipaaSafeHarbor);
+        builder.patient_name(TagAction::Change("Anonymous".to_string()));
+        builder.remove_tags(vec![tags::PATIENT_SEX, tags::PATIENT_ID].into());
+        let meta = builder.build().unwrap().with_preset();
+
+        assert_eq!(
+            meta.patient_name(),
+            &TagAction::Change("Anonymous".to_string())
+        );
+        assert_eq!(meta.max_patient_age(), Some(89));
+        let remove_tags = meta.remove_tags();
+        assert_eq!(
+            remove_tags.len(),
+            Preset::HipaaSafeHarbor.meta().remove_tags().len() + 1
+        );
+        assert!(remove_tags.iter().any(|item| item == &tags::PATIENT_SEX));
+        assert_eq!(meta.clone().with_preset(), meta);
+    }
+
+    #[test]
+    fn default_values_of_the_meta_win_over_the_preset() {
+        let mut builder = AnonymizerMetaBuilder::default();
+        builder.preset(Preset::HipaaSafeHarbor);
+        builder.patient_name(TagAction::Keep);
+        builder.scrub_text(false);
+        builder.max_patient_age(None);
+        let meta = builder.finish().with_preset();
+
+        assert_eq!(meta.patient_name(), &TagAction::Keep);
+        assert!(!meta.scrub_text());
+        assert_eq!(meta.max_patient_age(), None);
+        assert_eq!(meta.generalize_birth_date(), BirthDateGeneralization::Year);
+        assert_eq!(meta.clone().with_preset(), meta);
+    }
+}
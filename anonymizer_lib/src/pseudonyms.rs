@@ -0,0 +1,179 @@
+use dicom_core::dictionary::{DataDictionary, DictionaryEntry};
+use dicom_core::value::Value;
+use dicom_core::{DataElement, DicomValue, PrimitiveValue, Tag, VR};
+use dicom_dictionary_std::{tags, StandardDataDictionary};
+use dicom_object::mem::InMemElement;
+use dicom_object::{DefaultDicomObject, InMemDicomObject};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::mem;
+
+use crate::file::meta_builder;
+use crate::Result;
+
+/// Root of the UIDs defined by the standard, e.g. SOP classes and transfer syntaxes
+const DICOM_UID_ROOT: &str = "1.2.840.10008.";
+/// Root for UIDs derived from a UUID, see PS3.5 B.2
+const UUID_UID_ROOT: &str = "2.25.";
+const PATIENT_ID_PREFIX: &str = "ANON";
+
+/// Replaces instance UIDs and patient IDs so that the same original value gets the same
+/// replacement in every file it is applied to, e.g. all files of one archive.
+///
+/// Replacements are the HMAC-SHA256 of the original value keyed with a salt, so the same salt gives
+/// the same replacements on every platform and in every release. Anyone who knows the salt can
+/// check which original value a replacement belongs to, e.g. by trying likely patient IDs.
+#[derive(Debug, Clone)]
+pub struct Pseudonyms {
+    salt: Vec<u8>,
+    uids: HashMap<String, String>,
+    patient_ids: HashMap<String, String>,
+}
+
+impl Pseudonyms {
+    pub fn new(salt: &[u8]) -> Self {
+        Self {
+            salt: salt.to_vec(),
+            uids: HashMap::new(),
+            patient_ids: HashMap::new(),
+        }
+    }
+
+    /// The replacement of an instance UID, UIDs of the standard are kept.
+    pub fn uid(&mut self, uid: &str) -> String {
+        if uid.is_empty() || uid.starts_with(DICOM_UID_ROOT) {
+            return uid.to_string();
+        }
+
+        let salt = &self.salt;
+        self.uids
+            .entry(uid.to_string())
+            .or_insert_with(|| format!("{}{}", UUID_UID_ROOT, hash(salt, uid)))
+            .clone()
+    }
+
+    pub fn patient_id(&mut self, patient_id: &str) -> String {
+        let salt = &self.salt;
+        self.patient_ids
+            .entry(patient_id.to_string())
+            .or_insert_with(|| {
+                format!(
+                    "{}{:016X}",
+                    PATIENT_ID_PREFIX,
+                    hash(salt, patient_id) as u64
+                )
+            })
+            .clone()
+    }
+
+    /// Replaces every instance UID of the file, in sequences and the file meta group too, and
+    /// the patient ID.
+    pub fn apply(&mut self, obj: &mut DefaultDicomObject) -> Result<()> {
+        let uid = self.uid(trim(&obj.meta().media_storage_sop_instance_uid));
+        let meta = meta_builder(obj.meta()).media_storage_sop_instance_uid(uid);
+
+        let dataset = mem::replace(&mut **obj, InMemDicomObject::new_empty());
+        *obj = self.dataset(dataset).with_meta(meta)?;
+        Ok(())
+    }
+
+    fn dataset(&mut self, dataset: InMemDicomObject) -> InMemDicomObject {
+        let elements = dataset
+            .into_iter()
+            .map(|element| self.element(element))
+            .collect::<Vec<_>>();
+
+        InMemDicomObject::from_element_iter(elements)
+    }
+
+    fn element(&mut self, element: InMemElement) -> InMemElement {
+        let (header, value) = (*element.header(), element.into_value());
+
+        let value = match value {
+            Value::Sequence { items, size } => Value::Sequence {
+                items: items.into_iter().map(|item| self.dataset(item)).collect(),
+                size,
+            },
+            Value::Primitive(primitive) if header.vr == VR::UI && is_instance_uid(header.tag) => {
+                let uids = primitive
+                    .to_multi_str()
+                    .iter()
+                    .map(|uid| self.uid(trim(uid)))
+                    .collect();
+                Value::Primitive(PrimitiveValue::Strs(uids))
+            }
+            Value::Primitive(primitive) if header.tag == tags::PATIENT_ID => {
+                match trim(&primitive.to_str()) {
+                    "" => Value::Primitive(primitive),
+                    id => DicomValue::from(PrimitiveValue::from(self.patient_id(id))),
+                }
+            }
+            value => value,
+        };
+
+        DataElement::new(header.tag, header.vr, value)
+    }
+}
+
+/// UIDs of instances, not of classes like the SOP class or the transfer syntax.
+fn is_instance_uid(tag: Tag) -> bool {
+    StandardDataDictionary
+        .by_tag(tag)
+        .map(|entry| !entry.alias().ends_with("ClassUID") && entry.alias() != "TransferSyntaxUID")
+        .unwrap_or(true)
+}
+
+/// UIDs are padded with a null byte, other strings with spaces.
+fn trim(value: &str) -> &str {
+    value.trim_end_matches(['\0', ' '])
+}
+
+/// The first 128 bits of the HMAC-SHA256 of `value` keyed with `salt`.
+pub(crate) fn hash(salt: &[u8], value: &str) -> u128 {
+    let mut mac = Hmac::<Sha256>::new_from_slice(salt).expect("HMAC takes keys of any length");
+    mac.update(value.as_bytes());
+    let digest = mac.finalize().into_bytes();
+
+    u128::from_be_bytes(digest[..16].try_into().expect("SHA-256 has 32 bytes"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{hash, Pseudonyms};
+
+    #[test]
+    fn replacements_are_consistent() {
+        let mut pseudonyms = Pseudonyms::new(b"salt");
+
+        let uid = pseudonyms.uid("1.2.3.4");
+        assert!(uid.starts_with("2.25."));
+        assert!(uid.len() <= 64);
+        assert_eq!(pseudonyms.uid("1.2.3.4"), uid);
+        assert_ne!(pseudonyms.uid("1.2.3.5"), uid);
+        assert_ne!(Pseudonyms::new(b"other").uid("1.2.3.4"), uid);
+
+        let id = pseudonyms.patient_id("12345");
+        assert!(id.starts_with("ANON"));
+        assert_eq!(pseudonyms.patient_id("12345"), id);
+    }
+
+    #[test]
+    fn hash_is_hmac_sha256() {
+        // Test case 2 of RFC 4231
+        assert_eq!(
+            hash(b"Jefe", "what do ya want for nothing?"),
+            0x5bdcc146bf60754e6a042426089575c7
+        );
+    }
+
+    #[test]
+    fn keeps_uids_of_the_standard() {
+        let mut pseudonyms = Pseudonyms::new(b"salt");
+
+        assert_eq!(
+            pseudonyms.uid("1.2.840.10008.5.1.4.1.1.2"),
+            "1.2.840.10008.5.1.4.1.1.2"
+        );
+    }
+}
@@ -0,0 +1,264 @@
+use dicom_core::dictionary::{DataDictionary, DictionaryEntry};
+use dicom_core::{DicomValue, Tag, VR};
+use dicom_dictionary_std::StandardDataDictionary;
+use dicom_object::InMemDicomObject;
+use rhai::{Dynamic, Engine, EvalAltResult, Scope, AST};
+use std::fs;
+use tracing::{debug, warn};
+
+use crate::error::{Error, Result};
+use crate::hook::{Decision, ElementContext, ElementHook};
+use crate::pseudonyms::hash;
+use crate::types::ScriptRule;
+
+/// Operations, e.g. additions or function calls, one script may run per element
+const MAX_OPERATIONS: u64 = 100_000;
+const MAX_STRING_SIZE: usize = 64 * 1024;
+
+/// The compiled script rules of a meta, run as a hook on the top level elements.
+///
+/// Scripts see the element as `value` and every text attribute of the original dataset by its
+/// keyword, e.g. `PatientID`. They return the new value, `()` to leave the element to the other
+/// rules, `keep()` or `remove()`. `hash(text)` is keyed with the `pseudonym_salt` of the meta and
+/// fails without one. The engine can't read files, get the time or draw random numbers, and its
+/// operations are limited, so a script gives the same result for the same file.
+pub(crate) struct Scripts {
+    engine: Engine,
+    rules: Vec<(Tag, ScriptRule, AST)>,
+}
+
+impl Scripts {
+    /// Reads and compiles the script of every rule, `None` without rules.
+    pub(crate) fn load(rules: &[ScriptRule], salt: Option<&str>) -> Result<Option<Self>> {
+        if rules.is_empty() {
+            return Ok(None);
+        }
+
+        let engine = engine(salt.map(|salt| salt.as_bytes().to_vec()));
+        let rules = rules
+            .iter()
+            .map(|rule| {
+                let tag = rule
+                    .tag()
+                    .map_err(|e| Error::InvalidConfig(e.to_string()))?;
+                let source = fs::read_to_string(&rule.file).map_err(|source| Error::Io {
+                    message: format!("Could not read the script {}", rule.file.display()),
+                    source,
+                })?;
+                let ast = engine.compile(&source).map_err(|e| {
+                    Error::InvalidConfig(format!("The script {} is invalid: {}", rule, e))
+                })?;
+                Ok((tag, rule.clone(), ast))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Some(Scripts { engine, rules }))
+    }
+
+    fn run(&self, rule: &ScriptRule, ast: &AST, element: &ElementContext) -> Decision {
+        let mut scope = Scope::new();
+        for (keyword, value) in attributes(element.original) {
+            scope.push_constant(keyword, value);
+        }
+        if let Some(value) = text(element.element.vr(), element.element.value()) {
+            scope.push("value", value);
+        }
+
+        let result = match self.engine.eval_ast_with_scope::<Dynamic>(&mut scope, ast) {
+            Ok(result) => result,
+            Err(e) => {
+                // Whatever the script was meant to hide doesn't stay in the file
+                warn!("The script {} failed, the element is removed: {}", rule, e);
+                return Decision::Remove;
+            }
+        };
+
+        if result.is_unit() {
+            Decision::Pass
+        } else if result.is::<Decision>() {
+            result.cast::<Decision>()
+        } else if result.is_string() || result.is_int() || result.is_float() {
+            Decision::Change(result.to_string())
+        } else {
+            warn!(
+                "The script {} returned a {}, the element is removed",
+                rule,
+                result.type_name()
+            );
+            Decision::Remove
+        }
+    }
+}
+
+impl ElementHook for Scripts {
+    fn decide(&self, element: &ElementContext) -> Decision {
+        let tag = element.element.header().tag;
+        // Only the top level, an attribute in a sequence has its path, e.g. `Sequence[0].PatientID`
+        if element.path.contains('.') {
+            return Decision::Pass;
+        }
+
+        self.rules
+            .iter()
+            .filter(|(rule_tag, ..)| *rule_tag == tag)
+            .map(|(_, rule, ast)| {
+                debug!(script = %rule.file.display(), "Running");
+                self.run(rule, ast, element)
+            })
+            .find(|decision| *decision != Decision::Pass)
+            .unwrap_or(Decision::Pass)
+    }
+}
+
+fn engine(salt: Option<Vec<u8>>) -> Engine {
+    let mut engine = Engine::new();
+
+    engine
+        .set_max_operations(MAX_OPERATIONS)
+        .set_max_string_size(MAX_STRING_SIZE)
+        .set_max_array_size(1_000)
+        .set_max_map_size(1_000)
+        .set_max_call_levels(16)
+        .set_max_expr_depths(64, 32)
+        .disable_symbol("eval")
+        .on_print(|text| debug!(text, "Script print"))
+        .on_debug(|text, _, position| debug!(text, %position, "Script debug"));
+
+    engine
+        .register_type_with_name::<Decision>("Decision")
+        .register_fn("keep", || Decision::Keep)
+        .register_fn("remove", || Decision::Remove)
+        .register_fn("hash", move |value: &str| match &salt {
+            Some(salt) => Ok(format!("{:016X}", hash(salt, value) as u64)),
+            None => Err(Box::<EvalAltResult>::from(
+                "hash() needs a pseudonym_salt in the config",
+            )),
+        });
+
+    engine
+}
+
+/// The text attributes of the top level of `obj` by keyword.
+fn attributes(obj: &InMemDicomObject) -> Vec<(&'static str, String)> {
+    obj.into_iter()
+        .filter_map(|element| {
+            let keyword = StandardDataDictionary.by_tag(element.header().tag)?.alias();
+            Some((keyword, text(element.vr(), element.value())?))
+        })
+        .collect()
+}
+
+/// The value as a string without padding, `None` for binary values and sequences.
+fn text(vr: VR, value: &DicomValue<InMemDicomObject, Vec<u8>>) -> Option<String> {
+    match (vr, value) {
+        (VR::OB | VR::OD | VR::OF | VR::OL | VR::OV | VR::OW | VR::UN, _) => None,
+        (_, DicomValue::Primitive(value)) => {
+            Some(value.to_str().trim_end_matches([' ', '\0']).to_string())
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Scripts;
+    use crate::hook::{Decision, ElementContext, ElementHook};
+    use crate::types::ScriptRule;
+    use crate::Pseudonyms;
+    use dicom_core::{DataElement, PrimitiveValue, VR};
+    use dicom_dictionary_std::tags;
+    use dicom_object::InMemDicomObject;
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn load(name: &str, source: &str) -> Scripts {
+        load_with_salt(name, source, Some("salt"))
+    }
+
+    fn load_with_salt(name: &str, source: &str, salt: Option<&str>) -> Scripts {
+        let file = std::env::temp_dir().join(format!("dicom-tools-{}.rhai", name));
+        fs::write(&file, source).unwrap();
+        let rule = ScriptRule {
+            attribute: "PatientID".to_string(),
+            file,
+        };
+        Scripts::load(&[rule], salt).unwrap().unwrap()
+    }
+
+    fn decide(scripts: &Scripts, modality: &str) -> Decision {
+        let mut obj = InMemDicomObject::new_empty();
+        obj.put(DataElement::new(
+            tags::MODALITY,
+            VR::CS,
+            PrimitiveValue::from(modality),
+        ));
+        obj.put(DataElement::new(
+            tags::PATIENT_ID,
+            VR::LO,
+            PrimitiveValue::from("12345 "),
+        ));
+
+        let element = obj.element(tags::PATIENT_ID).unwrap();
+        scripts.decide(&ElementContext {
+            path: "PatientID",
+            element,
+            original: &obj,
+        })
+    }
+
+    #[test]
+    fn computes_values_from_other_attributes() {
+        let scripts = load(
+            "computes",
+            r#"if Modality == "CT" { "ANON-" + hash(PatientID) } else if Modality == "SR" { remove() }"#,
+        );
+
+        let id = match decide(&scripts, "CT") {
+            Decision::Change(id) => id,
+            decision => panic!("{:?}", decision),
+        };
+        assert!(id.starts_with("ANON-"));
+        assert_eq!(decide(&scripts, "CT"), Decision::Change(id));
+        assert_eq!(decide(&scripts, "SR"), Decision::Remove);
+        assert_eq!(decide(&scripts, "MR"), Decision::Pass);
+    }
+
+    #[test]
+    fn hash_is_keyed_with_the_salt() {
+        let scripts = load("hash", "hash(PatientID)");
+        let id = Pseudonyms::new(b"salt").patient_id("12345");
+        assert_eq!(
+            decide(&scripts, "CT"),
+            Decision::Change(id.trim_start_matches("ANON").to_string())
+        );
+
+        let unsalted = load_with_salt("unsalted", "hash(PatientID)", None);
+        assert_eq!(decide(&unsalted, "CT"), Decision::Remove);
+    }
+
+    #[test]
+    fn removes_the_element_when_a_script_fails() {
+        let scripts = load("loops", "loop { }");
+        assert_eq!(decide(&scripts, "CT"), Decision::Remove);
+
+        let scripts = load("unknown", "PatientName + value");
+        assert_eq!(decide(&scripts, "CT"), Decision::Remove);
+    }
+
+    #[test]
+    fn scripts_have_to_compile() {
+        let file = std::env::temp_dir().join("dicom-tools-invalid.rhai");
+        fs::write(&file, "if {").unwrap();
+        let rule = ScriptRule {
+            attribute: "PatientID".to_string(),
+            file,
+        };
+        assert!(Scripts::load(&[rule], None).is_err());
+
+        let rule = ScriptRule {
+            attribute: "PatientID".to_string(),
+            file: PathBuf::from("/does/not/exist.rhai"),
+        };
+        assert!(Scripts::load(&[rule], None).is_err());
+    }
+}
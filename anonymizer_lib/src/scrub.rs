@@ -0,0 +1,303 @@
+use chrono::{Datelike, NaiveDate};
+use dicom_core::value::Value;
+use dicom_core::{DataElement, PrimitiveValue, Tag, VR};
+use dicom_dictionary_std::tags;
+use dicom_object::mem::InMemElement;
+use dicom_object::InMemDicomObject;
+use regex::{Regex, RegexBuilder};
+use std::borrow::Cow;
+use std::str::FromStr;
+
+use crate::types::{PartialDate, Pattern};
+
+/// Written in place of every match
+pub const REDACTED: &str = "***";
+
+/// VRs of descriptions and comments that technicians type by hand
+const TEXT_VRS: [VR; 4] = [VR::LO, VR::LT, VR::ST, VR::UT];
+
+/// Text attributes that hold the identifiers on purpose, they have settings of their own
+#[allow(deprecated)]
+const IDENTIFIER_TAGS: [Tag; 3] = [
+    tags::PATIENT_ID,
+    tags::ISSUER_OF_PATIENT_ID,
+    tags::OTHER_PATIENT_I_DS,
+];
+
+/// Names shorter than this, like initials, would match too much text
+const MIN_NAME_PART: usize = 2;
+
+/// Redacts identifiers from the free text of a dataset.
+pub(crate) struct Scrubber {
+    regexes: Vec<Regex>,
+}
+
+impl Scrubber {
+    /// `identity` adds the name, IDs and birth date of the patient in `obj`, `patterns` are
+    /// redacted as they are. `None` if there is nothing to look for.
+    pub fn new(obj: &InMemDicomObject, identity: bool, patterns: &[Pattern]) -> Option<Self> {
+        let mut regexes = patterns
+            .iter()
+            .map(|pattern| pattern.regex().clone())
+            .collect::<Vec<_>>();
+        if identity {
+            regexes.extend(identity_regex(obj));
+        }
+
+        match regexes.is_empty() {
+            true => None,
+            false => Some(Self { regexes }),
+        }
+    }
+
+    pub fn dataset(&self, dataset: InMemDicomObject) -> InMemDicomObject {
+        let elements = dataset
+            .into_iter()
+            .map(|element| self.element(element))
+            .collect::<Vec<_>>();
+
+        InMemDicomObject::from_element_iter(elements)
+    }
+
+    fn element(&self, element: InMemElement) -> InMemElement {
+        let (header, value) = (*element.header(), element.into_value());
+
+        let value = match value {
+            Value::Sequence { items, size } => Value::Sequence {
+                items: items.into_iter().map(|item| self.dataset(item)).collect(),
+                size,
+            },
+            Value::Primitive(primitive) if is_free_text(header.tag, header.vr) => {
+                Value::Primitive(match primitive {
+                    PrimitiveValue::Str(value) => PrimitiveValue::Str(self.text(&value)),
+                    PrimitiveValue::Strs(values) => {
+                        PrimitiveValue::Strs(values.iter().map(|value| self.text(value)).collect())
+                    }
+                    primitive => primitive,
+                })
+            }
+            value => value,
+        };
+
+        DataElement::new(header.tag, header.vr, value)
+    }
+
+    pub fn text(&self, value: &str) -> String {
+        self.regexes
+            .iter()
+            .fold(Cow::Borrowed(value), |text, regex| {
+                match regex.replace_all(&text, REDACTED) {
+                    Cow::Borrowed(_) => text,
+                    Cow::Owned(replaced) => Cow::Owned(replaced),
+                }
+            })
+            .into_owned()
+    }
+}
+
+fn is_free_text(tag: Tag, vr: VR) -> bool {
+    // Private creators, (gggg,0010-00FF) of odd groups, name the owner of private blocks
+    let private_creator = tag.group() % 2 == 1 && (0x0010..=0x00FF).contains(&tag.element());
+
+    TEXT_VRS.contains(&vr) && !IDENTIFIER_TAGS.contains(&tag) && !private_creator
+}
+
+/// Matches the name parts, IDs and birth date of the patient, ignoring case.
+fn identity_regex(obj: &InMemDicomObject) -> Option<Regex> {
+    let values = |tag| -> Vec<String> {
+        obj.element_opt(tag)
+            .ok()
+            .flatten()
+            .and_then(|element| element.to_multi_str().ok())
+            .map(|values| {
+                values
+                    .iter()
+                    .map(|value| value.trim().to_string())
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+
+    let mut terms = Vec::new();
+    // Family, given and middle names of every representation, titles like `Dr` aren't
+    // identifying
+    for name in values(tags::PATIENT_NAME) {
+        terms.extend(
+            name.split('=')
+                .flat_map(|group| group.split('^').take(3))
+                .flat_map(|component| component.split([' ', ',']))
+                .filter(|part| part.chars().count() >= MIN_NAME_PART)
+                .map(word),
+        );
+    }
+    #[allow(deprecated)]
+    let ids = [tags::PATIENT_ID, tags::OTHER_PATIENT_I_DS];
+    for id in ids.into_iter().flat_map(values) {
+        if !id.is_empty() {
+            terms.push(word(&id));
+        }
+    }
+    if let Some(date) = values(tags::PATIENT_BIRTH_DATE)
+        .first()
+        .and_then(|value| PartialDate::from_str(value).ok()?.to_naive_date())
+    {
+        terms.extend(date_terms(date));
+    }
+
+    if terms.is_empty() {
+        return None;
+    }
+    RegexBuilder::new(&terms.join("|"))
+        .case_insensitive(true)
+        .build()
+        .ok()
+}
+
+/// The escaped term, only matching whole words where it starts or ends with a word character.
+fn word(term: &str) -> String {
+    let boundary = |c: Option<char>| match c {
+        Some(c) if c.is_alphanumeric() || c == '_' => r"\b",
+        _ => "",
+    };
+
+    format!(
+        "{}{}{}",
+        boundary(term.chars().next()),
+        regex::escape(term),
+        boundary(term.chars().last())
+    )
+}
+
+/// The usual ways to write a date: `19800612`, `1980-06-12`, `12.06.1980`, `6/12/1980`, ...
+fn date_terms(date: NaiveDate) -> Vec<String> {
+    let (y, m, d) = (date.year(), date.month(), date.day());
+    let separator = "[-./]";
+
+    vec![
+        format!(r"\b{:04}{:02}{:02}\b", y, m, d),
+        format!(
+            r"\b{y}{s}0?{m}{s}0?{d}\b",
+            y = y,
+            m = m,
+            d = d,
+            s = separator
+        ),
+        format!(
+            r"\b0?{d}{s}0?{m}{s}{y}\b",
+            y = y,
+            m = m,
+            d = d,
+            s = separator
+        ),
+        format!(
+            r"\b0?{m}{s}0?{d}{s}{y}\b",
+            y = y,
+            m = m,
+            d = d,
+            s = separator
+        ),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Scrubber;
+    use crate::types::Pattern;
+    use dicom_core::value::Value;
+    use dicom_core::{DataElement, Length, PrimitiveValue, Tag, VR};
+    use dicom_dictionary_std::tags;
+    use dicom_object::InMemDicomObject;
+    use std::str::FromStr;
+
+    fn factory() -> InMemDicomObject {
+        let mut obj = InMemDicomObject::new_empty();
+        for (tag, vr, value) in [
+            (tags::PATIENT_NAME, VR::PN, "Doe^John^^Dr"),
+            (tags::PATIENT_ID, VR::LO, "MRN-4711"),
+            (tags::PATIENT_BIRTH_DATE, VR::DA, "19800612"),
+        ] {
+            obj.put(DataElement::new(tag, vr, PrimitiveValue::from(value)));
+        }
+        obj
+    }
+
+    fn scrubber(identity: bool, patterns: &[&str]) -> Scrubber {
+        let patterns = patterns
+            .iter()
+            .map(|pattern| Pattern::from_str(pattern).unwrap())
+            .collect::<Vec<_>>();
+        Scrubber::new(&factory(), identity, &patterns).unwrap()
+    }
+
+    #[test]
+    fn redacts_the_identity_of_the_patient() {
+        let scrubber = scrubber(true, &[]);
+
+        assert_eq!(
+            scrubber.text("CT for JOHN doe, born 12.06.1980 (MRN-4711)"),
+            "CT for *** ***, born *** (***)"
+        );
+        assert_eq!(scrubber.text("1980-6-12 and 19800612"), "*** and ***");
+        // Parts of other words and other dates stay
+        assert_eq!(
+            scrubber.text("Doeblin Johnson 19800613 Dr"),
+            "Doeblin Johnson 19800613 Dr"
+        );
+    }
+
+    #[test]
+    fn redacts_configured_patterns() {
+        let scrubber = scrubber(false, &[r"\+?\d[\d /-]{6,}\d", r"[\w.]+@[\w.]+"]);
+
+        assert_eq!(
+            scrubber.text("Call +49 30 1234567 or mail j.doe@example.org, John"),
+            "Call *** or mail ***, John"
+        );
+        assert!(Scrubber::new(&factory(), false, &[]).is_none());
+    }
+
+    #[test]
+    fn scrubs_text_values_in_sequences_only() {
+        let mut item = InMemDicomObject::new_empty();
+        item.put(DataElement::new(
+            tags::IMAGE_COMMENTS,
+            VR::LT,
+            PrimitiveValue::from("John's left knee"),
+        ));
+        let mut obj = factory();
+        obj.put(DataElement::new(
+            tags::REFERENCED_IMAGE_SEQUENCE,
+            VR::SQ,
+            Value::Sequence {
+                items: vec![item].into(),
+                size: Length::UNDEFINED,
+            },
+        ));
+        obj.put(DataElement::new(
+            Tag(0x0009, 0x0010),
+            VR::LO,
+            PrimitiveValue::from("JOHN"),
+        ));
+        obj.put(DataElement::new(
+            tags::STUDY_DESCRIPTION,
+            VR::LO,
+            PrimitiveValue::Strs(["Knee".to_string(), "Doe".to_string()].into()),
+        ));
+
+        let obj = scrubber(true, &[]).dataset(obj);
+        let text =
+            |obj: &InMemDicomObject, tag| obj.element(tag).unwrap().to_str().unwrap().to_string();
+
+        let items = obj
+            .element(tags::REFERENCED_IMAGE_SEQUENCE)
+            .unwrap()
+            .value()
+            .items()
+            .unwrap();
+        assert_eq!(text(&items[0], tags::IMAGE_COMMENTS), "***'s left knee");
+        assert_eq!(text(&obj, tags::STUDY_DESCRIPTION), "Knee\\***");
+        assert_eq!(text(&obj, tags::PATIENT_ID), "MRN-4711");
+        assert_eq!(text(&obj, tags::PATIENT_NAME), "Doe^John^^Dr");
+        assert_eq!(text(&obj, Tag(0x0009, 0x0010)), "JOHN");
+    }
+}
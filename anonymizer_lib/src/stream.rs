@@ -0,0 +1,576 @@
+use std::mem;
+
+use crate::transfer_syntax::{
+    DEFLATED_EXPLICIT_VR_LITTLE_ENDIAN, EXPLICIT_VR_BIG_ENDIAN, IMPLICIT_VR_LITTLE_ENDIAN,
+};
+use crate::{
+    Anonymizer, AnonymizerMeta, Error, FileLayout, FilterAction, Result, LAYOUT_HEAD_LENGTH,
+};
+
+const MAGIC_CODE: &[u8; 4] = b"DICM";
+const PREAMBLE_LEN: usize = 128;
+const UNDEFINED_LENGTH: u32 = 0xFFFF_FFFF;
+const ITEM: (u16, u16) = (0xFFFE, 0xE000);
+const ITEM_DELIMITER: (u16, u16) = (0xFFFE, 0xE00D);
+const SEQUENCE_DELIMITER: (u16, u16) = (0xFFFE, 0xE0DD);
+/// Pixel Data, Float Pixel Data and Double Float Pixel Data
+const PIXEL_DATA_GROUP: u16 = 0x7FE0;
+
+/// Anonymizes a file that arrives in chunks. Only the header, everything before the pixel data,
+/// is held in memory and rewritten; the pixel data is passed through chunk by chunk.
+///
+/// Elements after the pixel data, e.g. trailing padding, are passed through unchanged as well.
+/// Every `FileLayout` is read, anonymized files are written as part 10 files.
+#[derive(Debug)]
+pub struct StreamingAnonymizer {
+    meta: AnonymizerMeta,
+    /// Decision of the filters, once the header has been read
+    action: Option<FilterAction>,
+    state: State,
+}
+
+#[derive(Debug)]
+enum State {
+    /// Collecting the header, `scanner` remembers how far it has been parsed.
+    Header {
+        buffer: Vec<u8>,
+        scanner: Scanner,
+    },
+    /// The header has been written, the rest of the file is passed through or dropped.
+    Body,
+    Finished,
+}
+
+impl StreamingAnonymizer {
+    pub fn new(meta: AnonymizerMeta) -> Self {
+        Self {
+            meta,
+            action: None,
+            state: State::Header {
+                buffer: Vec::new(),
+                scanner: Scanner::default(),
+            },
+        }
+    }
+
+    /// The decision of the filters, known as soon as the header has been processed.
+    pub fn action(&self) -> Option<FilterAction> {
+        self.action
+    }
+
+    /// Takes the next chunk of the input and returns the output that is ready, which is empty
+    /// while the header is still incomplete.
+    pub fn push(&mut self, chunk: &[u8]) -> Result<Vec<u8>> {
+        // Left finished if anything fails
+        match mem::replace(&mut self.state, State::Finished) {
+            State::Header {
+                mut buffer,
+                mut scanner,
+            } => {
+                buffer.extend_from_slice(chunk);
+
+                match scanner.scan(&buffer)? {
+                    Some(header_len) => self.process_header(buffer, header_len),
+                    None => {
+                        self.state = State::Header { buffer, scanner };
+                        Ok(Vec::new())
+                    }
+                }
+            }
+            State::Body => {
+                self.state = State::Body;
+                match self.action {
+                    Some(FilterAction::Skip) => Ok(Vec::new()),
+                    _ => Ok(chunk.to_vec()),
+                }
+            }
+            State::Finished => Err(stream_error("it has already been finished")),
+        }
+    }
+
+    /// Ends the input and returns the remaining output. A file without pixel data is only
+    /// processed here, as a whole.
+    pub fn finish(&mut self) -> Result<Vec<u8>> {
+        match mem::replace(&mut self.state, State::Finished) {
+            State::Header { buffer, .. } => {
+                let len = buffer.len();
+                let output = self.process_header(buffer, len);
+                self.state = State::Finished;
+                output
+            }
+            State::Body => Ok(Vec::new()),
+            State::Finished => Err(stream_error("it has already been finished")),
+        }
+    }
+
+    /// Rewrites the first `header_len` bytes of `buffer` and appends the rest unchanged.
+    fn process_header(&mut self, buffer: Vec<u8>, header_len: usize) -> Result<Vec<u8>> {
+        let mut any = Anonymizer::from_bytes(&buffer[..header_len])?;
+        any.meta(self.meta.clone());
+
+        let action = any.filter_action()?;
+        let mut output = match action {
+            FilterAction::Anonymize => {
+                // The pixel data is passed through as it is, so it can't change its encoding
+                if let Some(uid) = self.meta.transfer_syntax().uid() {
+                    let current = any.transfer_syntax()?;
+                    if current != uid {
+                        return Err(Error::TransferSyntaxConversion {
+                            from: current.to_string(),
+                            to: uid.to_string(),
+                            reason: "a stream passes the pixel data through, convert the file as a whole"
+                                .to_string(),
+                        });
+                    }
+                }
+                any.anonymize()?;
+                any.to_bytes()?
+            }
+            FilterAction::Copy => buffer[..header_len].to_vec(),
+            FilterAction::Skip => Vec::new(),
+        };
+        if action != FilterAction::Skip {
+            output.extend_from_slice(&buffer[header_len..]);
+        }
+
+        self.action = Some(action);
+        self.state = State::Body;
+        Ok(output)
+    }
+}
+
+/// Finds where the pixel data starts by walking the element headers, without parsing values.
+#[derive(Debug, Default)]
+struct Scanner {
+    /// Start of the next top level element that hasn't been skipped yet, once the file meta
+    /// group has been read.
+    position: Option<usize>,
+    explicit_vr: bool,
+}
+
+struct Header {
+    tag: (u16, u16),
+    len: u32,
+    /// Length of the header itself
+    size: usize,
+    /// Value of undefined length that contains items, an `UN` one is always implicit VR
+    sequence: Option<bool>,
+}
+
+impl Scanner {
+    /// Offset of the pixel data element, `None` if more data is needed to find it.
+    fn scan(&mut self, buffer: &[u8]) -> Result<Option<usize>> {
+        let mut position = match self.position {
+            Some(position) => position,
+            None => match self.read_file_meta(buffer)? {
+                Some(position) => position,
+                None => return Ok(None),
+            },
+        };
+
+        loop {
+            let header = match read_header(buffer, position, self.explicit_vr)? {
+                Some(header) => header,
+                None => break,
+            };
+            if header.tag.0 == PIXEL_DATA_GROUP {
+                return Ok(Some(position));
+            }
+
+            match skip_value(buffer, position + header.size, &header)? {
+                Some(end) => position = end,
+                None => break,
+            }
+        }
+
+        self.position = Some(position);
+        Ok(None)
+    }
+
+    /// Reads the transfer syntax, returns where the dataset starts. Every `FileLayout` is
+    /// accepted, a dataset without file meta group is read with the VR encoding it starts with.
+    fn read_file_meta(&mut self, buffer: &[u8]) -> Result<Option<usize>> {
+        // Shorter files are only processed as a whole, by `finish`
+        if buffer.len() < LAYOUT_HEAD_LENGTH {
+            return Ok(None);
+        }
+        let start = match FileLayout::detect(buffer) {
+            Some(FileLayout::Preamble) => PREAMBLE_LEN + MAGIC_CODE.len(),
+            Some(FileLayout::NoPreamble) => MAGIC_CODE.len(),
+            Some(FileLayout::NoMagicCode) => 0,
+            Some(FileLayout::Dataset { explicit_vr }) => {
+                self.explicit_vr = explicit_vr;
+                return Ok(Some(0));
+            }
+            None => return Err(stream_error("not a DICOM file")),
+        };
+
+        // File Meta Information Group Length, always explicit VR little endian
+        let group_length = match buffer.get(start..start + 12) {
+            Some(bytes) if bytes[..4] == [0x02, 0x00, 0x00, 0x00] => u32_at(bytes, 8) as usize,
+            Some(_) => {
+                return Err(stream_error(
+                    "the file meta group has to start with its group length",
+                ))
+            }
+            None => return Ok(None),
+        };
+        let end = start + 12 + group_length;
+        if buffer.len() < end {
+            return Ok(None);
+        }
+
+        let mut transfer_syntax = None;
+        let mut position = start + 12;
+        while position < end {
+            let header = read_header(buffer, position, true)?
+                .ok_or_else(|| stream_error("the file meta group is truncated"))?;
+            let value_start = position + header.size;
+            let value_end = value_start + header.len as usize;
+            if header.tag == (0x0002, 0x0010) {
+                let value = buffer
+                    .get(value_start..value_end)
+                    .ok_or_else(|| stream_error("the file meta group is truncated"))?;
+                transfer_syntax = Some(
+                    String::from_utf8_lossy(value)
+                        .trim_end_matches(['\0', ' '])
+                        .to_string(),
+                );
+            }
+            position = value_end;
+        }
+
+        self.explicit_vr = match transfer_syntax.as_deref() {
+            None => return Err(stream_error("the file meta group has no transfer syntax")),
+            Some(IMPLICIT_VR_LITTLE_ENDIAN) => false,
+            Some(DEFLATED_EXPLICIT_VR_LITTLE_ENDIAN) => {
+                return Err(stream_error(
+                    "deflated files can't be streamed, anonymize them as a whole",
+                ))
+            }
+            Some(EXPLICIT_VR_BIG_ENDIAN) => {
+                return Err(stream_error(
+                    "big endian files can't be streamed, anonymize them as a whole",
+                ))
+            }
+            Some(_) => true,
+        };
+
+        Ok(Some(end))
+    }
+}
+
+fn stream_error(message: impl Into<String>) -> Error {
+    Error::Stream(message.into())
+}
+
+fn u16_at(buffer: &[u8], position: usize) -> u16 {
+    u16::from_le_bytes([buffer[position], buffer[position + 1]])
+}
+
+fn u32_at(buffer: &[u8], position: usize) -> u32 {
+    u32::from_le_bytes([
+        buffer[position],
+        buffer[position + 1],
+        buffer[position + 2],
+        buffer[position + 3],
+    ])
+}
+
+/// The element header at `position`, `None` if the buffer ends before it is complete.
+fn read_header(buffer: &[u8], position: usize, explicit_vr: bool) -> Result<Option<Header>> {
+    if buffer.len() < position + 8 {
+        return Ok(None);
+    }
+    let tag = (u16_at(buffer, position), u16_at(buffer, position + 2));
+
+    // Items and delimiters have no VR in every transfer syntax
+    if tag.0 == 0xFFFE || !explicit_vr {
+        let len = u32_at(buffer, position + 4);
+        let sequence = (len == UNDEFINED_LENGTH).then_some(false);
+        return Ok(Some(Header {
+            tag,
+            len,
+            size: 8,
+            sequence,
+        }));
+    }
+
+    let vr = &buffer[position + 4..position + 6];
+    match vr {
+        b"OB" | b"OD" | b"OF" | b"OL" | b"OV" | b"OW" | b"SQ" | b"SV" | b"UC" | b"UN" | b"UR"
+        | b"UT" | b"UV" => {
+            if buffer.len() < position + 12 {
+                return Ok(None);
+            }
+            let len = u32_at(buffer, position + 8);
+            let sequence = match (vr, len == UNDEFINED_LENGTH) {
+                (b"SQ", true) => Some(true),
+                (b"UN", true) => Some(false),
+                (_, true) => {
+                    return Err(stream_error(format!(
+                        "element ({:04X},{:04X}) has an undefined length",
+                        tag.0, tag.1
+                    )))
+                }
+                (_, false) => None,
+            };
+            Ok(Some(Header {
+                tag,
+                len,
+                size: 12,
+                sequence,
+            }))
+        }
+        _ => Ok(Some(Header {
+            tag,
+            len: u16_at(buffer, position + 6) as u32,
+            size: 8,
+            sequence: None,
+        })),
+    }
+}
+
+/// End of the value that starts at `position`, `None` if the buffer ends before it is known.
+fn skip_value(buffer: &[u8], position: usize, header: &Header) -> Result<Option<usize>> {
+    let explicit_vr = match header.sequence {
+        None => {
+            return position
+                .checked_add(header.len as usize)
+                .map(Some)
+                .ok_or_else(|| stream_error("an element length is out of range"))
+        }
+        Some(explicit_vr) => explicit_vr,
+    };
+
+    let mut position = position;
+    loop {
+        let item = match read_header(buffer, position, explicit_vr)? {
+            Some(item) => item,
+            None => return Ok(None),
+        };
+        position += item.size;
+
+        match item.tag {
+            SEQUENCE_DELIMITER => return Ok(Some(position)),
+            ITEM if item.len == UNDEFINED_LENGTH => loop {
+                let element = match read_header(buffer, position, explicit_vr)? {
+                    Some(element) => element,
+                    None => return Ok(None),
+                };
+                position += element.size;
+                if element.tag == ITEM_DELIMITER {
+                    break;
+                }
+                position = match skip_value(buffer, position, &element)? {
+                    Some(end) => end,
+                    None => return Ok(None),
+                };
+            },
+            ITEM => position += item.len as usize,
+            (group, element) => {
+                return Err(stream_error(format!(
+                    "unexpected element ({:04X},{:04X}) in a sequence",
+                    group, element
+                )))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StreamingAnonymizer;
+    use crate::types::FilterRule;
+    use crate::{Anonymizer, AnonymizerMeta, Error, FilterAction, OutputTransferSyntax, TagAction};
+    use dicom_core::{DataElement, DicomValue, Length, PrimitiveValue, Tag, VR};
+    use dicom_dictionary_std::tags;
+    use dicom_object::{FileMetaTableBuilder, InMemDicomObject};
+
+    fn factory(transfer_syntax: &str) -> Vec<u8> {
+        let mut item = InMemDicomObject::new_empty();
+        item.put(DataElement::new(
+            tags::CODE_VALUE,
+            VR::SH,
+            PrimitiveValue::from("T-04000"),
+        ));
+
+        let mut obj = InMemDicomObject::new_empty();
+        obj.put(DataElement::new(
+            tags::PATIENT_NAME,
+            VR::PN,
+            PrimitiveValue::from("Doe^John"),
+        ));
+        obj.put(DataElement::new(
+            tags::MODALITY,
+            VR::CS,
+            PrimitiveValue::from("CT"),
+        ));
+        obj.put(DataElement::new(
+            tags::ANATOMIC_REGION_SEQUENCE,
+            VR::SQ,
+            DicomValue::Sequence {
+                items: vec![item].into(),
+                size: Length::UNDEFINED,
+            },
+        ));
+        obj.put(DataElement::new(
+            tags::PIXEL_DATA,
+            VR::OW,
+            PrimitiveValue::from((0..2048).map(|i| i as u8).collect::<Vec<u8>>()),
+        ));
+        obj.put(DataElement::new(
+            Tag(0xFFFC, 0xFFFC),
+            VR::OB,
+            PrimitiveValue::from(vec![0_u8; 4]),
+        ));
+
+        let mut data = Vec::new();
+        obj.with_meta(
+            FileMetaTableBuilder::new()
+                .transfer_syntax(transfer_syntax)
+                .media_storage_sop_class_uid("1.2.840.10008.5.1.4.1.1.2")
+                .media_storage_sop_instance_uid("1.2.3.4"),
+        )
+        .unwrap()
+        .write_all(&mut data)
+        .unwrap();
+        data
+    }
+
+    fn meta() -> AnonymizerMeta {
+        let mut builder = Anonymizer::meta_builder();
+        builder.patient_name(TagAction::Change("Anonymous".to_string()));
+        builder.build().unwrap()
+    }
+
+    fn stream(data: &[u8], meta: AnonymizerMeta, chunk_size: usize) -> Vec<u8> {
+        let mut stream = StreamingAnonymizer::new(meta);
+        let mut output = Vec::new();
+        for chunk in data.chunks(chunk_size) {
+            output.extend(stream.push(chunk).unwrap());
+        }
+        output.extend(stream.finish().unwrap());
+        output
+    }
+
+    fn anonymize(data: &[u8], meta: AnonymizerMeta) -> Vec<u8> {
+        let mut any = Anonymizer::from_bytes(data).unwrap();
+        any.meta(meta);
+        any.anonymize().unwrap();
+        any.to_bytes().unwrap()
+    }
+
+    #[test]
+    fn streams_the_same_bytes_as_a_whole_file() {
+        for transfer_syntax in ["1.2.840.10008.1.2", "1.2.840.10008.1.2.1"] {
+            let data = factory(transfer_syntax);
+            let expected = anonymize(&data, meta());
+
+            for chunk_size in [1, 7, 100, data.len()] {
+                assert_eq!(stream(&data, meta(), chunk_size), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn passes_the_pixel_data_through_after_the_header() {
+        let data = factory("1.2.840.10008.1.2.1");
+        // Pixel data and trailing padding, each with a 12 byte header
+        let header_len = data.len() - (12 + 2048) - (12 + 4);
+        let mut stream = StreamingAnonymizer::new(meta());
+
+        assert!(stream.push(&data[..header_len]).unwrap().is_empty());
+        assert_eq!(stream.action(), None);
+
+        let output = stream.push(&data[header_len..header_len + 12]).unwrap();
+        assert!(output.ends_with(&data[header_len..header_len + 12]));
+        assert_eq!(stream.action(), Some(FilterAction::Anonymize));
+
+        let rest = &data[header_len + 12..];
+        assert_eq!(stream.push(rest).unwrap(), rest);
+        assert!(stream.finish().unwrap().is_empty());
+        assert_eq!(stream.action(), Some(FilterAction::Anonymize));
+        assert!(matches!(stream.push(rest), Err(Error::Stream(_))));
+    }
+
+    #[test]
+    fn streams_every_file_layout() {
+        for transfer_syntax in ["1.2.840.10008.1.2", "1.2.840.10008.1.2.1"] {
+            let data = factory(transfer_syntax);
+            let group_length =
+                u32::from_le_bytes([data[140], data[141], data[142], data[143]]) as usize;
+
+            // Without preamble, without magic code and without file meta group
+            for start in [128, 132, 144 + group_length] {
+                let data = &data[start..];
+                assert_eq!(stream(data, meta(), 7), anonymize(data, meta()));
+            }
+        }
+    }
+
+    #[test]
+    fn processes_files_without_pixel_data_when_finished() {
+        let mut obj = InMemDicomObject::new_empty();
+        obj.put(DataElement::new(
+            tags::PATIENT_NAME,
+            VR::PN,
+            PrimitiveValue::from("Doe^John"),
+        ));
+        let mut data = Vec::new();
+        obj.with_meta(
+            FileMetaTableBuilder::new()
+                .transfer_syntax("1.2.840.10008.1.2.1")
+                .media_storage_sop_class_uid("1.2.840.10008.5.1.4.1.1.2")
+                .media_storage_sop_instance_uid("1.2.3.4"),
+        )
+        .unwrap()
+        .write_all(&mut data)
+        .unwrap();
+
+        assert_eq!(stream(&data[128..], meta(), 16), anonymize(&data, meta()));
+    }
+
+    #[test]
+    fn follows_the_filters() {
+        let data = factory("1.2.840.10008.1.2.1");
+
+        let mut meta = meta();
+        meta.filters = vec![FilterRule::new(FilterAction::Copy)];
+        assert_eq!(stream(&data, meta.clone(), 64), data);
+
+        meta.filters = vec![FilterRule::new(FilterAction::Skip)];
+        assert!(stream(&data, meta, 64).is_empty());
+    }
+
+    #[test]
+    fn rejects_what_cant_be_streamed() {
+        let mut stream = StreamingAnonymizer::new(meta());
+        assert!(stream.push(&[0_u8; 200]).is_err());
+
+        let data = factory("1.2.840.10008.1.2.1");
+        let position = data
+            .windows(20)
+            .position(|window| window == b"1.2.840.10008.1.2.1\0")
+            .unwrap();
+        let mut data = data;
+        data[position + 18] = b'2';
+        let mut stream = StreamingAnonymizer::new(meta());
+        assert!(matches!(stream.push(&data), Err(Error::Stream(_))));
+    }
+
+    #[test]
+    fn keeps_the_transfer_syntax() {
+        let data = factory("1.2.840.10008.1.2");
+        let mut meta = meta();
+        meta.transfer_syntax = OutputTransferSyntax::ExplicitVrLittleEndian;
+        let mut converting = StreamingAnonymizer::new(meta.clone());
+        assert!(matches!(
+            converting.push(&data),
+            Err(Error::TransferSyntaxConversion { .. })
+        ));
+
+        meta.transfer_syntax = OutputTransferSyntax::ImplicitVrLittleEndian;
+        assert_eq!(stream(&data, meta.clone(), 64), anonymize(&data, meta));
+    }
+}
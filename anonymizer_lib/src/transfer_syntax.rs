@@ -0,0 +1,230 @@
+use dicom_core::value::Value;
+use dicom_dictionary_std::tags;
+use dicom_encoding::transfer_syntax::{Endianness, TransferSyntaxIndex};
+use dicom_object::{DefaultDicomObject, InMemDicomObject};
+use dicom_transfer_syntax_registry::TransferSyntaxRegistry;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use std::io::Write;
+use std::mem;
+
+use crate::enums::OutputTransferSyntax;
+use crate::error::{Error, Result};
+use crate::file::{meta_builder, written_meta};
+
+pub(crate) const IMPLICIT_VR_LITTLE_ENDIAN: &str = "1.2.840.10008.1.2";
+pub(crate) const EXPLICIT_VR_LITTLE_ENDIAN: &str = "1.2.840.10008.1.2.1";
+pub(crate) const DEFLATED_EXPLICIT_VR_LITTLE_ENDIAN: &str = "1.2.840.10008.1.2.1.99";
+pub(crate) const EXPLICIT_VR_BIG_ENDIAN: &str = "1.2.840.10008.1.2.2";
+
+/// Switches `obj` to `syntax` and returns the UID it had before, `None` if nothing changed.
+///
+/// The dataset is held decoded, so only the file meta group changes. Encapsulated pixel data
+/// can't be decoded and big endian binary values would have to be swapped, both are refused.
+pub(crate) fn convert(
+    obj: &mut DefaultDicomObject,
+    syntax: OutputTransferSyntax,
+) -> Result<Option<String>> {
+    let uid = match syntax.uid() {
+        Some(uid) => uid,
+        None => return Ok(None),
+    };
+    let current = trim(&obj.meta().transfer_syntax);
+    if current == uid {
+        return Ok(None);
+    }
+
+    let refuse = |reason: &str| Error::TransferSyntaxConversion {
+        from: name(&current),
+        to: name(uid),
+        reason: reason.to_string(),
+    };
+    if let Ok(element) = obj.element(tags::PIXEL_DATA) {
+        if let Value::PixelSequence { .. } = element.value() {
+            return Err(refuse(
+                "the pixel data is encapsulated and can't be decoded",
+            ));
+        }
+    }
+    let endianness = TransferSyntaxRegistry
+        .get(&current)
+        .map(|ts| ts.endianness());
+    if endianness != Some(Endianness::Little) {
+        return Err(refuse("only little endian files can be converted"));
+    }
+
+    let meta = meta_builder(obj.meta()).transfer_syntax(uid);
+    let dataset = mem::replace(&mut **obj, InMemDicomObject::new_empty());
+    *obj = dataset.with_meta(meta)?;
+
+    Ok(Some(current))
+}
+
+/// Writes the whole file with the file meta group of `written_meta`. Unlike `write_all` of
+/// dicom-object this covers the deflated transfer syntax: its dataset is Explicit VR Little
+/// Endian compressed with raw deflate.
+pub(crate) fn write_all(obj: &DefaultDicomObject, mut to: impl Write) -> Result<()> {
+    let meta = written_meta(obj)?;
+    let io_error = |source| Error::Io {
+        message: "Could not write the file".to_string(),
+        source,
+    };
+    to.write_all(&[0; 128]).map_err(io_error)?;
+    to.write_all(b"DICM").map_err(io_error)?;
+    meta.write(&mut to).map_err(|e| Error::Parse(Box::new(e)))?;
+
+    let uid = trim(&meta.transfer_syntax);
+    if uid != DEFLATED_EXPLICIT_VR_LITTLE_ENDIAN {
+        let ts = TransferSyntaxRegistry
+            .get(&uid)
+            .ok_or(Error::UnsupportedTransferSyntax(uid))?;
+        obj.write_dataset_with_ts(to, ts)?;
+        return Ok(());
+    }
+
+    let explicit = TransferSyntaxRegistry
+        .get(EXPLICIT_VR_LITTLE_ENDIAN)
+        .expect("Explicit VR Little Endian is built in");
+    let mut deflate = DeflateEncoder::new(to, Compression::default());
+    obj.write_dataset_with_ts(&mut deflate, explicit)?;
+    deflate.finish().map_err(io_error)?;
+
+    Ok(())
+}
+
+fn trim(uid: &str) -> String {
+    uid.trim_end_matches(['\0', ' ']).to_string()
+}
+
+/// The name of the transfer syntax `uid`, or the UID if it's unknown.
+fn name(uid: &str) -> String {
+    TransferSyntaxRegistry
+        .get(uid)
+        .map(|ts| ts.name().to_string())
+        .unwrap_or_else(|| uid.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Anonymizer, Error, OutputTransferSyntax};
+    use dicom_core::value::Value;
+    use dicom_core::{DataElement, PrimitiveValue, VR};
+    use dicom_dictionary_std::tags;
+    use dicom_encoding::transfer_syntax::TransferSyntaxIndex;
+    use dicom_object::{FileMetaTableBuilder, InMemDicomObject};
+    use dicom_transfer_syntax_registry::TransferSyntaxRegistry;
+    use flate2::read::DeflateDecoder;
+
+    fn dataset() -> InMemDicomObject {
+        let mut obj = InMemDicomObject::new_empty();
+        obj.put(DataElement::new(
+            tags::PATIENT_NAME,
+            VR::PN,
+            PrimitiveValue::from("Doe^John"),
+        ));
+        obj.put(DataElement::new(
+            tags::ROWS,
+            VR::US,
+            PrimitiveValue::from(512_u16),
+        ));
+        obj
+    }
+
+    fn anonymize(
+        obj: InMemDicomObject,
+        transfer_syntax: &str,
+        output: OutputTransferSyntax,
+    ) -> Result<Anonymizer, Error> {
+        let obj = obj.with_meta(
+            FileMetaTableBuilder::new()
+                .transfer_syntax(transfer_syntax)
+                .media_storage_sop_class_uid("1.2.840.10008.5.1.4.1.1.2")
+                .media_storage_sop_instance_uid("1.2.3.4"),
+        )?;
+
+        let mut builder = Anonymizer::meta_builder();
+        builder.transfer_syntax(output);
+        let mut any = Anonymizer::from_object(obj)?;
+        any.meta(builder.build().unwrap());
+        any.anonymize()?;
+        Ok(any)
+    }
+
+    #[test]
+    fn converts_the_file_and_its_meta_group() {
+        let any = anonymize(
+            dataset(),
+            "1.2.840.10008.1.2",
+            OutputTransferSyntax::ExplicitVrLittleEndian,
+        )
+        .unwrap();
+
+        let any = Anonymizer::from_bytes(&any.to_bytes().unwrap()).unwrap();
+        assert_eq!(any.transfer_syntax().unwrap(), "1.2.840.10008.1.2.1");
+        let rows = any
+            .elements()
+            .unwrap()
+            .into_iter()
+            .find(|element| element.keyword == "Rows")
+            .unwrap();
+        assert_eq!((rows.vr.as_str(), rows.value.as_str()), ("US", "512"));
+    }
+
+    #[test]
+    fn deflates_the_dataset() {
+        let any = anonymize(
+            dataset(),
+            "1.2.840.10008.1.2.1",
+            OutputTransferSyntax::DeflatedExplicitVrLittleEndian,
+        )
+        .unwrap();
+        assert_eq!(any.transfer_syntax().unwrap(), "1.2.840.10008.1.2.1.99");
+
+        let data = any.to_bytes().unwrap();
+        assert_eq!(&data[128..132], b"DICM");
+        // Preamble, magic code and the group length element, then the rest of the group
+        let group_length = u32::from_le_bytes(data[140..144].try_into().unwrap()) as usize;
+        let deflated = &data[144 + group_length..];
+
+        let explicit = TransferSyntaxRegistry.get("1.2.840.10008.1.2.1").unwrap();
+        let obj = InMemDicomObject::read_dataset_with_ts(DeflateDecoder::new(deflated), explicit)
+            .unwrap();
+        assert_eq!(
+            obj.element(tags::PATIENT_NAME).unwrap().to_str().unwrap(),
+            "Doe^John"
+        );
+    }
+
+    #[test]
+    fn refuses_encapsulated_pixel_data() {
+        let mut obj = dataset();
+        obj.put(DataElement::new(
+            tags::PIXEL_DATA,
+            VR::OB,
+            Value::PixelSequence {
+                offset_table: vec![].into(),
+                fragments: vec![vec![0xFF, 0xD8]].into(),
+            },
+        ));
+
+        let error = anonymize(
+            obj,
+            "1.2.840.10008.1.2.4.50",
+            OutputTransferSyntax::ExplicitVrLittleEndian,
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "The transfer syntax JPEG Baseline (Process 1) can't be converted to Explicit VR Little Endian: the pixel data is encapsulated and can't be decoded"
+        );
+
+        // Without pixel data only the file meta group changes
+        let any = anonymize(
+            dataset(),
+            "1.2.840.10008.1.2.4.50",
+            OutputTransferSyntax::ExplicitVrLittleEndian,
+        )
+        .unwrap();
+        assert_eq!(any.transfer_syntax().unwrap(), "1.2.840.10008.1.2.1");
+    }
+}
@@ -0,0 +1,143 @@
+use anyhow::{anyhow, bail, Result};
+use chrono::{Datelike, NaiveDate};
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum AgeUnit {
+    Days,
+    Weeks,
+    Months,
+    Years,
+}
+
+/// An AS value like `045Y`: three digits and the unit `D`, `W`, `M` or `Y`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Age {
+    value: u16,
+    unit: AgeUnit,
+}
+
+impl Age {
+    pub fn new(value: u16, unit: AgeUnit) -> Result<Self> {
+        if value > 999 {
+            bail!("An age has at most three digits, {} has more", value);
+        }
+        Ok(Self { value, unit })
+    }
+
+    /// The age at `reference` in full years, or in months or days for babies. `None` if
+    /// `reference` is before `birth`.
+    pub fn between(birth: NaiveDate, reference: NaiveDate) -> Option<Self> {
+        if reference < birth {
+            return None;
+        }
+
+        let before_birthday = (reference.month(), reference.day()) < (birth.month(), birth.day());
+        let years = reference.year() - birth.year() - before_birthday as i32;
+        let months = (reference.year() - birth.year()) * 12 + reference.month() as i32
+            - birth.month() as i32
+            - (reference.day() < birth.day()) as i32;
+
+        let (value, unit) = match (years, months) {
+            (1.., _) => (years as u16, AgeUnit::Years),
+            (_, 1..) => (months as u16, AgeUnit::Months),
+            _ => ((reference - birth).num_days() as u16, AgeUnit::Days),
+        };
+        Some(Self {
+            value: value.min(999),
+            unit,
+        })
+    }
+
+    pub fn value(&self) -> u16 {
+        self.value
+    }
+
+    pub fn unit(&self) -> AgeUnit {
+        self.unit
+    }
+
+    /// Full years, e.g. 1 for `018M`.
+    pub fn years(&self) -> u16 {
+        match self.unit {
+            AgeUnit::Days => self.value / 365,
+            AgeUnit::Weeks => self.value / 52,
+            AgeUnit::Months => self.value / 12,
+            AgeUnit::Years => self.value,
+        }
+    }
+}
+
+impl FromStr for Age {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let error = || anyhow!("`{}` has to be three digits and D, W, M or Y, e.g. 045Y", s);
+        if s.len() != 4 || !s[..3].bytes().all(|b| b.is_ascii_digit()) {
+            return Err(error());
+        }
+
+        let unit = match &s[3..] {
+            "D" => AgeUnit::Days,
+            "W" => AgeUnit::Weeks,
+            "M" => AgeUnit::Months,
+            "Y" => AgeUnit::Years,
+            _ => return Err(error()),
+        };
+        Ok(Self {
+            value: s[..3].parse()?,
+            unit,
+        })
+    }
+}
+
+impl fmt::Display for Age {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let unit = match self.unit {
+            AgeUnit::Days => 'D',
+            AgeUnit::Weeks => 'W',
+            AgeUnit::Months => 'M',
+            AgeUnit::Years => 'Y',
+        };
+        write!(f, "{:03}{}", self.value, unit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Age, AgeUnit};
+    use chrono::NaiveDate;
+    use std::str::FromStr;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd(y, m, d)
+    }
+
+    #[test]
+    fn parses_and_writes_as_values() {
+        for value in ["045Y", "011M", "003W", "000D"] {
+            assert_eq!(Age::from_str(value).unwrap().to_string(), value);
+        }
+        for value in ["45Y", "045", "045y", "0450Y"] {
+            assert!(Age::from_str(value).is_err(), "{}", value);
+        }
+        assert_eq!(Age::from_str("018M").unwrap().years(), 1);
+    }
+
+    #[test]
+    fn counts_full_years_months_or_days() {
+        let age = |birth, reference| Age::between(birth, reference).unwrap().to_string();
+
+        assert_eq!(age(date(1980, 6, 15), date(2020, 6, 14)), "039Y");
+        assert_eq!(age(date(1980, 6, 15), date(2020, 6, 15)), "040Y");
+        assert_eq!(age(date(2020, 1, 31), date(2020, 12, 30)), "010M");
+        assert_eq!(age(date(2020, 1, 1), date(2020, 1, 20)), "019D");
+        assert_eq!(Age::between(date(2020, 1, 2), date(2020, 1, 1)), None);
+        assert_eq!(
+            Age::new(90, AgeUnit::Years).unwrap(),
+            Age::from_str("090Y").unwrap()
+        );
+    }
+}
@@ -0,0 +1,661 @@
+use anyhow::{anyhow, bail, Result};
+use chrono::{Datelike, FixedOffset, NaiveDate, Timelike};
+use dicom_core::VR;
+use serde::de::{self, MapAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::marker::PhantomData;
+use std::str::FromStr;
+
+/// A DA value: `YYYY`, `YYYYMM` or `YYYYMMDD`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PartialDate {
+    year: u16,
+    month: Option<u8>,
+    /// Only set together with the month
+    day: Option<u8>,
+}
+
+/// A TM value: `HH`, `HHMM`, `HHMMSS` or `HHMMSS.F` with 1 to 6 fraction digits.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PartialTime {
+    hour: u8,
+    minute: Option<u8>,
+    second: Option<u8>,
+    /// The digits as written, `.10` and `.1` are different values
+    fraction: Option<String>,
+}
+
+/// A DT value: a date, a time if the date is complete, and an optional UTC offset `&ZZXX`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PartialDateTime {
+    date: PartialDate,
+    time: Option<PartialTime>,
+    /// Minutes east of UTC
+    offset: Option<i16>,
+}
+
+/// A date, time or datetime value as it is written to a DICOM element, with the precision it was
+/// given in. Ranges (`start-end`, `start-` or `-end`) are the notation of DICOM queries.
+///
+/// Serialized as the DICOM string, e.g. `"19800101"`, `"1980"` or `"1980-1990"`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Temporal<T> {
+    Single(T),
+    Range { start: Option<T>, end: Option<T> },
+}
+
+pub type DaValue = Temporal<PartialDate>;
+pub type TmValue = Temporal<PartialTime>;
+pub type DtValue = Temporal<PartialDateTime>;
+
+/// The values a `Temporal` can hold.
+pub trait TemporalPart: FromStr<Err = anyhow::Error> + fmt::Display + Sized {
+    /// The VR elements with these values have
+    const VR: VR;
+
+    /// The value with the precision of a chrono datetime, used for the `{"data": "..."}` values
+    /// of older config files.
+    fn from_chrono(value: &chrono::DateTime<FixedOffset>) -> Self;
+}
+
+/// Parses `len` ASCII digits at `position`, `None` if the string ends before.
+fn digits(s: &str, position: usize, len: usize) -> Result<Option<u32>> {
+    match s.get(position..position + len) {
+        None if s.len() <= position => Ok(None),
+        None => bail!("`{}` is cut off after {} characters", s, s.len()),
+        Some(part) if part.bytes().all(|b| b.is_ascii_digit()) => Ok(Some(part.parse()?)),
+        Some(part) => bail!("`{}` in `{}` isn't a number", part, s),
+    }
+}
+
+fn in_range(value: Option<u32>, min: u32, max: u32, name: &str) -> Result<Option<u8>> {
+    match value {
+        Some(value) if value < min || value > max => {
+            bail!("{} {} isn't between {} and {}", name, value, min, max)
+        }
+        value => Ok(value.map(|value| value as u8)),
+    }
+}
+
+impl PartialDate {
+    pub fn from_ymd(year: u16, month: Option<u8>, day: Option<u8>) -> Result<Self> {
+        Self::from_str(
+            &[
+                Some(format!("{:04}", year)),
+                month.map(|month| format!("{:02}", month)),
+                day.map(|day| format!("{:02}", day)),
+            ]
+            .into_iter()
+            .flatten()
+            .collect::<String>(),
+        )
+    }
+
+    pub fn year(&self) -> u16 {
+        self.year
+    }
+
+    pub fn month(&self) -> Option<u8> {
+        self.month
+    }
+
+    pub fn day(&self) -> Option<u8> {
+        self.day
+    }
+
+    /// Only the year, e.g. `1980` for `19800612`.
+    pub fn year_only(&self) -> Self {
+        Self {
+            year: self.year,
+            month: None,
+            day: None,
+        }
+    }
+
+    /// January 1st of the year, e.g. `19800101` for `19800612`.
+    pub fn january_first(&self) -> Self {
+        Self {
+            year: self.year,
+            month: Some(1),
+            day: Some(1),
+        }
+    }
+
+    /// The calendar date, if it is complete.
+    pub fn to_naive_date(&self) -> Option<NaiveDate> {
+        NaiveDate::from_ymd_opt(self.year as i32, self.month? as u32, self.day? as u32)
+    }
+}
+
+impl FromStr for PartialDate {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if !matches!(s.len(), 4 | 6 | 8) {
+            bail!("`{}` has to be YYYY, YYYYMM or YYYYMMDD", s);
+        }
+
+        let year = digits(s, 0, 4)?.unwrap_or_default() as u16;
+        let month = in_range(digits(s, 4, 2)?, 1, 12, "Month")?;
+        let day = in_range(digits(s, 6, 2)?, 1, 31, "Day")?;
+
+        let date = Self { year, month, day };
+        if day.is_some() && date.to_naive_date().is_none() {
+            bail!("`{}` isn't a date of the calendar", s);
+        }
+
+        Ok(date)
+    }
+}
+
+impl fmt::Display for PartialDate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}", self.year)?;
+        if let Some(month) = self.month {
+            write!(f, "{:02}", month)?;
+        }
+        if let Some(day) = self.day {
+            write!(f, "{:02}", day)?;
+        }
+        Ok(())
+    }
+}
+
+impl TemporalPart for PartialDate {
+    const VR: VR = VR::DA;
+
+    fn from_chrono(value: &chrono::DateTime<FixedOffset>) -> Self {
+        Self {
+            year: value.year() as u16,
+            month: Some(value.month() as u8),
+            day: Some(value.day() as u8),
+        }
+    }
+}
+
+impl PartialTime {
+    pub fn hour(&self) -> u8 {
+        self.hour
+    }
+
+    pub fn minute(&self) -> Option<u8> {
+        self.minute
+    }
+
+    pub fn second(&self) -> Option<u8> {
+        self.second
+    }
+
+    pub fn fraction(&self) -> Option<&str> {
+        self.fraction.as_deref()
+    }
+}
+
+impl FromStr for PartialTime {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (hms, fraction) = match s.split_once('.') {
+            Some((hms, fraction)) => (hms, Some(fraction)),
+            None => (s, None),
+        };
+        if !matches!(hms.len(), 2 | 4 | 6) {
+            bail!("`{}` has to be HH, HHMM, HHMMSS or HHMMSS.FFFFFF", s);
+        }
+
+        let hour = in_range(digits(hms, 0, 2)?, 0, 23, "Hour")?.unwrap_or_default();
+        let minute = in_range(digits(hms, 2, 2)?, 0, 59, "Minute")?;
+        // 60 is a leap second
+        let second = in_range(digits(hms, 4, 2)?, 0, 60, "Second")?;
+
+        let fraction = match fraction {
+            None => None,
+            Some(_) if second.is_none() => {
+                bail!("`{}` has a fraction without seconds", s)
+            }
+            Some(f) if (1..=6).contains(&f.len()) && f.bytes().all(|b| b.is_ascii_digit()) => {
+                Some(f.to_string())
+            }
+            Some(f) => bail!("The fraction `{}` has to be 1 to 6 digits", f),
+        };
+
+        Ok(Self {
+            hour,
+            minute,
+            second,
+            fraction,
+        })
+    }
+}
+
+impl fmt::Display for PartialTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02}", self.hour)?;
+        if let Some(minute) = self.minute {
+            write!(f, "{:02}", minute)?;
+        }
+        if let Some(second) = self.second {
+            write!(f, "{:02}", second)?;
+        }
+        if let Some(fraction) = &self.fraction {
+            write!(f, ".{}", fraction)?;
+        }
+        Ok(())
+    }
+}
+
+impl TemporalPart for PartialTime {
+    const VR: VR = VR::TM;
+
+    fn from_chrono(value: &chrono::DateTime<FixedOffset>) -> Self {
+        Self {
+            hour: value.hour() as u8,
+            minute: Some(value.minute() as u8),
+            second: Some(value.second() as u8),
+            fraction: None,
+        }
+    }
+}
+
+impl PartialDateTime {
+    pub fn date(&self) -> &PartialDate {
+        &self.date
+    }
+
+    pub fn time(&self) -> Option<&PartialTime> {
+        self.time.as_ref()
+    }
+
+    /// Minutes east of UTC
+    pub fn offset(&self) -> Option<i16> {
+        self.offset
+    }
+}
+
+impl FromStr for PartialDateTime {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (value, offset) = match s.rfind(['+', '-']) {
+            Some(position) => (&s[..position], Some(&s[position..])),
+            None => (s, None),
+        };
+
+        let offset = match offset {
+            None => None,
+            Some(offset) if offset.len() == 5 => {
+                let hours = digits(offset, 1, 2)?.unwrap_or_default() as i16;
+                let minutes = in_range(digits(offset, 3, 2)?, 0, 59, "Offset minute")?
+                    .unwrap_or_default() as i16;
+                let minutes = hours * 60 + minutes;
+                let minutes = match &offset[..1] {
+                    "-" => -minutes,
+                    _ => minutes,
+                };
+                if !(-12 * 60..=14 * 60).contains(&minutes) {
+                    bail!("The offset `{}` isn't between -1200 and +1400", offset);
+                }
+                Some(minutes)
+            }
+            Some(offset) => bail!("The offset `{}` has to be &ZZXX, e.g. +0100", offset),
+        };
+
+        let split = value.len().min(8);
+        let date = PartialDate::from_str(&value[..split])
+            .map_err(|e| anyhow!("The date of `{}` is invalid: {}", s, e))?;
+        let time = match &value[split..] {
+            "" => None,
+            _ if date.day.is_none() => bail!("`{}` has a time without a complete date", s),
+            time => Some(
+                PartialTime::from_str(time)
+                    .map_err(|e| anyhow!("The time of `{}` is invalid: {}", s, e))?,
+            ),
+        };
+
+        Ok(Self { date, time, offset })
+    }
+}
+
+impl fmt::Display for PartialDateTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.date)?;
+        if let Some(time) = &self.time {
+            write!(f, "{}", time)?;
+        }
+        if let Some(offset) = self.offset {
+            let sign = if offset < 0 { '-' } else { '+' };
+            write!(
+                f,
+                "{}{:02}{:02}",
+                sign,
+                offset.abs() / 60,
+                offset.abs() % 60
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl TemporalPart for PartialDateTime {
+    const VR: VR = VR::DT;
+
+    fn from_chrono(value: &chrono::DateTime<FixedOffset>) -> Self {
+        Self {
+            date: PartialDate::from_chrono(value),
+            time: Some(PartialTime::from_chrono(value)),
+            offset: Some((value.offset().local_minus_utc() / 60) as i16),
+        }
+    }
+}
+
+impl<T: TemporalPart> Temporal<T> {
+    pub fn vr(&self) -> VR {
+        T::VR
+    }
+
+    /// The value if it isn't a range.
+    pub fn single(&self) -> Option<&T> {
+        match self {
+            Temporal::Single(value) => Some(value),
+            Temporal::Range { .. } => None,
+        }
+    }
+}
+
+impl Temporal<PartialDate> {
+    /// Parses `yyyy-mm-dd` (or `yyyy-m-d`) as well as the DICOM notation, the CLI flags and config
+    /// files accept both.
+    pub fn parse_date(value: &str) -> Result<Self> {
+        match NaiveDate::parse_from_str(value.trim(), "%Y-%m-%d") {
+            Ok(date) => Ok(Temporal::Single(PartialDate {
+                year: date.year() as u16,
+                month: Some(date.month() as u8),
+                day: Some(date.day() as u8),
+            })),
+            Err(_) => Self::from_str(value).map_err(|_| {
+                anyhow!(
+                    "`{}` has to be yyyy-mm-dd or a DICOM date like 19800101, 198001 or 1980",
+                    value
+                )
+            }),
+        }
+    }
+
+    /// `yyyy-mm-dd` for complete dates, the DICOM notation otherwise.
+    pub fn to_date_string(&self) -> String {
+        match self.single().and_then(PartialDate::to_naive_date) {
+            Some(date) => date.format("%Y-%m-%d").to_string(),
+            None => self.to_string(),
+        }
+    }
+}
+
+impl<T: TemporalPart> From<T> for Temporal<T> {
+    fn from(value: T) -> Self {
+        Temporal::Single(value)
+    }
+}
+
+impl<T: TemporalPart> FromStr for Temporal<T> {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let error = match T::from_str(s) {
+            Ok(value) => return Ok(Temporal::Single(value)),
+            Err(e) => e,
+        };
+
+        let part = |value: &str| match value {
+            "" => Ok(None),
+            value => T::from_str(value).map(Some),
+        };
+        for (position, _) in s.match_indices('-') {
+            if let (Ok(start), Ok(end)) = (part(&s[..position]), part(&s[position + 1..])) {
+                if start.is_some() || end.is_some() {
+                    return Ok(Temporal::Range { start, end });
+                }
+            }
+        }
+
+        Err(anyhow!("`{}` isn't a valid {} value: {}", s, T::VR, error))
+    }
+}
+
+impl<T: TemporalPart> fmt::Display for Temporal<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Temporal::Single(value) => write!(f, "{}", value),
+            Temporal::Range { start, end } => {
+                if let Some(start) = start {
+                    write!(f, "{}", start)?;
+                }
+                write!(f, "-")?;
+                if let Some(end) = end {
+                    write!(f, "{}", end)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl<T: TemporalPart> Serialize for Temporal<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de, T: TemporalPart> Deserialize<'de> for Temporal<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct TemporalVisitor<T>(PhantomData<T>);
+
+        impl<'de, T: TemporalPart> Visitor<'de> for TemporalVisitor<T> {
+            type Value = Temporal<T>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                write!(formatter, "a {} value like `19800101`", T::VR)
+            }
+
+            fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Temporal::from_str(value).map_err(de::Error::custom)
+            }
+
+            /// `{"data": "yyyy-mm-dd hh:mm:ss +hh:mm"}`, written by earlier versions
+            fn visit_map<V>(self, mut map: V) -> Result<Self::Value, V::Error>
+            where
+                V: MapAccess<'de>,
+            {
+                let mut data: Option<String> = None;
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "data" if data.is_none() => data = Some(map.next_value()?),
+                        "data" => return Err(de::Error::duplicate_field("data")),
+                        _ => return Err(de::Error::unknown_field(&key, &["data"])),
+                    }
+                }
+                let data = data.ok_or_else(|| de::Error::missing_field("data"))?;
+
+                let value = chrono::DateTime::parse_from_str(&data, "%F %T %:z").map_err(|_| {
+                    de::Error::custom(format!(
+                        "`{}` has to be in the format yyyy-mm-dd hh:mm:ss +hh:mm",
+                        data
+                    ))
+                })?;
+                Ok(Temporal::Single(T::from_chrono(&value)))
+            }
+        }
+
+        deserializer.deserialize_any(TemporalVisitor(PhantomData))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DaValue, DtValue, PartialDate, Temporal, TmValue};
+    use dicom_core::VR;
+    use std::str::FromStr;
+
+    fn round_trip<T>(values: &[&str])
+    where
+        T: FromStr<Err = anyhow::Error> + ToString + serde::Serialize + serde::de::DeserializeOwned,
+    {
+        for value in values {
+            let parsed = T::from_str(value).unwrap();
+            assert_eq!(&parsed.to_string(), value);
+
+            let json = serde_json::to_string(&parsed).unwrap();
+            assert_eq!(json, format!("\"{}\"", value));
+            assert_eq!(
+                serde_json::from_str::<T>(&json).unwrap().to_string(),
+                *value
+            );
+        }
+    }
+
+    mod da {
+        use super::*;
+
+        #[test]
+        fn keeps_the_precision() {
+            round_trip::<DaValue>(&[
+                "1980",
+                "198002",
+                "19800229",
+                "1980-1990",
+                "-19800101",
+                "1980-",
+            ]);
+        }
+
+        #[test]
+        fn rejects_invalid_dates() {
+            for value in [
+                "198",
+                "19801",
+                "19801301",
+                "19810229",
+                "1980-02-01x",
+                "-",
+                "abcd",
+            ] {
+                assert!(DaValue::from_str(value).is_err(), "{}", value);
+            }
+        }
+
+        #[test]
+        fn has_components() {
+            let date = PartialDate::from_str("198002").unwrap();
+
+            assert_eq!(
+                (date.year(), date.month(), date.day()),
+                (1980, Some(2), None)
+            );
+            assert_eq!(date.to_naive_date(), None);
+            assert_eq!(date.year_only().to_string(), "1980");
+            assert_eq!(date.january_first().to_string(), "19800101");
+            assert_eq!(
+                PartialDate::from_ymd(1980, Some(2), Some(29))
+                    .unwrap()
+                    .to_string(),
+                "19800229"
+            );
+            assert_eq!(DaValue::from_str("1980").unwrap().vr(), VR::DA);
+        }
+
+        #[test]
+        fn parses_dashed_dates() {
+            assert_eq!(
+                DaValue::parse_date("1980-2-9").unwrap().to_string(),
+                "19800209"
+            );
+            assert_eq!(DaValue::parse_date("198002").unwrap().to_string(), "198002");
+            assert_eq!(
+                DaValue::parse_date("19800209").unwrap().to_date_string(),
+                "1980-02-09"
+            );
+            assert_eq!(
+                DaValue::parse_date("1980").unwrap().to_date_string(),
+                "1980"
+            );
+            assert!(DaValue::parse_date("2022-15-03").is_err());
+        }
+
+        #[test]
+        fn reads_the_data_values_of_earlier_versions() {
+            let value: DaValue =
+                serde_json::from_str(r#"{"data": "2000-11-05 12:14:05 +01:00"}"#).unwrap();
+
+            assert_eq!(value.to_string(), "20001105");
+            assert!(serde_json::from_str::<DaValue>(r#"{"data": "2000-11-05"}"#).is_err());
+        }
+    }
+
+    mod tm {
+        use super::*;
+
+        #[test]
+        fn keeps_the_precision() {
+            round_trip::<TmValue>(&[
+                "07",
+                "0730",
+                "073015",
+                "073015.1",
+                "073015.100000",
+                "07-1530",
+            ]);
+        }
+
+        #[test]
+        fn rejects_invalid_times() {
+            for value in ["7", "2400", "0760", "073015.", "0730.5", "073015.1234567"] {
+                assert!(TmValue::from_str(value).is_err(), "{}", value);
+            }
+        }
+    }
+
+    mod dt {
+        use super::*;
+
+        #[test]
+        fn keeps_the_precision() {
+            round_trip::<DtValue>(&[
+                "1980",
+                "19800101",
+                "1980010107",
+                "19800101073015.123",
+                "19800101073015+0100",
+                "1980-0500",
+                "19800101-0500",
+                "19800101073015-19800102",
+            ]);
+        }
+
+        #[test]
+        fn prefers_offsets_over_ranges() {
+            let value = DtValue::from_str("19800101-0500").unwrap();
+
+            match value {
+                Temporal::Single(value) => assert_eq!(value.offset(), Some(-300)),
+                Temporal::Range { .. } => panic!("Should be a single value"),
+            }
+        }
+
+        #[test]
+        fn rejects_invalid_datetimes() {
+            for value in ["198001010", "198001073015", "19800101+01", "19800101+1500"] {
+                assert!(DtValue::from_str(value).is_err(), "{}", value);
+            }
+        }
+    }
+}
@@ -0,0 +1,323 @@
+use anyhow::{anyhow, bail, Error, Result};
+use dicom_core::Tag;
+use dicom_dictionary_std::tags;
+use dicom_object::InMemDicomObject;
+use regex::Regex;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::FilterAction;
+
+/// Decides what happens to a file in a batch run. Every condition that is set has to match, a rule
+/// without conditions matches every file.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FilterRule {
+    pub action: FilterAction,
+
+    /// Modality (0008,0060) is one of these values, e.g. `["CT", "MR"]`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub modality: Vec<String>,
+
+    /// SOP Class UID (0008,0016) is one of these values
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sop_class_uid: Vec<String>,
+
+    /// Series Description (0008,103E) matches this regular expression
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub series_description: Option<Pattern>,
+
+    /// Image Type (0008,0008) contains every one of these values, e.g. `["ORIGINAL"]`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub image_type: Vec<String>,
+}
+
+impl FilterRule {
+    pub fn new(action: FilterAction) -> Self {
+        Self {
+            action,
+            ..Default::default()
+        }
+    }
+
+    /// Action of the first rule that matches `obj`, `FilterAction::Anonymize` if none does.
+    pub fn decide(rules: &[FilterRule], obj: &InMemDicomObject) -> FilterAction {
+        rules
+            .iter()
+            .find(|rule| rule.matches(obj))
+            .map(|rule| rule.action)
+            .unwrap_or_default()
+    }
+
+    pub fn matches(&self, obj: &InMemDicomObject) -> bool {
+        let one_of = |allowed: &[String], tag: Tag| {
+            allowed.is_empty()
+                || values(obj, tag)
+                    .first()
+                    .map(|value| allowed.iter().any(|item| item.eq_ignore_ascii_case(value)))
+                    .unwrap_or(false)
+        };
+
+        let series_description = match &self.series_description {
+            None => true,
+            Some(pattern) => pattern
+                .0
+                .is_match(&values(obj, tags::SERIES_DESCRIPTION).join("\\")),
+        };
+
+        let image_type = values(obj, tags::IMAGE_TYPE);
+        let contains_image_type = self.image_type.iter().all(|wanted| {
+            image_type
+                .iter()
+                .any(|value| value.eq_ignore_ascii_case(wanted))
+        });
+
+        one_of(&self.modality, tags::MODALITY)
+            && one_of(&self.sop_class_uid, tags::SOP_CLASS_UID)
+            && series_description
+            && contains_image_type
+    }
+}
+
+/// Values of `tag` without the padding, empty if the element is missing.
+fn values(obj: &InMemDicomObject, tag: Tag) -> Vec<String> {
+    obj.element(tag)
+        .ok()
+        .and_then(|element| element.to_multi_str().ok())
+        .map(|values| {
+            values
+                .iter()
+                .map(|value| value.trim_end_matches([' ', '\0']).to_string())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Parses the `ACTION[:CONDITION;...]` notation used by the CLI and version 1 config files, e.g.
+/// `skip:modality=SR,PR` or `anonymize:modality=CT;series_description=^Thorax;image_type=ORIGINAL`.
+impl FromStr for FilterRule {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let (action, conditions) = value.split_once(':').unwrap_or((value, ""));
+        let mut rule = FilterRule::new(FilterAction::from_str(action)?);
+
+        for condition in conditions.split(';').filter(|c| !c.trim().is_empty()) {
+            let (key, value) = condition
+                .split_once('=')
+                .ok_or_else(|| anyhow!("Filter condition `{}` has to be key=value", condition))?;
+            let list = || {
+                value
+                    .split(',')
+                    .map(|item| item.trim().to_string())
+                    .filter(|item| !item.is_empty())
+                    .collect::<Vec<_>>()
+            };
+
+            match key.trim().replace('-', "_").as_str() {
+                "modality" => rule.modality = list(),
+                "sop_class_uid" => rule.sop_class_uid = list(),
+                "series_description" => rule.series_description = Some(Pattern::from_str(value)?),
+                "image_type" => rule.image_type = list(),
+                other => bail!(
+                    "Unknown filter condition `{}`, expected one of: modality, sop_class_uid, series_description, image_type",
+                    other
+                ),
+            }
+        }
+
+        Ok(rule)
+    }
+}
+
+/// Formats the rule in the notation of `FromStr`.
+impl fmt::Display for FilterRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut conditions = Vec::new();
+        if !self.modality.is_empty() {
+            conditions.push(format!("modality={}", self.modality.join(",")));
+        }
+        if !self.sop_class_uid.is_empty() {
+            conditions.push(format!("sop_class_uid={}", self.sop_class_uid.join(",")));
+        }
+        if let Some(pattern) = &self.series_description {
+            conditions.push(format!("series_description={}", pattern));
+        }
+        if !self.image_type.is_empty() {
+            conditions.push(format!("image_type={}", self.image_type.join(",")));
+        }
+
+        let action = self.action.to_string().to_ascii_lowercase();
+        match conditions.is_empty() {
+            true => write!(f, "{}", action),
+            false => write!(f, "{}:{}", action, conditions.join(";")),
+        }
+    }
+}
+
+/// A regular expression that is written as a plain string in config files.
+#[derive(Debug, Clone)]
+pub struct Pattern(Regex);
+
+impl Pattern {
+    pub(crate) fn regex(&self) -> &Regex {
+        &self.0
+    }
+}
+
+impl FromStr for Pattern {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        Regex::new(value)
+            .map(Pattern)
+            .map_err(|e| anyhow!("`{}` isn't a valid regular expression: {}", value, e))
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.as_str())
+    }
+}
+
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_str() == other.0.as_str()
+    }
+}
+
+impl Eq for Pattern {}
+
+impl Serialize for Pattern {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.0.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Pattern {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let raw = String::deserialize(deserializer)?;
+        Pattern::from_str(&raw).map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FilterRule;
+    use crate::FilterAction;
+    use dicom_core::{DataElement, PrimitiveValue, VR};
+    use dicom_dictionary_std::tags;
+    use dicom_object::InMemDicomObject;
+    use std::str::FromStr;
+
+    fn factory(modality: &str, description: &str, image_type: &[&str]) -> InMemDicomObject {
+        let mut obj = InMemDicomObject::new_empty();
+        obj.put(DataElement::new(
+            tags::MODALITY,
+            VR::CS,
+            PrimitiveValue::from(modality),
+        ));
+        obj.put(DataElement::new(
+            tags::SERIES_DESCRIPTION,
+            VR::LO,
+            PrimitiveValue::from(description),
+        ));
+        obj.put(DataElement::new(
+            tags::IMAGE_TYPE,
+            VR::CS,
+            PrimitiveValue::Strs(image_type.iter().map(|v| v.to_string()).collect()),
+        ));
+        obj
+    }
+
+    mod from_str {
+        use super::{FilterAction, FilterRule};
+        use std::str::FromStr;
+
+        #[test]
+        fn ok() {
+            let rule = FilterRule::from_str(
+                "anonymize:modality=CT, MR;series-description=^Thorax;image_type=ORIGINAL",
+            )
+            .unwrap();
+
+            assert_eq!(rule.action, FilterAction::Anonymize);
+            assert_eq!(rule.modality, vec!["CT", "MR"]);
+            assert_eq!(
+                rule.series_description.as_ref().unwrap().to_string(),
+                "^Thorax"
+            );
+            assert_eq!(rule.image_type, vec!["ORIGINAL"]);
+            assert_eq!(FilterRule::from_str(&rule.to_string()).unwrap(), rule);
+            assert_eq!(
+                FilterRule::from_str("skip").unwrap(),
+                FilterRule::new(FilterAction::Skip)
+            );
+        }
+
+        #[test]
+        fn error() {
+            assert!(FilterRule::from_str("delete:modality=CT").is_err());
+            assert!(FilterRule::from_str("skip:body_part=HEAD").is_err());
+            assert!(FilterRule::from_str("skip:modality").is_err());
+            assert!(FilterRule::from_str("skip:series_description=[").is_err());
+        }
+    }
+
+    #[test]
+    fn first_matching_rule_wins() {
+        let rules = vec![
+            FilterRule::from_str("skip:modality=SR,PR").unwrap(),
+            FilterRule::from_str("copy:image_type=DERIVED").unwrap(),
+            FilterRule::from_str("anonymize:modality=CT;series_description=(?i)thorax").unwrap(),
+            FilterRule::from_str("skip").unwrap(),
+        ];
+
+        let decide = |obj: InMemDicomObject| FilterRule::decide(&rules, &obj);
+
+        assert_eq!(decide(factory("SR", "", &[])), FilterAction::Skip);
+        assert_eq!(
+            decide(factory("CT", "Scout", &["DERIVED", "SECONDARY"])),
+            FilterAction::Copy
+        );
+        assert_eq!(
+            decide(factory("CT", "THORAX 1.0 ", &["ORIGINAL", "PRIMARY"])),
+            FilterAction::Anonymize
+        );
+        assert_eq!(
+            decide(factory("MR", "Thorax", &["ORIGINAL"])),
+            FilterAction::Skip
+        );
+    }
+
+    #[test]
+    fn missing_attributes_do_not_match() {
+        let rule = FilterRule::from_str("skip:modality=CT").unwrap();
+
+        assert!(!rule.matches(&InMemDicomObject::new_empty()));
+        assert_eq!(
+            FilterRule::decide(&[rule], &InMemDicomObject::new_empty()),
+            FilterAction::Anonymize
+        );
+    }
+
+    #[test]
+    fn can_be_serialized() {
+        let rule = FilterRule::from_str("skip:modality=SR;series_description=^Dose").unwrap();
+        let json = serde_json::to_string(&rule).unwrap();
+
+        assert_eq!(
+            json,
+            r#"{"action":"Skip","modality":["SR"],"series_description":"^Dose"}"#
+        );
+        assert_eq!(serde_json::from_str::<FilterRule>(&json).unwrap(), rule);
+    }
+}
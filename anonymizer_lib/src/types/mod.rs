@@ -1,5 +1,15 @@
-mod dicom_date_time;
+mod age;
+mod date_time;
+mod filter;
+mod script;
+mod sex_mapping;
 mod tag;
 
-pub use dicom_date_time::CustomDicomDateTime;
+pub use age::{Age, AgeUnit};
+pub use date_time::{
+    DaValue, DtValue, PartialDate, PartialDateTime, PartialTime, Temporal, TemporalPart, TmValue,
+};
+pub use filter::{FilterRule, Pattern};
+pub use script::ScriptRule;
+pub use sex_mapping::SexMapping;
 pub use tag::CustomTag;
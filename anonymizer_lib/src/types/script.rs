@@ -0,0 +1,67 @@
+use anyhow::{anyhow, bail, Error, Result};
+use dicom_core::dictionary::{DataDictionary, DictionaryEntry};
+use dicom_core::Tag;
+use dicom_dictionary_std::StandardDataDictionary;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::PathBuf;
+use std::slice;
+use std::str::FromStr;
+
+use crate::script::Scripts;
+
+/// Computes the new value of an attribute with a Rhai script file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScriptRule {
+    /// Keyword of the attribute, e.g. `PatientID`
+    pub attribute: String,
+    pub file: PathBuf,
+}
+
+impl ScriptRule {
+    pub fn tag(&self) -> Result<Tag> {
+        StandardDataDictionary
+            .by_name(&self.attribute)
+            .map(|entry| entry.tag())
+            .ok_or_else(|| {
+                anyhow!(
+                    "`{}` isn't a keyword of the DICOM dictionary",
+                    self.attribute
+                )
+            })
+    }
+
+    /// Reads and compiles the script, to report a missing file or a syntax error before the
+    /// first DICOM file.
+    pub fn check(&self) -> crate::Result<()> {
+        Scripts::load(slice::from_ref(self), None).map(|_| ())
+    }
+}
+
+/// Parses the `ATTRIBUTE=FILE` notation used by the CLI and version 1 config files, e.g.
+/// `PatientID=rules/patient_id.rhai`.
+impl FromStr for ScriptRule {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let (attribute, file) = match value.split_once('=') {
+            Some((attribute, file)) if !file.trim().is_empty() => (attribute, file),
+            _ => bail!("Script rule `{}` has to be ATTRIBUTE=FILE", value),
+        };
+        let rule = ScriptRule {
+            attribute: attribute.trim().to_string(),
+            file: PathBuf::from(file.trim()),
+        };
+
+        rule.tag()?;
+        Ok(rule)
+    }
+}
+
+/// Formats the rule in the notation of `FromStr`.
+impl fmt::Display for ScriptRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.attribute, self.file.display())
+    }
+}
@@ -0,0 +1,116 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::PatientSex;
+
+/// Replacements for the Patient Sex values found in files, applied when the sex is kept.
+///
+/// Keys are the values as they are found, compared without case, and `*` for every value without
+/// its own entry. Values without an entry stay as they are. In the short notation of the CLI,
+/// `M=M,F=F,*=` keeps M and F and empties every other value, `*=O` maps everything to O.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SexMapping(BTreeMap<String, PatientSex>);
+
+impl SexMapping {
+    pub const ANY: &'static str = "*";
+
+    pub fn new(entries: BTreeMap<String, PatientSex>) -> Self {
+        Self(entries)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The replacement of a value found in a file, `None` if it is kept.
+    pub fn get(&self, value: &str) -> Option<PatientSex> {
+        let value = value.trim();
+
+        self.0
+            .iter()
+            .find(|(key, _)| key.as_str() != Self::ANY && key.trim().eq_ignore_ascii_case(value))
+            .or_else(|| self.0.get_key_value(Self::ANY))
+            .map(|(_, sex)| *sex)
+    }
+}
+
+impl FromStr for SexMapping {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut entries = BTreeMap::new();
+
+        for entry in s.split(',').filter(|entry| !entry.trim().is_empty()) {
+            let (from, to) = entry.split_once('=').ok_or_else(|| {
+                anyhow!(
+                    "`{}` has to be in the format FROM=TO, e.g. M=M or *=O",
+                    entry
+                )
+            })?;
+            let to = PatientSex::from_str(to).map_err(|_| {
+                anyhow!(
+                    "`{}` in `{}` isn't one of the values M, F, O or empty",
+                    to,
+                    entry
+                )
+            })?;
+            entries.insert(from.trim().to_string(), to);
+        }
+
+        Ok(Self(entries))
+    }
+}
+
+impl fmt::Display for SexMapping {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let entries = self
+            .0
+            .iter()
+            .map(|(from, to)| format!("{}={}", from, to))
+            .collect::<Vec<_>>();
+
+        write!(f, "{}", entries.join(","))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SexMapping;
+    use crate::PatientSex;
+    use std::str::FromStr;
+
+    #[test]
+    fn keeps_values_without_an_entry() {
+        let mapping = SexMapping::from_str("m=M,F=F").unwrap();
+
+        assert_eq!(mapping.get("M"), Some(PatientSex::M));
+        assert_eq!(mapping.get("f "), Some(PatientSex::F));
+        assert_eq!(mapping.get("O"), None);
+    }
+
+    #[test]
+    fn falls_back_to_any() {
+        let mapping = SexMapping::from_str("M=M,F=F,*=").unwrap();
+
+        assert_eq!(mapping.get("M"), Some(PatientSex::M));
+        assert_eq!(mapping.get("MALE"), Some(PatientSex::Empty));
+        assert_eq!(mapping.get(""), Some(PatientSex::Empty));
+    }
+
+    #[test]
+    fn notation() {
+        let mapping = SexMapping::from_str("*=O, MALE=M").unwrap();
+
+        assert_eq!(mapping.to_string(), "*=O,MALE=M");
+        assert_eq!(
+            serde_json::to_string(&mapping).unwrap(),
+            r#"{"*":"O","MALE":"M"}"#
+        );
+        assert!(SexMapping::from_str("M").is_err());
+        assert!(SexMapping::from_str("M=X").is_err());
+    }
+}
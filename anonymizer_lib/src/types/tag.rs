@@ -1,6 +1,9 @@
+use anyhow::{bail, Error, Result};
 use dicom_core::header::{ElementNumber, GroupNumber};
 use dicom_core::Tag;
 use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct CustomTag {
@@ -35,6 +38,44 @@ impl PartialEq<CustomTag> for Tag {
     }
 }
 
+/// Parses the `0x____-0x____` notation used by config files and the CLI.
+impl FromStr for CustomTag {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let splitted = value.split('-').collect::<Vec<&str>>();
+
+        if splitted.len() != 2 {
+            bail!(
+                "Error while parsing tags, has to be 0x____-0x____ but received {}",
+                value
+            );
+        }
+
+        let group_number = match u16::from_str_radix(splitted[0].trim_start_matches("0x"), 16) {
+            Ok(value) => value,
+            Err(_) => {
+                bail!("Error while parsing input as hex number")
+            }
+        };
+        let element_number = match u16::from_str_radix(splitted[1].trim_start_matches("0x"), 16) {
+            Ok(value) => value,
+            Err(_) => {
+                bail!("Error while parsing input as hex number")
+            }
+        };
+
+        Ok(CustomTag::new(group_number, element_number))
+    }
+}
+
+/// Formats the tag in the `0x____-0x____` notation, the inverse of `FromStr`.
+impl fmt::Display for CustomTag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "0x{:04X}-0x{:04X}", self.group, self.element)
+    }
+}
+
 impl CustomTag {
     pub fn new(group: GroupNumber, element: ElementNumber) -> Self {
         Self { group, element }
@@ -52,6 +93,7 @@ mod tests {
     use crate::types::CustomTag;
     use dicom_core::Tag;
     use serde_json::from_str;
+    use std::str::FromStr;
 
     #[test]
     fn custom_and_dicom_tag_are_the_same() {
@@ -82,4 +124,14 @@ mod tests {
             c_t
         )
     }
+
+    #[test]
+    fn can_be_parsed_and_formatted() {
+        let c_t = CustomTag::from_str("0x00ff-0x0012").unwrap();
+
+        assert_eq!(c_t, CustomTag::new(0x00FF, 0x0012));
+        assert_eq!(c_t.to_string(), "0x00FF-0x0012");
+        assert_eq!(CustomTag::from_str(&c_t.to_string()).unwrap(), c_t);
+        assert!(CustomTag::from_str("0x0010").is_err());
+    }
 }
@@ -0,0 +1,347 @@
+use anyhow::{anyhow, bail, Result};
+use dicom_core::dictionary::{DataDictionary, DictionaryEntry};
+use dicom_core::{Tag, VR};
+use dicom_dictionary_std::StandardDataDictionary;
+use std::str::FromStr;
+
+use crate::types::{Age, DaValue, DtValue, TmValue};
+
+const ESCAPE: char = '\u{1b}';
+/// Person names have at most three component groups: alphabetic, ideographic and phonetic
+const PN_GROUPS: usize = 3;
+/// Family, given, middle name, prefix and suffix
+const PN_COMPONENTS: usize = 5;
+const PN_GROUP_LEN: usize = 64;
+
+/// Name and VR of `tag` in the standard dictionary.
+pub(crate) fn attribute(tag: Tag) -> Result<(&'static str, VR)> {
+    StandardDataDictionary
+        .by_tag(tag)
+        .map(|entry| (entry.alias, entry.vr()))
+        .ok_or_else(|| anyhow!("{} isn't an attribute of the DICOM dictionary", tag))
+}
+
+/// Checks `value`, several values separated by `\`, against the length, character and format
+/// rules of `vr`. VRs of binary data and sequences always pass.
+pub(crate) fn validate(vr: VR, value: &str) -> Result<()> {
+    values(vr, value).try_for_each(|value| validate_one(vr, value))
+}
+
+/// `value` changed to pass `validate`: cut to the maximum length, extra name components and
+/// control characters dropped, codes uppercased and numbers rewritten. Fails for values that
+/// can't be fixed without guessing, like a date that isn't one.
+pub(crate) fn coerce(vr: VR, value: &str) -> Result<String> {
+    let coerced = values(vr, value)
+        .map(|value| coerce_one(vr, value))
+        .collect::<Result<Vec<_>>>()?
+        .join("\\");
+
+    validate(vr, &coerced)?;
+    Ok(coerced)
+}
+
+/// Text VRs hold a single value that may contain backslashes.
+fn values(vr: VR, value: &str) -> impl Iterator<Item = &str> {
+    let single = matches!(vr, VR::LT | VR::ST | VR::UT | VR::UR);
+    value.split(move |c: char| c == '\\' && !single)
+}
+
+/// Characters per value, `None` for VRs without a limit or without text.
+fn max_len(vr: VR) -> Option<usize> {
+    match vr {
+        VR::AE | VR::CS | VR::DS | VR::SH => Some(16),
+        VR::AS => Some(4),
+        // Room for ranges like `19800101-19801231`
+        VR::DA => Some(18),
+        VR::DT => Some(54),
+        VR::IS => Some(12),
+        VR::TM => Some(28),
+        VR::LO | VR::UI => Some(64),
+        VR::ST => Some(1024),
+        VR::LT => Some(10240),
+        _ => None,
+    }
+}
+
+fn is_allowed_control(vr: VR, c: char) -> bool {
+    match vr {
+        VR::LT | VR::ST | VR::UT => matches!(c, ESCAPE | '\r' | '\n' | '\t' | '\u{c}'),
+        VR::SH | VR::LO | VR::PN | VR::UC => c == ESCAPE,
+        _ => false,
+    }
+}
+
+fn is_text(vr: VR) -> bool {
+    matches!(
+        vr,
+        VR::AE
+            | VR::AS
+            | VR::CS
+            | VR::DA
+            | VR::DS
+            | VR::DT
+            | VR::IS
+            | VR::LO
+            | VR::LT
+            | VR::PN
+            | VR::SH
+            | VR::ST
+            | VR::TM
+            | VR::UC
+            | VR::UI
+            | VR::UR
+            | VR::UT
+    )
+}
+
+fn is_code_char(c: char) -> bool {
+    c.is_ascii_uppercase() || c.is_ascii_digit() || c == ' ' || c == '_'
+}
+
+fn validate_one(vr: VR, value: &str) -> Result<()> {
+    if !is_text(vr) {
+        return Ok(());
+    }
+    if let Some(c) = value
+        .chars()
+        .find(|c| c.is_control() && !is_allowed_control(vr, *c))
+    {
+        bail!(
+            "{} values can't contain the control character U+{:04X}",
+            vr,
+            c as u32
+        );
+    }
+
+    if vr == VR::PN {
+        let groups = value.split('=').collect::<Vec<_>>();
+        if groups.len() > PN_GROUPS {
+            bail!(
+                "A person name has at most {} component groups separated by `=`, `{}` has {}",
+                PN_GROUPS,
+                value,
+                groups.len()
+            );
+        }
+        for group in groups {
+            let components = group.split('^').count();
+            if components > PN_COMPONENTS {
+                bail!(
+                    "A person name has at most {} components separated by `^`, `{}` has {}",
+                    PN_COMPONENTS,
+                    group,
+                    components
+                );
+            }
+            let len = group.chars().count();
+            if len > PN_GROUP_LEN {
+                bail!(
+                    "A person name has at most {} characters per component group, `{}` has {}",
+                    PN_GROUP_LEN,
+                    group,
+                    len
+                );
+            }
+        }
+        return Ok(());
+    }
+
+    let len = value.chars().count();
+    if let Some(max) = max_len(vr).filter(|max| len > *max) {
+        bail!(
+            "{} values have at most {} characters, `{}` has {}",
+            vr,
+            max,
+            value,
+            len
+        );
+    }
+
+    // Padding doesn't count for the formats
+    let trimmed = value.trim_matches([' ', '\0']);
+    let valid = match vr {
+        VR::CS => trimmed.chars().all(is_code_char),
+        VR::DS => trimmed.is_empty() || parse_decimal(trimmed).is_some(),
+        VR::IS => trimmed.is_empty() || parse_integer(trimmed).is_some(),
+        VR::UI => trimmed.split('.').all(|component| {
+            !component.is_empty()
+                && component.chars().all(|c| c.is_ascii_digit())
+                && (component == "0" || !component.starts_with('0'))
+        }),
+        VR::AS => Age::from_str(trimmed).is_ok(),
+        VR::DA => trimmed.is_empty() || DaValue::from_str(trimmed).is_ok(),
+        VR::TM => trimmed.is_empty() || TmValue::from_str(trimmed).is_ok(),
+        VR::DT => trimmed.is_empty() || DtValue::from_str(trimmed).is_ok(),
+        _ => true,
+    };
+    match valid {
+        true => Ok(()),
+        false => Err(anyhow!("`{}` isn't a valid {} value", value, vr)),
+    }
+}
+
+fn coerce_one(vr: VR, value: &str) -> Result<String> {
+    if !is_text(vr) {
+        return Ok(value.to_string());
+    }
+    let value = value
+        .chars()
+        .filter(|c| !c.is_control() || is_allowed_control(vr, *c))
+        .collect::<String>();
+    let truncate = |value: &str, max: usize| value.chars().take(max).collect::<String>();
+
+    let coerced = match vr {
+        VR::PN => value
+            .split('=')
+            .take(PN_GROUPS)
+            .map(|group| {
+                let group = group.split('^').take(PN_COMPONENTS).collect::<Vec<_>>();
+                truncate(&group.join("^"), PN_GROUP_LEN)
+                    .trim_end_matches(['^', ' '])
+                    .to_string()
+            })
+            .collect::<Vec<_>>()
+            .join("="),
+        VR::CS => truncate(
+            &value
+                .trim()
+                .to_ascii_uppercase()
+                .chars()
+                .map(|c| if is_code_char(c) { c } else { '_' })
+                .collect::<String>(),
+            16,
+        ),
+        VR::DS => {
+            let number = parse_decimal(value.trim())
+                .ok_or_else(|| anyhow!("`{}` isn't a decimal number", value))?;
+            format_decimal(number)
+        }
+        VR::IS => {
+            let number = value
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|number| number.is_finite())
+                .ok_or_else(|| anyhow!("`{}` isn't an integer", value))?;
+            (number.round().clamp(i32::MIN as f64, i32::MAX as f64) as i32).to_string()
+        }
+        _ => match max_len(vr) {
+            Some(max) => truncate(&value, max).trim_end().to_string(),
+            None => value,
+        },
+    };
+
+    Ok(coerced)
+}
+
+/// Digits with an optional sign, fraction and exponent, e.g. `-1.5e3`. No `inf` or `NaN`.
+fn parse_decimal(value: &str) -> Option<f64> {
+    let valid = value
+        .chars()
+        .all(|c| c.is_ascii_digit() || matches!(c, '+' | '-' | '.' | 'e' | 'E'))
+        && value.chars().any(|c| c.is_ascii_digit());
+
+    value.parse().ok().filter(|_| valid)
+}
+
+fn parse_integer(value: &str) -> Option<i32> {
+    let digits = value.strip_prefix(['+', '-']).unwrap_or(value);
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    value.parse().ok()
+}
+
+/// `number` in at most 16 characters, rounded if it has to be.
+fn format_decimal(number: f64) -> String {
+    let fits = |value: &String| value.len() <= 16;
+
+    Some(number.to_string())
+        .filter(fits)
+        .or_else(|| Some(format!("{:e}", number)).filter(fits))
+        .or_else(|| {
+            (0..16)
+                .rev()
+                .map(|precision| format!("{:.*}", precision, number))
+                .chain(
+                    (0..16)
+                        .rev()
+                        .map(|precision| format!("{:.*e}", precision, number)),
+                )
+                .find(fits)
+        })
+        .unwrap_or_else(|| "0".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{coerce, validate};
+    use dicom_core::VR;
+
+    #[test]
+    fn checks_lengths_and_characters() {
+        assert!(validate(VR::LO, &"a".repeat(64)).is_ok());
+        assert!(validate(VR::LO, &"a".repeat(65)).is_err());
+        // The limit is per value
+        assert!(validate(VR::SH, "short\\values").is_ok());
+        assert!(validate(VR::LT, &"\\".repeat(100)).is_ok());
+        assert!(validate(VR::LO, "line\nbreak").is_err());
+        assert!(validate(VR::LT, "line\nbreak").is_ok());
+        assert!(validate(VR::CS, "ORIGINAL").is_ok());
+        assert!(validate(VR::CS, "original").is_err());
+        assert!(validate(VR::OB, &"a".repeat(100)).is_ok());
+    }
+
+    #[test]
+    fn checks_person_names() {
+        assert!(validate(VR::PN, "Doe^John^^Dr^Jr=ドウ^ジョン=どう^じょん").is_ok());
+        assert!(validate(VR::PN, "a=b=c=d").is_err());
+        assert!(validate(VR::PN, "a^b^c^d^e^f").is_err());
+        assert!(validate(VR::PN, &format!("{}={}", "a".repeat(64), "b".repeat(64))).is_ok());
+        assert!(validate(VR::PN, &"a".repeat(65)).is_err());
+    }
+
+    #[test]
+    fn checks_formats() {
+        for value in ["1.5", "-1e3", " +.5 ", "12345678901234.5"] {
+            assert!(validate(VR::DS, value).is_ok(), "{}", value);
+        }
+        for value in ["1,5", "inf", "NaN", "12345678901234.56"] {
+            assert!(validate(VR::DS, value).is_err(), "{}", value);
+        }
+        assert!(validate(VR::IS, "-2147483648").is_ok());
+        assert!(validate(VR::IS, "2147483648").is_err());
+        assert!(validate(VR::IS, "1.0").is_err());
+        assert!(validate(VR::UI, "1.2.840.10008.1.2").is_ok());
+        assert!(validate(VR::UI, "1.02.3").is_err());
+        assert!(validate(VR::DA, "19800612").is_ok());
+        assert!(validate(VR::DA, "1980-06-12").is_err());
+        assert!(validate(VR::AS, "045Y").is_ok());
+        assert!(validate(VR::AS, "45Y").is_err());
+    }
+
+    #[test]
+    fn coerces_values_to_fit() {
+        let name = format!("{}^John^^^^Extra=a=b=c", "D".repeat(70));
+        assert_eq!(
+            coerce(VR::PN, &name).unwrap(),
+            format!("{}=a=b", "D".repeat(64))
+        );
+        assert_eq!(coerce(VR::CS, " derived-2 ").unwrap(), "DERIVED_2");
+        assert_eq!(coerce(VR::LO, "tab\there").unwrap(), "tabhere");
+        assert_eq!(
+            coerce(VR::SH, &format!("ok\\{}", "b".repeat(20))).unwrap(),
+            format!("ok\\{}", "b".repeat(16))
+        );
+        assert_eq!(
+            coerce(VR::DS, "3.141592653589793238").unwrap(),
+            "3.14159265358979"
+        );
+        assert_eq!(coerce(VR::DS, "1e300").unwrap().len(), 5);
+        assert_eq!(coerce(VR::IS, " 3000000000 ").unwrap(), "2147483647");
+        assert_eq!(coerce(VR::IS, "2.6").unwrap(), "3");
+        assert!(coerce(VR::DS, "one").is_err());
+        assert!(coerce(VR::DA, "yesterday").is_err());
+    }
+}
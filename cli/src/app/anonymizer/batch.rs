@@ -0,0 +1,379 @@
+use anonymizer_lib::archive::{anonymize_zip, BatchReport};
+use anonymizer_lib::{Anonymizer, AnonymizerMeta, FilterAction, NonDicomPolicy};
+use anyhow::Result;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::{info, info_span, warn};
+
+use crate::app::utils::is_dcm_file;
+
+/// Applies the filters of `meta` to a single file, then anonymizes or copies it to `output`.
+pub fn process_file(
+    input: &Path,
+    output: Option<&Path>,
+    meta: &AnonymizerMeta,
+    dry_run: bool,
+) -> Result<FilterAction> {
+    let obj = Anonymizer::from_file(&input.to_string_lossy())?;
+
+    let (action, warnings) = process(obj, input, output, meta, dry_run)?;
+    for warning in warnings {
+        warn!("{}", warning);
+    }
+
+    Ok(action)
+}
+
+fn process(
+    mut obj: Anonymizer,
+    input: &Path,
+    output: Option<&Path>,
+    meta: &AnonymizerMeta,
+    dry_run: bool,
+) -> Result<(FilterAction, Vec<String>)> {
+    obj.meta(meta.clone());
+
+    let action = obj.filter_action()?;
+    let output = output.filter(|_| !dry_run);
+
+    match (action, output) {
+        (FilterAction::Anonymize, output) => {
+            obj.anonymize()?;
+            if let Some(path) = output {
+                create_parent(path)?;
+                obj.save(path.to_string_lossy().as_ref())?;
+            }
+        }
+        (FilterAction::Copy, Some(path)) => {
+            create_parent(path)?;
+            fs::copy(input, path)?;
+        }
+        (FilterAction::Copy, None) | (FilterAction::Skip, _) => (),
+    }
+
+    Ok((action, obj.warnings().to_vec()))
+}
+
+/// Processes every file below `input`, keeping the folder structure in `output`. Files that aren't
+/// DICOM are left out or copied, depending on `non_dicom`. DICOM files that can't be read or
+/// anonymized are left out and reported, never copied.
+pub fn process_folder(
+    input: &Path,
+    output: Option<&Path>,
+    meta: &AnonymizerMeta,
+    non_dicom: NonDicomPolicy,
+    dry_run: bool,
+) -> Result<BatchReport> {
+    let mut report = BatchReport::default();
+
+    for file in files(input, output)? {
+        let relative = file.strip_prefix(input)?;
+        let target = output.map(|dir| dir.join(relative));
+        let _file = info_span!("file", path = %relative.display()).entered();
+
+        let obj = match Anonymizer::from_file(&file.to_string_lossy()) {
+            Ok(obj) => obj,
+            Err(error) if is_dcm_file(&file)? => {
+                info!("not anonymized, {}", error);
+                report.fail(&relative.to_string_lossy(), &error);
+                continue;
+            }
+            Err(_) => {
+                info!("not DICOM, {}", non_dicom);
+                report.not_dicom += 1;
+                if let (NonDicomPolicy::Copy, Some(path), false) = (non_dicom, &target, dry_run) {
+                    create_parent(path)?;
+                    fs::copy(&file, path)?;
+                }
+                continue;
+            }
+        };
+
+        match process(obj, &file, target.as_deref(), meta, dry_run) {
+            Ok((action, warnings)) => {
+                info!("{}", action);
+                report.record(action);
+                report.warn(&relative.to_string_lossy(), &warnings);
+            }
+            Err(error) => {
+                info!("not anonymized, {}", error);
+                report.fail(&relative.to_string_lossy(), &error);
+                // Don't leave a partly written file behind
+                if let (Some(path), false) = (&target, dry_run) {
+                    let _ = fs::remove_file(path);
+                }
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+/// Processes every entry of the zip archive `input` into the archive `output`.
+pub fn process_zip(
+    input: &Path,
+    output: Option<&Path>,
+    meta: &AnonymizerMeta,
+    non_dicom: NonDicomPolicy,
+    dry_run: bool,
+) -> Result<BatchReport> {
+    let (data, report) = anonymize_zip(&fs::read(input)?, meta, non_dicom)?;
+
+    if let (Some(path), false) = (output, dry_run) {
+        create_parent(path)?;
+        fs::write(path, data)?;
+    }
+
+    Ok(report)
+}
+
+/// All files below `dir` in a stable order, without the ones in `exclude` (the output folder).
+fn files(dir: &Path, exclude: Option<&Path>) -> Result<Vec<PathBuf>> {
+    let mut entries = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    entries.sort();
+
+    let mut found = Vec::new();
+    for path in entries {
+        if exclude.map(|exclude| path == exclude).unwrap_or(false) {
+            continue;
+        }
+        match path.is_dir() {
+            true => found.extend(files(&path, exclude)?),
+            false => found.push(path),
+        }
+    }
+
+    Ok(found)
+}
+
+fn create_parent(path: &Path) -> Result<()> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{process_folder, process_zip};
+    use anonymizer_lib::archive::BatchReport;
+    use anonymizer_lib::types::FilterRule;
+    use anonymizer_lib::{
+        Anonymizer, AnonymizerMeta, NonDicomPolicy, OutputTransferSyntax, TagAction,
+    };
+    use dicom_core::value::Value;
+    use dicom_core::{DataElement, PrimitiveValue, VR};
+    use dicom_dictionary_std::tags;
+    use dicom_object::{open_file, FileMetaTableBuilder, InMemDicomObject};
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::str::FromStr;
+
+    fn write_dcm(path: &Path, modality: &str) {
+        write(path, dataset(modality), "1.2.840.10008.1.2.1");
+    }
+
+    /// A file with JPEG compressed pixel data, which can't be converted to another transfer syntax.
+    fn write_jpeg(path: &Path) {
+        let mut obj = dataset("CT");
+        obj.put(DataElement::new(
+            tags::PIXEL_DATA,
+            VR::OB,
+            Value::PixelSequence {
+                offset_table: vec![].into(),
+                fragments: vec![vec![0xFF, 0xD8]].into(),
+            },
+        ));
+        write(path, obj, "1.2.840.10008.1.2.4.50");
+    }
+
+    fn dataset(modality: &str) -> InMemDicomObject {
+        let mut obj = InMemDicomObject::new_empty();
+        obj.put(DataElement::new(
+            tags::MODALITY,
+            VR::CS,
+            PrimitiveValue::from(modality),
+        ));
+        obj.put(DataElement::new(
+            tags::PATIENT_NAME,
+            VR::PN,
+            PrimitiveValue::from("Doe^John"),
+        ));
+        obj
+    }
+
+    fn write(path: &Path, obj: InMemDicomObject, transfer_syntax: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        obj.with_meta(
+            FileMetaTableBuilder::new()
+                .transfer_syntax(transfer_syntax)
+                .media_storage_sop_class_uid("1.2.840.10008.5.1.4.1.1.2")
+                .media_storage_sop_instance_uid("1.2.3.4"),
+        )
+        .unwrap()
+        .write_to_file(path)
+        .unwrap();
+    }
+
+    fn meta() -> AnonymizerMeta {
+        let mut builder = Anonymizer::meta_builder();
+        builder.patient_name(TagAction::Change("Anonymous".to_string()));
+        builder.filters(vec![
+            FilterRule::from_str("skip:modality=SR").unwrap(),
+            FilterRule::from_str("copy:modality=PR").unwrap(),
+        ]);
+        builder.build().unwrap()
+    }
+
+    fn patient_name(path: &Path) -> String {
+        let obj = open_file(path).unwrap();
+        let name = obj.element(tags::PATIENT_NAME).unwrap().to_str().unwrap();
+        name.trim_end().to_string()
+    }
+
+    #[test]
+    fn applies_the_filters_to_every_file() {
+        let dir = std::env::temp_dir().join(format!("dicom-tools-batch-{}", std::process::id()));
+        let input = dir.join("input");
+        let output = dir.join("output");
+        write_dcm(&input.join("ct.dcm"), "CT");
+        write_dcm(&input.join("series").join("pr.dcm"), "PR");
+        write_dcm(&input.join("series").join("sr.dcm"), "SR");
+        fs::write(input.join("notes.txt"), "not a DICOM file").unwrap();
+
+        let report =
+            process_folder(&input, Some(&output), &meta(), NonDicomPolicy::Skip, false).unwrap();
+
+        assert_eq!(
+            report,
+            BatchReport {
+                anonymized: 1,
+                copied: 1,
+                skipped: 1,
+                not_dicom: 1,
+                failed: 0,
+                warnings: vec![],
+            }
+        );
+        assert_eq!(patient_name(&output.join("ct.dcm")), "Anonymous");
+        assert_eq!(
+            patient_name(&output.join("series").join("pr.dcm")),
+            "Doe^John"
+        );
+        assert!(!output.join("series").join("sr.dcm").exists());
+        assert!(!output.join("notes.txt").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn dry_run_writes_nothing() {
+        let dir = std::env::temp_dir().join(format!("dicom-tools-dry-{}", std::process::id()));
+        write_dcm(&dir.join("input").join("ct.dcm"), "CT");
+        let output: PathBuf = dir.join("output");
+
+        let report = process_folder(
+            &dir.join("input"),
+            Some(&output),
+            &meta(),
+            NonDicomPolicy::Skip,
+            true,
+        )
+        .unwrap();
+
+        assert_eq!(report.anonymized, 1);
+        assert!(!output.exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn copies_files_that_arent_dicom_by_policy() {
+        let dir = std::env::temp_dir().join(format!("dicom-tools-copy-{}", std::process::id()));
+        let input = dir.join("input");
+        let output = dir.join("output");
+        fs::create_dir_all(&input).unwrap();
+        fs::write(input.join("notes.txt"), "not a DICOM file").unwrap();
+
+        let report =
+            process_folder(&input, Some(&output), &meta(), NonDicomPolicy::Copy, false).unwrap();
+
+        assert_eq!(report.not_dicom, 1);
+        assert_eq!(
+            fs::read_to_string(output.join("notes.txt")).unwrap(),
+            "not a DICOM file"
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn never_copies_dicom_files_that_cant_be_read() {
+        let dir = std::env::temp_dir().join(format!("dicom-tools-failed-{}", std::process::id()));
+        let input = dir.join("input");
+        let output = dir.join("output");
+        write_dcm(&input.join("ct.dcm"), "CT");
+        let data = fs::read(input.join("ct.dcm")).unwrap();
+        fs::write(input.join("ct.dcm"), &data[..data.len() - 10]).unwrap();
+
+        let report =
+            process_folder(&input, Some(&output), &meta(), NonDicomPolicy::Copy, false).unwrap();
+
+        assert_eq!((report.failed, report.not_dicom), (1, 0));
+        assert!(!output.join("ct.dcm").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn keeps_going_after_files_that_cant_be_anonymized() {
+        let dir = std::env::temp_dir().join(format!("dicom-tools-convert-{}", std::process::id()));
+        let input = dir.join("input");
+        let output = dir.join("output");
+        write_dcm(&input.join("ct.dcm"), "CT");
+        write_jpeg(&input.join("jpeg.dcm"));
+        write_dcm(&input.join("mr.dcm"), "MR");
+        let mut builder = Anonymizer::meta_builder();
+        builder.transfer_syntax(OutputTransferSyntax::ExplicitVrLittleEndian);
+
+        let report = process_folder(
+            &input,
+            Some(&output),
+            &builder.build().unwrap(),
+            NonDicomPolicy::Copy,
+            false,
+        )
+        .unwrap();
+
+        assert_eq!((report.anonymized, report.failed), (2, 1));
+        assert!(report.warnings[0].starts_with("jpeg.dcm: not anonymized, The transfer syntax"));
+        assert!(output.join("ct.dcm").exists());
+        assert!(!output.join("jpeg.dcm").exists());
+        assert!(output.join("mr.dcm").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_zip_archives_that_cant_be_read() {
+        let dir = std::env::temp_dir().join(format!("dicom-tools-zip-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("broken.zip"), "not a zip archive").unwrap();
+
+        let output = dir.join("output.zip");
+        let result = process_zip(
+            &dir.join("broken.zip"),
+            Some(&output),
+            &meta(),
+            NonDicomPolicy::Skip,
+            false,
+        );
+
+        assert!(result.is_err());
+        assert!(!output.exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
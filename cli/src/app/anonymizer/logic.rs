@@ -1,24 +1,73 @@
-use anonymizer_lib::Anonymizer;
-use anyhow::Result;
+use anonymizer_lib::archive::BatchReport;
+use anyhow::{bail, Result};
 use clap::ArgMatches;
+use tracing::{info, info_span, warn};
 
+use crate::app::anonymizer::batch::{process_file, process_folder, process_zip};
 use crate::app::anonymizer::values::AnonymizerValues;
 use crate::app::types::IMatcher;
+use crate::app::utils::is_zip_path;
 
 pub fn logic(matches: ArgMatches) -> Result<()> {
     let matches = *AnonymizerValues::match_args(matches)?;
 
-    let mut obj = Anonymizer::from_file(&matches.input.to_string_lossy())?;
-    obj.meta(matches.match_trait()?);
+    if matches.explain {
+        println!("{}", matches.config.explain());
+        return Ok(());
+    }
+
+    let meta = matches.match_trait()?;
+    if let Some(preset) = meta.preset() {
+        for limitation in preset.limitations() {
+            warn!("{} preset: {}", preset, limitation);
+        }
+    }
+    let output = matches.output.as_deref();
 
-    obj.anonymize()?;
+    if matches.input.is_dir() {
+        let report = process_folder(
+            &matches.input,
+            output,
+            &meta,
+            matches.non_dicom,
+            matches.dry_run,
+        )?;
+        print_report(&report);
+        return Ok(());
+    }
 
-    match (matches.output, matches.dry_run) {
-        (_, true) => (),
-        (None, false) => (),
-        (Some(path), false) => {
-            obj.save(path.to_string_lossy().as_ref())?;
+    if is_zip_path(&matches.input) {
+        if let Some(output) = output.filter(|output| !is_zip_path(output)) {
+            bail!("{} has to be a zip archive too", output.display());
         }
+        let report = process_zip(
+            &matches.input,
+            output,
+            &meta,
+            matches.non_dicom,
+            matches.dry_run,
+        )?;
+        print_report(&report);
+        return Ok(());
     }
+
+    // Files from modalities often have no extension, so only an existing folder means a folder
+    let output = output.map(|path| match (path.is_dir(), matches.input.file_name()) {
+        (true, Some(name)) => path.join(name),
+        _ => path.to_path_buf(),
+    });
+    let _file = info_span!("file", path = %matches.input.display()).entered();
+    let action = process_file(&matches.input, output.as_deref(), &meta, matches.dry_run)?;
+    if !meta.filters().is_empty() {
+        info!("{}", action);
+    }
+
     Ok(())
 }
+
+fn print_report(report: &BatchReport) {
+    for warning in &report.warnings {
+        warn!("{}", warning);
+    }
+    println!("{}", report);
+}
@@ -1,9 +1,13 @@
+use anonymizer_lib::Preset;
 use clap::{Arg, ValueHint};
+use std::path::Path;
 
 use crate::app::types::StaticArg;
+use crate::app::utils::is_zip_path;
 use crate::app::validator::{
-    validator_is_date, validator_is_dcm_file, validator_is_dcm_path, validator_is_file_path,
-    validator_is_sex,
+    validator_is_age_limit, validator_is_date, validator_is_dcm_file, validator_is_file_path,
+    validator_is_output_path, validator_is_pattern, validator_is_script_rule, validator_is_sex,
+    validator_is_sex_map, validator_is_zip_file,
 };
 
 #[inline(always)]
@@ -21,8 +25,14 @@ pub fn input() -> StaticArg {
         .takes_value(true)
         .value_name("FILE")
         .required(true)
-        .help("DICOM file to anonymize")
+        .help("DICOM file, folder or zip archive to anonymize")
         .validator(|v| -> Result<(), String> {
+            if Path::new(v).is_dir() {
+                return Ok(());
+            }
+            if is_zip_path(v) {
+                return validator_is_zip_file(v);
+            }
             let v_df = validator_is_dcm_file(v);
 
             match v_df {
@@ -30,7 +40,7 @@ pub fn input() -> StaticArg {
                 Err(e) => Err(e),
             }
         })
-        .value_hint(ValueHint::FilePath)
+        .value_hint(ValueHint::AnyPath)
 }
 
 #[inline(always)]
@@ -39,9 +49,18 @@ pub fn output() -> StaticArg {
         .takes_value(true)
         .short('o')
         .long("output")
-        .help("Output path for DICOM file")
-        .validator(validator_is_dcm_path)
-        .value_hint(ValueHint::FilePath)
+        .help("Output path for DICOM file, output folder if the input is a folder, or zip archive if the input is one")
+        .validator(validator_is_output_path)
+        .value_hint(ValueHint::AnyPath)
+}
+
+#[inline(always)]
+pub fn preset() -> StaticArg {
+    Arg::new("preset")
+        .takes_value(true)
+        .long("preset")
+        .possible_values(Preset::NAMES)
+        .help("Start from the settings of a de-identification standard, the other settings win over it")
 }
 
 #[inline(always)]
@@ -59,22 +78,86 @@ pub fn patient_sex() -> StaticArg {
     Arg::new("patient_sex")
         .takes_value(true)
         .long("patient-sex")
-        .help("Change the patient sex (M,F,O)")
+        .help("Change the patient sex (M,F,O or an empty value)")
         .validator(validator_is_sex)
         .value_hint(ValueHint::Other)
 }
 
+#[inline(always)]
+pub fn patient_sex_map() -> StaticArg {
+    Arg::new("patient_sex_map")
+        .takes_value(true)
+        .long("patient-sex-map")
+        .value_name("FROM=TO,...")
+        .help("Replace the patient sex values found in files when the sex is kept, * matches any other value. Example: M=M,F=F,*= keeps M and F and empties the rest")
+        .validator(validator_is_sex_map)
+        .value_hint(ValueHint::Other)
+}
+
+#[inline(always)]
+pub fn value_strictness() -> StaticArg {
+    Arg::new("value_strictness")
+        .takes_value(true)
+        .long("value-strictness")
+        .value_name("LEVEL")
+        .possible_values(["reject", "coerce"])
+        .help("Refuse new values that don't fit their VR, e.g. a patient name longer than 64 characters, or cut and fix them with a warning")
+}
+
+#[inline(always)]
+pub fn transfer_syntax() -> StaticArg {
+    Arg::new("transfer_syntax")
+        .takes_value(true)
+        .long("transfer-syntax")
+        .value_name("SYNTAX")
+        .possible_values(["keep", "explicit", "implicit", "deflated"])
+        .help("Write the files as Explicit or Implicit VR Little Endian or Deflated Explicit VR Little Endian instead of keeping the transfer syntax of the input")
+}
+
 #[inline(always)]
 pub fn patient_birth_day() -> StaticArg {
     Arg::new("patient_birth_day")
         .takes_value(true)
         .long("patient-birth-day")
         .aliases(&["patient-bd", "patient-birthday"])
-        .help("Change the patient birthday (yyy-mm-dd or yyyy-m-d)")
+        .help("Change the patient birthday (yyyy-mm-dd, or a DICOM date like 19800101, 198001 or 1980)")
         .validator(validator_is_date)
         .value_hint(ValueHint::Other)
 }
 
+#[inline(always)]
+pub fn generalize_birth_date() -> StaticArg {
+    Arg::new("generalize_birth_date")
+        .takes_value(true)
+        .long("generalize-birth-date")
+        .value_name("PRECISION")
+        .possible_values(["full", "year", "january-first"])
+        .help("Coarsen kept birth dates to the year (1980) or to January 1st (19800101)")
+}
+
+#[inline(always)]
+pub fn patient_age() -> StaticArg {
+    Arg::new("patient_age")
+        .takes_value(true)
+        .long("patient-age")
+        .value_name("ACTION")
+        .possible_values(["keep", "recompute"])
+        .help(
+            "Keep the patient age or recompute it from the original birth date and the study date",
+        )
+}
+
+#[inline(always)]
+pub fn max_patient_age() -> StaticArg {
+    Arg::new("max_patient_age")
+        .takes_value(true)
+        .long("max-patient-age")
+        .value_name("YEARS")
+        .help("Older patients get the age YEARS + 1 and a birth date that matches it, HIPAA Safe Harbor uses 89")
+        .validator(validator_is_age_limit)
+        .value_hint(ValueHint::Other)
+}
+
 #[inline(always)]
 pub fn remove_tags() -> StaticArg {
     Arg::new("remove_tags")
@@ -86,13 +169,75 @@ pub fn remove_tags() -> StaticArg {
         .value_hint(ValueHint::Other)
 }
 
+#[inline(always)]
+pub fn scrub_text() -> StaticArg {
+    Arg::new("scrub_text")
+        .takes_value(false)
+        .long("scrub-text")
+        .help("Redact the name, IDs and birth date of the patient from descriptions and comments (LO, LT, ST and UT values)")
+}
+
+#[inline(always)]
+pub fn scrub_pattern() -> StaticArg {
+    Arg::new("scrub_pattern")
+        .takes_value(true)
+        .multiple_occurrences(true)
+        .long("scrub-pattern")
+        .value_name("REGEX")
+        .help("Also redact matches of this regular expression from descriptions and comments, can be repeated. Example: '\\d{3}-\\d{4}'")
+        .validator(validator_is_pattern)
+        .value_hint(ValueHint::Other)
+}
+
 #[inline(always)]
 pub fn config() -> StaticArg {
     Arg::new("config")
         .takes_value(true)
         .short('c')
         .long("config")
-        .help("Custom config yaml-file with presets")
+        .help("Custom config file (yaml, toml or json) with presets")
         .validator(validator_is_file_path)
         .value_hint(ValueHint::FilePath)
 }
+
+#[inline(always)]
+pub fn explain() -> StaticArg {
+    Arg::new("explain")
+        .takes_value(false)
+        .long("explain")
+        .help("Print every effective setting and where it came from, then exit")
+}
+
+#[inline(always)]
+pub fn filters() -> StaticArg {
+    Arg::new("filters")
+        .takes_value(true)
+        .multiple_occurrences(true)
+        .value_name("RULE")
+        .long("filter")
+        .help("Decide per file whether to anonymize, copy or skip it, the first matching rule wins. Example: skip:modality=SR,PR or anonymize:modality=CT;series_description=^Thorax;image_type=ORIGINAL")
+        .value_hint(ValueHint::Other)
+}
+
+#[inline(always)]
+pub fn script() -> StaticArg {
+    Arg::new("script")
+        .takes_value(true)
+        .multiple_occurrences(true)
+        .value_name("ATTRIBUTE=FILE")
+        .long("script")
+        .help("Compute the new value of an attribute with a Rhai script, can be repeated. Example: PatientID=rules/patient_id.rhai")
+        .validator(validator_is_script_rule)
+        .value_hint(ValueHint::Other)
+}
+
+#[inline(always)]
+pub fn non_dicom() -> StaticArg {
+    Arg::new("non_dicom")
+        .takes_value(true)
+        .long("non-dicom")
+        .value_name("POLICY")
+        .possible_values(["skip", "copy"])
+        .default_value("skip")
+        .help("What happens to files of a folder or zip archive that aren't DICOM")
+}
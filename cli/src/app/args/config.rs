@@ -0,0 +1,66 @@
+use clap::{Arg, ValueHint};
+
+use crate::app::config::Profile;
+use crate::app::types::StaticArg;
+use crate::app::validator::validator_is_file_path;
+use strum::VariantNames;
+
+#[inline(always)]
+pub fn profile() -> StaticArg {
+    Arg::new("profile")
+        .takes_value(true)
+        .long("profile")
+        .default_value("empty")
+        .possible_values(Profile::VARIANTS)
+        .help("Settings the new config file starts with")
+}
+
+#[inline(always)]
+pub fn format() -> StaticArg {
+    Arg::new("format")
+        .takes_value(true)
+        .short('f')
+        .long("format")
+        .possible_values(["yaml", "toml", "json"])
+        .help("Output format, defaults to the extension of --output or yaml")
+}
+
+#[inline(always)]
+pub fn output() -> StaticArg {
+    Arg::new("output")
+        .takes_value(true)
+        .short('o')
+        .long("output")
+        .help("Write the config to this file instead of stdout")
+        .validator(validator_is_file_path)
+        .value_hint(ValueHint::FilePath)
+}
+
+#[inline(always)]
+pub fn force() -> StaticArg {
+    Arg::new("force")
+        .takes_value(false)
+        .long("force")
+        .help("Overwrite the output file if it exists")
+}
+
+#[inline(always)]
+pub fn files() -> StaticArg {
+    Arg::new("files")
+        .takes_value(true)
+        .multiple_values(true)
+        .value_name("FILE")
+        .required(true)
+        .help("Config files to check")
+        .value_hint(ValueHint::FilePath)
+}
+
+#[inline(always)]
+pub fn ctp_script() -> StaticArg {
+    Arg::new("script")
+        .takes_value(true)
+        .value_name("SCRIPT")
+        .required(true)
+        .help("CTP DicomAnonymizer script (XML) to translate")
+        .value_hint(ValueHint::FilePath)
+}
@@ -0,0 +1,36 @@
+use clap::Arg;
+
+use crate::app::types::StaticArg;
+
+#[inline(always)]
+pub fn verbose() -> StaticArg {
+    Arg::new("verbose")
+        .takes_value(false)
+        .multiple_occurrences(true)
+        .short('v')
+        .long("verbose")
+        .global(true)
+        .help("Log every change made to a file, -vv for even more details")
+}
+
+#[inline(always)]
+pub fn quiet() -> StaticArg {
+    Arg::new("quiet")
+        .takes_value(false)
+        .short('q')
+        .long("quiet")
+        .global(true)
+        .conflicts_with("verbose")
+        .help("Only log errors")
+}
+
+#[inline(always)]
+pub fn log_format() -> StaticArg {
+    Arg::new("log_format")
+        .takes_value(true)
+        .long("log-format")
+        .global(true)
+        .default_value("text")
+        .possible_values(["text", "json"])
+        .help("Format of the log on stderr, json writes one object per line")
+}
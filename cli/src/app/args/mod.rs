@@ -1,16 +1,88 @@
 use crate::app::types::StaticArg;
 
 mod anonymizer;
+mod config;
+mod logging;
 
-pub fn anonymizer() -> [StaticArg; 8] {
+pub fn anonymizer() -> [StaticArg; 21] {
     [
         anonymizer::dry_run(),
         anonymizer::input(),
         anonymizer::output(),
+        anonymizer::preset(),
         anonymizer::patient_name(),
         anonymizer::patient_sex(),
+        anonymizer::patient_sex_map(),
+        anonymizer::value_strictness(),
         anonymizer::patient_birth_day(),
+        anonymizer::generalize_birth_date(),
+        anonymizer::patient_age(),
+        anonymizer::max_patient_age(),
         anonymizer::remove_tags(),
+        anonymizer::scrub_text(),
+        anonymizer::scrub_pattern(),
+        anonymizer::filters(),
+        anonymizer::script(),
+        anonymizer::transfer_syntax(),
+        anonymizer::non_dicom(),
         anonymizer::config(),
+        anonymizer::explain(),
+    ]
+}
+
+/// Verbosity and format of the log, for every command.
+pub fn logging() -> [StaticArg; 3] {
+    [logging::verbose(), logging::quiet(), logging::log_format()]
+}
+
+/// Settings that make up the effective config, shared by `config show-effective` and `export`.
+pub fn settings() -> [StaticArg; 16] {
+    [
+        anonymizer::preset(),
+        anonymizer::patient_name(),
+        anonymizer::patient_sex(),
+        anonymizer::patient_sex_map(),
+        anonymizer::value_strictness(),
+        anonymizer::patient_birth_day(),
+        anonymizer::generalize_birth_date(),
+        anonymizer::patient_age(),
+        anonymizer::max_patient_age(),
+        anonymizer::remove_tags(),
+        anonymizer::scrub_text(),
+        anonymizer::scrub_pattern(),
+        anonymizer::filters(),
+        anonymizer::script(),
+        anonymizer::transfer_syntax(),
+        anonymizer::config(),
+    ]
+}
+
+pub fn config_init() -> [StaticArg; 4] {
+    [
+        config::profile(),
+        config::format(),
+        config::output(),
+        config::force(),
+    ]
+}
+
+pub fn config_validate() -> [StaticArg; 1] {
+    [config::files()]
+}
+
+pub fn config_show_effective() -> [StaticArg; 1] {
+    [config::format()]
+}
+
+pub fn config_export() -> [StaticArg; 3] {
+    [config::format(), config::output(), config::force()]
+}
+
+pub fn config_import_ctp() -> [StaticArg; 4] {
+    [
+        config::ctp_script(),
+        config::format(),
+        config::output(),
+        config::force(),
     ]
 }
@@ -1,9 +1,12 @@
 use anyhow::Result;
 use clap::Command;
 use std::ffi::OsString;
+use tracing::error;
 
 use crate::app::anonymizer;
 use crate::app::args;
+use crate::app::config;
+use crate::app::logging;
 use crate::app::types::StaticCommand;
 
 #[derive(Debug)]
@@ -12,7 +15,7 @@ pub struct App {}
 impl App {
     pub fn run() {
         Self::new_from(std::env::args_os()).unwrap_or_else(|e| {
-            println!("{}", e);
+            error!("{:#}", e);
             std::process::exit(1);
         });
     }
@@ -24,10 +27,12 @@ impl App {
     {
         let app = Self::build_cli();
         let matches = app.get_matches_from(args);
+        logging::init(&matches);
 
-        anonymizer::logic(matches)?;
-
-        Ok(())
+        match matches.subcommand() {
+            Some(("config", sub)) => config::logic(sub),
+            _ => anonymizer::logic(matches),
+        }
     }
 
     fn build_cli() -> StaticCommand {
@@ -39,5 +44,43 @@ impl App {
             .author("Domenic Melcher")
             .arg_required_else_help(true)
             .args(&args)
+            .args(args::logging())
+            .args_conflicts_with_subcommands(true)
+            .subcommand_negates_reqs(true)
+            .subcommand(Self::build_config_cli())
+    }
+
+    fn build_config_cli() -> StaticCommand {
+        Command::new("config")
+            .about("Create, check and inspect config files")
+            .subcommand_required(true)
+            .arg_required_else_help(true)
+            .subcommand(
+                Command::new("init")
+                    .about("Write a commented config file for a profile")
+                    .args(args::config_init()),
+            )
+            .subcommand(
+                Command::new("validate")
+                    .about("Check config files and report every error with its location")
+                    .args(args::config_validate()),
+            )
+            .subcommand(
+                Command::new("show-effective")
+                    .about("Print the config that results from all layers")
+                    .args(args::settings())
+                    .args(args::config_show_effective()),
+            )
+            .subcommand(
+                Command::new("export")
+                    .about("Turn command line flags into a config file")
+                    .args(args::settings())
+                    .args(args::config_export()),
+            )
+            .subcommand(
+                Command::new("import-ctp")
+                    .about("Translate a CTP DicomAnonymizer script into a config file")
+                    .args(args::config_import_ctp()),
+            )
     }
 }
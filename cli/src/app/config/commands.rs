@@ -0,0 +1,146 @@
+use anonymizer_lib::config::ConfigFileVersions;
+use anonymizer_lib::ctp;
+use anyhow::{anyhow, bail, Context, Result};
+use clap::ArgMatches;
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+use tracing::warn;
+
+use crate::app::config::document::ConfigFormat;
+use crate::app::config::effective::{cli_layers, effective_config};
+use crate::app::config::file::load;
+use crate::app::config::layers::EffectiveConfig;
+use crate::app::config::profiles::Profile;
+use crate::app::config::render::{render, render_commented};
+
+/// Entry point of `dicom-tools config <SUBCOMMAND>`.
+pub fn logic(matches: &ArgMatches) -> Result<()> {
+    match matches.subcommand() {
+        Some(("init", m)) => init(m),
+        Some(("validate", m)) => validate(m),
+        Some(("show-effective", m)) => show_effective(m),
+        Some(("export", m)) => export(m),
+        Some(("import-ctp", m)) => import_ctp(m),
+        _ => unreachable!("clap requires a subcommand"),
+    }
+}
+
+fn init(matches: &ArgMatches) -> Result<()> {
+    let profile = Profile::from_str(matches.value_of("profile").unwrap_or("empty"))?;
+    let format = output_format(matches)?;
+    let cfv = ConfigFileVersions::from(profile.meta());
+
+    let header = [
+        format!("dicom-tools config, profile `{}`", profile),
+        profile.description().to_string(),
+        "Check it with `dicom-tools config validate <FILE>`".to_string(),
+    ];
+    let header = header.iter().map(String::as_str).collect::<Vec<_>>();
+
+    write_output(matches, render_commented(&cfv, format, &header)?)
+}
+
+fn validate(matches: &ArgMatches) -> Result<()> {
+    let files = matches
+        .values_of("files")
+        .map(|files| files.map(PathBuf::from).collect::<Vec<_>>())
+        .unwrap_or_default();
+
+    let mut invalid = 0;
+    for file in &files {
+        match load(file) {
+            Ok(cfv) => println!("{}: valid (version {})", file.display(), cfv.version()),
+            Err(errors) => {
+                invalid += 1;
+                println!("{}", errors);
+            }
+        }
+    }
+
+    match invalid {
+        0 => Ok(()),
+        _ => bail!("{} of {} config files are invalid", invalid, files.len()),
+    }
+}
+
+fn show_effective(matches: &ArgMatches) -> Result<()> {
+    let cfv = ConfigFileVersions::from(effective_config(matches)?.meta()?);
+
+    let format = match matches.value_of("format") {
+        Some(format) => ConfigFormat::from_str(format)?,
+        None => ConfigFormat::Yaml,
+    };
+
+    print!("{}", render(&cfv, format)?);
+    Ok(())
+}
+
+fn export(matches: &ArgMatches) -> Result<()> {
+    let mut config = EffectiveConfig::default();
+    for (layer, source) in cli_layers(matches)? {
+        config.apply(layer, source);
+    }
+    let cfv = ConfigFileVersions::from(config.meta()?.with_every_key());
+
+    write_output(matches, render(&cfv, output_format(matches)?)?)
+}
+
+/// Writes the rules of a CTP script that map to settings as a config, the others are warned about
+/// and listed in its header.
+fn import_ctp(matches: &ArgMatches) -> Result<()> {
+    let path = PathBuf::from(matches.value_of("script").unwrap_or_default());
+    let xml =
+        fs::read_to_string(&path).with_context(|| format!("Could not read {}", path.display()))?;
+    let import =
+        ctp::import(&xml).with_context(|| format!("Could not import {}", path.display()))?;
+
+    let mut header = vec![format!(
+        "dicom-tools config, imported from {}",
+        path.display()
+    )];
+    if !import.unmapped.is_empty() {
+        header.push("Not imported, these rules have to be checked by hand:".to_string());
+    }
+    for unmapped in &import.unmapped {
+        warn!("Not imported: {}", unmapped);
+        header.push(format!("- {}", unmapped));
+    }
+    let header = header.iter().map(String::as_str).collect::<Vec<_>>();
+
+    let cfv = ConfigFileVersions::from(import.meta);
+    write_output(
+        matches,
+        render_commented(&cfv, output_format(matches)?, &header)?,
+    )
+}
+
+/// `--format` if given, otherwise the extension of `--output`, otherwise YAML.
+fn output_format(matches: &ArgMatches) -> Result<ConfigFormat> {
+    if let Some(format) = matches.value_of("format") {
+        return ConfigFormat::from_str(format);
+    }
+
+    Ok(matches
+        .value_of("output")
+        .map(ConfigFormat::from_path)
+        .unwrap_or(ConfigFormat::Yaml))
+}
+
+fn write_output(matches: &ArgMatches, content: String) -> Result<()> {
+    match matches.value_of("output").map(PathBuf::from) {
+        None => print!("{}", content),
+        Some(path) => {
+            if path.exists() && !matches.is_present("force") {
+                return Err(anyhow!(
+                    "{} already exists, use --force to overwrite it",
+                    path.display()
+                ));
+            }
+            fs::write(&path, content)?;
+            println!("Wrote {}", path.display());
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,292 @@
+use anonymizer_lib::config::{join_index, join_key};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use yaml_rust::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust::scanner::Marker;
+
+use crate::app::config::error::{ConfigError, ConfigErrors, Location};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Yaml,
+    Toml,
+    Json,
+}
+
+impl ConfigFormat {
+    /// Picks the format by file extension, everything unknown is read as YAML.
+    pub fn from_path<P>(path: P) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        match path
+            .as_ref()
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(str::to_ascii_lowercase)
+            .as_deref()
+        {
+            Some("toml") => ConfigFormat::Toml,
+            Some("json") => ConfigFormat::Json,
+            _ => ConfigFormat::Yaml,
+        }
+    }
+}
+
+impl FromStr for ConfigFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "yaml" | "yml" => Ok(ConfigFormat::Yaml),
+            "toml" => Ok(ConfigFormat::Toml),
+            "json" => Ok(ConfigFormat::Json),
+            _ => Err(anyhow::anyhow!(
+                "`{}` isn't one of the formats yaml, toml or json",
+                value
+            )),
+        }
+    }
+}
+
+/// A parsed config file together with the source location of every key.
+#[derive(Debug)]
+pub struct ConfigDocument {
+    path: PathBuf,
+    root: Value,
+    locations: HashMap<String, Location>,
+}
+
+impl ConfigDocument {
+    pub fn load<P>(path: P) -> Result<Self, ConfigErrors>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)
+            .map_err(|e| ConfigError::new(path, "", format!("Can't read file: {}", e)))?;
+
+        Self::from_str(path, ConfigFormat::from_path(path), &content)
+    }
+
+    pub fn from_str<P>(path: P, format: ConfigFormat, content: &str) -> Result<Self, ConfigErrors>
+    where
+        P: Into<PathBuf>,
+    {
+        let path = path.into();
+        let syntax_error = |message: String, location: Option<Location>| {
+            ConfigError::new(&path, "", message).at(location)
+        };
+
+        let root = match format {
+            ConfigFormat::Yaml => serde_yaml::from_str::<Value>(content).map_err(|e| {
+                let location = e.location().map(|l| Location {
+                    line: l.line(),
+                    column: l.column(),
+                });
+                syntax_error(e.to_string(), location)
+            }),
+            ConfigFormat::Toml => toml::from_str::<Value>(content).map_err(|e| {
+                let location = e.line_col().map(|(line, column)| Location {
+                    line: line + 1,
+                    column: column + 1,
+                });
+                syntax_error(e.to_string(), location)
+            }),
+            ConfigFormat::Json => serde_json::from_str::<Value>(content).map_err(|e| {
+                let location = Location {
+                    line: e.line(),
+                    column: e.column(),
+                };
+                syntax_error(e.to_string(), Some(location))
+            }),
+        }?;
+
+        // JSON is a subset of YAML, so the YAML event parser can locate keys in both formats.
+        let mut index = LocationIndex::default();
+        if format != ConfigFormat::Toml {
+            let _ = Parser::new(content.chars()).load(&mut index, false);
+        }
+
+        Ok(Self {
+            path,
+            root,
+            locations: index.locations,
+        })
+    }
+
+    pub fn root(&self) -> &Value {
+        &self.root
+    }
+
+    pub fn location(&self, key: &str) -> Option<Location> {
+        self.locations.get(key).copied()
+    }
+
+    /// Creates an error for `key`, pointing at the key's position in the file.
+    pub fn error<K, M>(&self, key: K, message: M) -> ConfigError
+    where
+        K: Into<String>,
+        M: Into<String>,
+    {
+        let key = key.into();
+        let location = self.location(&key);
+        ConfigError::new(&self.path, key, message).at(location)
+    }
+}
+
+fn location(marker: &Marker) -> Location {
+    Location {
+        line: marker.line(),
+        column: marker.col() + 1,
+    }
+}
+
+enum Frame {
+    Mapping { key: String, next: Option<String> },
+    Sequence { key: String, index: usize },
+}
+
+/// Records the position of every mapping key and sequence item of the first document.
+#[derive(Default)]
+struct LocationIndex {
+    stack: Vec<Frame>,
+    locations: HashMap<String, Location>,
+    done: bool,
+}
+
+impl LocationIndex {
+    /// Key of the value that starts at the current position, `None` if the event is a mapping key.
+    fn value_key(&mut self, marker: Marker) -> Option<String> {
+        match self.stack.last_mut() {
+            None => Some(String::new()),
+            Some(Frame::Mapping { key, next }) => next.take().map(|k| join_key(key, &k)),
+            Some(Frame::Sequence { key, index }) => {
+                let item = join_index(key, *index);
+                *index += 1;
+                self.locations.insert(item.clone(), location(&marker));
+                Some(item)
+            }
+        }
+    }
+}
+
+impl MarkedEventReceiver for LocationIndex {
+    fn on_event(&mut self, ev: Event, marker: Marker) {
+        if self.done {
+            return;
+        }
+
+        match ev {
+            Event::Scalar(value, ..) => {
+                if self.value_key(marker).is_none() {
+                    if let Some(Frame::Mapping { key, next }) = self.stack.last_mut() {
+                        self.locations
+                            .insert(join_key(key, &value), location(&marker));
+                        *next = Some(value);
+                    }
+                }
+            }
+            Event::MappingStart(_) => {
+                let key = self.value_key(marker).unwrap_or_default();
+                self.stack.push(Frame::Mapping { key, next: None });
+            }
+            Event::SequenceStart(_) => {
+                let key = self.value_key(marker).unwrap_or_default();
+                self.stack.push(Frame::Sequence { key, index: 0 });
+            }
+            Event::Alias(_) => {
+                self.value_key(marker);
+            }
+            Event::MappingEnd | Event::SequenceEnd => {
+                self.stack.pop();
+            }
+            Event::DocumentEnd => self.done = true,
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ConfigDocument, ConfigFormat};
+    use crate::app::config::error::Location;
+    use std::path::PathBuf;
+
+    #[test]
+    fn detects_format_by_extension() {
+        assert_eq!(
+            ConfigFormat::from_path(PathBuf::from("a.toml")),
+            ConfigFormat::Toml
+        );
+        assert_eq!(
+            ConfigFormat::from_path(PathBuf::from("a.JSON")),
+            ConfigFormat::Json
+        );
+        assert_eq!(
+            ConfigFormat::from_path(PathBuf::from("a.yml")),
+            ConfigFormat::Yaml
+        );
+        assert_eq!(
+            ConfigFormat::from_path(PathBuf::from("config")),
+            ConfigFormat::Yaml
+        );
+    }
+
+    #[test]
+    fn records_key_locations() {
+        let doc = ConfigDocument::from_str(
+            "test.yaml",
+            ConfigFormat::Yaml,
+            "version: \"1.0\"\nconfig:\n  patient_name: \"Name\"\n  remove_tags:\n    - \"0x0010-0x0020\"\n    - \"0x0010-0x0040\"\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            doc.location("version"),
+            Some(Location { line: 1, column: 1 })
+        );
+        assert_eq!(
+            doc.location("config.patient_name"),
+            Some(Location { line: 3, column: 3 })
+        );
+        assert_eq!(
+            doc.location("config.remove_tags[1]"),
+            Some(Location { line: 6, column: 7 })
+        );
+    }
+
+    #[test]
+    fn records_key_locations_in_json() {
+        let doc = ConfigDocument::from_str(
+            "test.json",
+            ConfigFormat::Json,
+            "{\n  \"version\": \"1.0\",\n  \"config\": {\n    \"patient_name\": \"Name\"\n  }\n}\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            doc.location("config.patient_name"),
+            Some(Location { line: 4, column: 5 })
+        );
+    }
+
+    #[test]
+    fn syntax_errors_have_a_location() {
+        let inputs = [
+            (ConfigFormat::Yaml, "config:\n  - a\n b: ["),
+            (ConfigFormat::Toml, "[config]\npatient_name = "),
+            (ConfigFormat::Json, "{\"config\": }"),
+        ];
+
+        for (format, content) in inputs {
+            let errors = ConfigDocument::from_str("test", format, content).unwrap_err();
+
+            assert_eq!(errors.iter().count(), 1);
+            assert!(errors.iter().next().unwrap().location.is_some());
+        }
+    }
+}
@@ -0,0 +1,181 @@
+use anonymizer_lib::types::{FilterRule, Pattern, ScriptRule, SexMapping};
+use anonymizer_lib::{
+    BirthDateGeneralization, OutputTransferSyntax, PatientAgeAction, PatientSex, Preset, TagAction,
+    ValueStrictness,
+};
+use anyhow::Result;
+use clap::ArgMatches;
+use dicom_core::Tag;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::app::config::file::load;
+use crate::app::config::layers::{
+    system_config_path, user_config_path, ConfigLayer, EffectiveConfig, Source,
+};
+use crate::app::utils::{parse_date, parse_tag};
+
+/// Merges all configuration layers, from lowest to highest precedence:
+/// built-in defaults, system config, user config, `--config` file, environment, CLI flags.
+pub fn effective_config(matches: &ArgMatches) -> Result<EffectiveConfig> {
+    let mut config = EffectiveConfig::default();
+
+    let system = system_config_path();
+    if system.is_file() {
+        config.apply(file_layer(&system)?, Source::System(system));
+    }
+
+    if let Some(user) = user_config_path().filter(|p| p.is_file()) {
+        config.apply(file_layer(&user)?, Source::User(user));
+    }
+
+    if let Some(path) = matches.value_of("config").map(PathBuf::from) {
+        config.apply(file_layer(&path)?, Source::ConfigFile(path));
+    }
+
+    for (layer, source) in ConfigLayer::from_env(std::env::vars())? {
+        config.apply(layer, source);
+    }
+
+    for (layer, source) in cli_layers(matches)? {
+        config.apply(layer, source);
+    }
+
+    Ok(config)
+}
+
+/// The settings of the config file at `path`. Script paths are relative to the file, so a config
+/// and its scripts can be moved together.
+fn file_layer(path: &Path) -> Result<ConfigLayer> {
+    let mut layer = ConfigLayer::from(load(path)?);
+
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    for rule in layer.scripts.iter_mut().flatten() {
+        rule.file = dir.join(&rule.file);
+    }
+
+    Ok(layer)
+}
+
+/// Only the settings given as command line flags, one layer per flag.
+pub fn cli_layers(matches: &ArgMatches) -> Result<Vec<(ConfigLayer, Source)>> {
+    let mut layers = Vec::new();
+    let source = |flag: &str| Source::Cli(flag.to_string());
+
+    if let Some(v) = matches.value_of("preset") {
+        let layer = ConfigLayer {
+            preset: Some(Some(Preset::from_str(v)?)),
+            ..Default::default()
+        };
+        layers.push((layer, source("--preset")));
+    }
+    if let Some(v) = matches.value_of("patient_name") {
+        let layer = ConfigLayer {
+            patient_name: Some(TagAction::Change(v.to_string())),
+            ..Default::default()
+        };
+        layers.push((layer, source("--patient-name")));
+    }
+    if let Some(v) = matches.value_of("patient_sex") {
+        let layer = ConfigLayer {
+            patient_sex: Some(TagAction::Change(PatientSex::from_str(v)?)),
+            ..Default::default()
+        };
+        layers.push((layer, source("--patient-sex")));
+    }
+    if let Some(v) = matches.value_of("patient_sex_map") {
+        let layer = ConfigLayer {
+            patient_sex_map: Some(SexMapping::from_str(v)?),
+            ..Default::default()
+        };
+        layers.push((layer, source("--patient-sex-map")));
+    }
+    if let Some(pbd) = matches.value_of("patient_birth_day") {
+        let layer = ConfigLayer {
+            patient_birth_day: Some(TagAction::Change(parse_date(pbd)?)),
+            ..Default::default()
+        };
+        layers.push((layer, source("--patient-birth-day")));
+    }
+    if let Some(v) = matches.value_of("generalize_birth_date") {
+        let layer = ConfigLayer {
+            generalize_birth_date: Some(BirthDateGeneralization::from_str(v)?),
+            ..Default::default()
+        };
+        layers.push((layer, source("--generalize-birth-date")));
+    }
+    if let Some(v) = matches.value_of("value_strictness") {
+        let layer = ConfigLayer {
+            value_strictness: Some(ValueStrictness::from_str(v)?),
+            ..Default::default()
+        };
+        layers.push((layer, source("--value-strictness")));
+    }
+    if let Some(v) = matches.value_of("transfer_syntax") {
+        let layer = ConfigLayer {
+            transfer_syntax: Some(OutputTransferSyntax::from_str(v)?),
+            ..Default::default()
+        };
+        layers.push((layer, source("--transfer-syntax")));
+    }
+    if let Some(v) = matches.value_of("patient_age") {
+        let layer = ConfigLayer {
+            patient_age: Some(PatientAgeAction::from_str(v)?),
+            ..Default::default()
+        };
+        layers.push((layer, source("--patient-age")));
+    }
+    if let Some(v) = matches.value_of("max_patient_age") {
+        let layer = ConfigLayer {
+            max_patient_age: Some(Some(v.parse()?)),
+            ..Default::default()
+        };
+        layers.push((layer, source("--max-patient-age")));
+    }
+    if matches.is_present("scrub_text") {
+        let layer = ConfigLayer {
+            scrub_text: Some(true),
+            ..Default::default()
+        };
+        layers.push((layer, source("--scrub-text")));
+    }
+    if let Some(values) = matches.values_of("scrub_pattern") {
+        let patterns = values.map(Pattern::from_str).collect::<Result<Vec<_>>>()?;
+        let layer = ConfigLayer {
+            scrub_patterns: Some(patterns),
+            ..Default::default()
+        };
+        layers.push((layer, source("--scrub-pattern")));
+    }
+    if let Some(rt) = matches.values_of("remove_tags") {
+        let remove_tags = rt.map(parse_tag).collect::<Result<Vec<Tag>>>()?;
+        let layer = ConfigLayer {
+            remove_tags: Some(remove_tags),
+            ..Default::default()
+        };
+        layers.push((layer, source("--remove-tags")));
+    }
+
+    if let Some(rules) = matches.values_of("filters") {
+        let filters = rules
+            .map(FilterRule::from_str)
+            .collect::<Result<Vec<_>>>()?;
+        let layer = ConfigLayer {
+            filters: Some(filters),
+            ..Default::default()
+        };
+        layers.push((layer, source("--filter")));
+    }
+    if let Some(rules) = matches.values_of("script") {
+        let scripts = rules
+            .map(ScriptRule::from_str)
+            .collect::<Result<Vec<_>>>()?;
+        let layer = ConfigLayer {
+            scripts: Some(scripts),
+            ..Default::default()
+        };
+        layers.push((layer, source("--script")));
+    }
+
+    Ok(layers)
+}
@@ -1,42 +1,129 @@
-use std::fs::File;
-use std::io::Read;
-use std::path::PathBuf;
-use strum::EnumCount;
-use yaml_rust::YamlLoader;
-
-use crate::app::config::versions::{ConfigFileV1, ConfigFileV1_1};
-use crate::app::types::IConfigFile;
-
-#[derive(Debug, EnumCount)]
-pub enum ConfigFileVersions {
-    V1_0(ConfigFileV1),
-    V1_1(ConfigFileV1_1),
+use anonymizer_lib::config::ConfigFileVersions;
+use std::path::Path;
+
+use crate::app::config::document::ConfigDocument;
+use crate::app::config::error::ConfigErrors;
+
+/// Loads a YAML, TOML or JSON config file and validates it against the versioned schema.
+pub fn load<P>(path: P) -> Result<ConfigFileVersions, ConfigErrors>
+where
+    P: AsRef<Path>,
+{
+    let document = ConfigDocument::load(path)?;
+
+    parse_document(&document)
 }
 
-impl ConfigFileVersions {
-    pub fn parse(path: PathBuf) -> Option<Self> {
-        let mut file = File::open(path).ok()?;
-        let mut content = String::new();
-        file.read_to_string(&mut content).ok()?;
+/// Validates the document and points every error at the position of its key in the file.
+pub(crate) fn parse_document(
+    document: &ConfigDocument,
+) -> Result<ConfigFileVersions, ConfigErrors> {
+    ConfigFileVersions::from_value(document.root().clone()).map_err(|errors| {
+        let mut config_errors = ConfigErrors::new();
+        for error in errors {
+            config_errors.push(document.error(error.key, error.message));
+        }
+        config_errors
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_document;
+    use crate::app::config::document::{ConfigDocument, ConfigFormat};
+    use crate::app::config::error::{ConfigErrors, Location};
+    use anonymizer_lib::config::ConfigFileVersions;
+    use anonymizer_lib::{PatientSex, TagAction};
+
+    fn parse(format: ConfigFormat, content: &str) -> Result<ConfigFileVersions, ConfigErrors> {
+        let doc = ConfigDocument::from_str("test", format, content).unwrap();
+        parse_document(&doc)
+    }
+
+    #[test]
+    fn defaults_to_version_1_0() {
+        let cfv = parse(ConfigFormat::Yaml, "config:\n  patient_name: \"Name\"\n").unwrap();
+
+        assert_eq!(cfv.version(), "1.0");
+    }
+
+    #[test]
+    fn all_formats_produce_the_same_config() {
+        let yaml = parse(
+            ConfigFormat::Yaml,
+            "version: \"1.1\"\nconfig:\n  patient_sex: \"f\"\n  remove_tags:\n    - \"0x0010-0x0020\"\n",
+        )
+        .unwrap();
+        let toml = parse(
+            ConfigFormat::Toml,
+            "version = \"1.1\"\n[config]\npatient_sex = \"f\"\nremove_tags = [\"0x0010-0x0020\"]\n",
+        )
+        .unwrap();
+        let json = parse(
+            ConfigFormat::Json,
+            r#"{"version": "1.1", "config": {"patient_sex": "f", "remove_tags": ["0x0010-0x0020"]}}"#,
+        )
+        .unwrap();
+
+        assert_eq!(yaml, toml);
+        assert_eq!(yaml, json);
+        assert_eq!(yaml.meta().patient_sex(), &TagAction::Change(PatientSex::F));
+    }
 
-        let parsed = YamlLoader::load_from_str(&content).ok()?;
+    #[test]
+    fn accepts_the_wasm_json_input() {
+        let cfv = parse(
+            ConfigFormat::Json,
+            r#"{"version": "2.0", "config": {"patient_sex": {"Change": "O"}, "patient_name": "Remove"}}"#,
+        )
+        .unwrap();
 
-        debug_assert!(
-            ConfigFileVersions::COUNT == 2,
-            "TODO: implement new 'ConfigFileVersions' member in match statement"
+        let meta = cfv.meta();
+        assert_eq!(meta.patient_sex(), &TagAction::Change(PatientSex::O));
+        assert_eq!(meta.patient_name(), &TagAction::Remove);
+    }
+
+    #[test]
+    fn rejects_unknown_versions_and_keys() {
+        let errors = parse(
+            ConfigFormat::Yaml,
+            "version: \"9.9\"\nconfgi: {}\nconfig: {}\n",
+        )
+        .unwrap_err();
+
+        let keys = errors.iter().map(|e| e.key.as_str()).collect::<Vec<_>>();
+        assert_eq!(keys, vec!["confgi", "version"]);
+    }
+
+    #[test]
+    fn collects_all_errors() {
+        let errors = parse(
+            ConfigFormat::Yaml,
+            "config:\n  patient_nme: \"Name\"\n  patient_sex: \"x\"\n  remove_tags:\n    - \"0x0010-0x0020\"\n    - \"0x00ZZ-0x0040\"\n",
+        )
+        .unwrap_err();
+
+        let errors = errors.iter().collect::<Vec<_>>();
+        assert_eq!(errors.len(), 3);
+
+        assert_eq!(errors[0].key, "config.patient_nme");
+        assert!(errors[0].message.starts_with("unknown field `patient_nme`"));
+        assert_eq!(errors[0].location, Some(Location { line: 2, column: 3 }));
+
+        assert_eq!(errors[1].key, "config.patient_sex");
+        assert_eq!(errors[1].location, Some(Location { line: 3, column: 3 }));
+
+        assert_eq!(errors[2].key, "config.remove_tags[1]");
+        assert_eq!(errors[2].location, Some(Location { line: 6, column: 7 }));
+    }
+
+    #[test]
+    fn reports_errors_with_file_path() {
+        let errors = parse(ConfigFormat::Yaml, "config:\n  patient_sex: \"x\"\n").unwrap_err();
+
+        assert_eq!(
+            errors.to_string(),
+            "Invalid config file:\n  test:2:3: `config.patient_sex`: `x` isn't one of the values M, F, O or empty"
         );
-        match parsed[0]["version"].as_str().unwrap_or("1.0") {
-            "1.0" => {
-                let cf = *ConfigFileV1::parse(parsed).ok()?;
-
-                Some(ConfigFileVersions::V1_0(cf))
-            }
-            "1.1" => {
-                let cf = *ConfigFileV1_1::parse(parsed).ok()?;
-
-                Some(ConfigFileVersions::V1_1(cf))
-            }
-            _ => None,
-        }
     }
 }
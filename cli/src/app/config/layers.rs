@@ -0,0 +1,1047 @@
+use anonymizer_lib::config::ConfigFileVersions;
+use anonymizer_lib::types::{DaValue, FilterRule, Pattern, ScriptRule, SexMapping};
+use anonymizer_lib::{
+    Anonymizer, AnonymizerMeta, BirthDateGeneralization, MetaKey, OutputTransferSyntax,
+    PatientAgeAction, PatientSex, Preset, TagAction, ValueStrictness,
+};
+use anyhow::{anyhow, bail, Context, Result};
+use dicom_core::Tag;
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::app::utils::{parse_date, parse_tag};
+
+pub const ENV_PREFIX: &str = "DICOM_TOOLS_";
+
+/// Where an effective setting came from, ordered from lowest to highest precedence.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    Default,
+    System(PathBuf),
+    User(PathBuf),
+    ConfigFile(PathBuf),
+    Env(String),
+    Cli(String),
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Default => write!(f, "built-in default"),
+            Source::System(path) => write!(f, "system config ({})", path.display()),
+            Source::User(path) => write!(f, "user config ({})", path.display()),
+            Source::ConfigFile(path) => write!(f, "--config ({})", path.display()),
+            Source::Env(name) => write!(f, "environment variable {}", name),
+            Source::Cli(flag) => write!(f, "command line flag {}", flag),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Setting<T> {
+    pub value: T,
+    pub source: Source,
+}
+
+impl<T> Setting<T> {
+    fn new(value: T, source: Source) -> Self {
+        Self { value, source }
+    }
+}
+
+/// The settings one configuration layer provides. `None` means the layer doesn't touch the setting,
+/// `Some(None)` for a setting that can be empty clears what lower layers set.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ConfigLayer {
+    pub preset: Option<Option<Preset>>,
+    pub patient_name: Option<TagAction<String>>,
+    pub patient_birth_day: Option<TagAction<DaValue>>,
+    pub generalize_birth_date: Option<BirthDateGeneralization>,
+    pub patient_age: Option<PatientAgeAction>,
+    pub max_patient_age: Option<Option<u16>>,
+    pub patient_sex: Option<TagAction<PatientSex>>,
+    pub patient_sex_map: Option<SexMapping>,
+    pub value_strictness: Option<ValueStrictness>,
+    pub remove_tags: Option<Vec<Tag>>,
+    pub scrub_text: Option<bool>,
+    pub scrub_patterns: Option<Vec<Pattern>>,
+    pub filters: Option<Vec<FilterRule>>,
+    pub scripts: Option<Vec<ScriptRule>>,
+    pub transfer_syntax: Option<OutputTransferSyntax>,
+    pub pseudonym_salt: Option<Option<String>>,
+}
+
+impl ConfigLayer {
+    /// Reads the `DICOM_TOOLS_*` variables out of `vars`, one layer per variable.
+    pub fn from_env<I>(vars: I) -> Result<Vec<(Self, Source)>>
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        let mut layers = Vec::new();
+
+        for (name, value) in vars {
+            let key = match name.strip_prefix(ENV_PREFIX) {
+                Some(key) => key,
+                None => continue,
+            };
+            let error = |e: &dyn fmt::Display| anyhow!("Invalid value in {}: {}", name, e);
+
+            let mut layer = Self::default();
+            match key {
+                "PRESET" => {
+                    let preset = Preset::from_str(&value).map_err(|e| error(&e))?;
+                    layer.preset = Some(Some(preset));
+                }
+                "PATIENT_NAME" => layer.patient_name = Some(TagAction::Change(value)),
+                "PATIENT_SEX" => {
+                    let sex = PatientSex::from_str(&value).map_err(|e| error(&e))?;
+                    layer.patient_sex = Some(TagAction::Change(sex));
+                }
+                "PATIENT_SEX_MAP" => {
+                    let map = SexMapping::from_str(&value).map_err(|e| error(&e))?;
+                    layer.patient_sex_map = Some(map);
+                }
+                "PATIENT_BIRTH_DAY" => {
+                    let date = parse_date(&value).map_err(|e| error(&e))?;
+                    layer.patient_birth_day = Some(TagAction::Change(date));
+                }
+                "GENERALIZE_BIRTH_DATE" => {
+                    let value = BirthDateGeneralization::from_str(&value).map_err(|e| error(&e))?;
+                    layer.generalize_birth_date = Some(value);
+                }
+                "PATIENT_AGE" => {
+                    let value = PatientAgeAction::from_str(&value).map_err(|e| error(&e))?;
+                    layer.patient_age = Some(value);
+                }
+                "VALUE_STRICTNESS" => {
+                    let value = ValueStrictness::from_str(&value).map_err(|e| error(&e))?;
+                    layer.value_strictness = Some(value);
+                }
+                "TRANSFER_SYNTAX" => {
+                    let value = OutputTransferSyntax::from_str(&value).map_err(|e| error(&e))?;
+                    layer.transfer_syntax = Some(value);
+                }
+                "MAX_PATIENT_AGE" => {
+                    let value = u16::from_str(&value).map_err(|e| error(&e))?;
+                    layer.max_patient_age = Some(Some(value));
+                }
+                "PSEUDONYM_SALT" => layer.pseudonym_salt = Some(Some(value)),
+                "SCRUB_TEXT" => {
+                    let value = bool::from_str(&value).map_err(|e| error(&e))?;
+                    layer.scrub_text = Some(value);
+                }
+                "REMOVE_TAGS" => {
+                    let tags = value
+                        .split(',')
+                        .filter(|item| !item.trim().is_empty())
+                        .map(|item| parse_tag(item.trim()))
+                        .collect::<Result<Vec<_>>>()
+                        .map_err(|e| error(&e))?;
+                    layer.remove_tags = Some(tags);
+                }
+                _ => continue,
+            }
+            layers.push((layer, Source::Env(name)));
+        }
+
+        Ok(layers)
+    }
+}
+
+/// Only the keys a config file sets end up in its layer, also when they set the default value, so
+/// a higher layer can turn a setting of a lower one off again.
+impl From<ConfigFileVersions> for ConfigLayer {
+    fn from(cfv: ConfigFileVersions) -> Self {
+        let meta = cfv.meta();
+        let set = |key: MetaKey| meta.is_set(key);
+        let remove_tags = meta
+            .remove_tags()
+            .iter()
+            .map(|tag| Tag::from(tag.clone()))
+            .collect::<Vec<_>>();
+
+        ConfigLayer {
+            preset: set(MetaKey::Preset).then(|| meta.preset()),
+            patient_name: set(MetaKey::PatientName).then(|| meta.patient_name().clone()),
+            patient_birth_day: set(MetaKey::PatientBirthDate)
+                .then(|| meta.patient_birth_date().clone()),
+            generalize_birth_date: set(MetaKey::GeneralizeBirthDate)
+                .then(|| meta.generalize_birth_date()),
+            patient_age: set(MetaKey::PatientAge).then(|| meta.patient_age()),
+            max_patient_age: set(MetaKey::MaxPatientAge).then(|| meta.max_patient_age()),
+            patient_sex: set(MetaKey::PatientSex).then(|| meta.patient_sex().clone()),
+            patient_sex_map: set(MetaKey::PatientSexMap).then(|| meta.patient_sex_map().clone()),
+            value_strictness: set(MetaKey::ValueStrictness).then(|| meta.value_strictness()),
+            remove_tags: set(MetaKey::RemoveTags).then_some(remove_tags),
+            scrub_text: set(MetaKey::ScrubText).then(|| meta.scrub_text()),
+            scrub_patterns: set(MetaKey::ScrubPatterns).then(|| meta.scrub_patterns().to_vec()),
+            filters: set(MetaKey::Filters).then(|| meta.filters().to_vec()),
+            scripts: set(MetaKey::Scripts).then(|| meta.scripts().to_vec()),
+            transfer_syntax: set(MetaKey::TransferSyntax).then(|| meta.transfer_syntax()),
+            pseudonym_salt: set(MetaKey::PseudonymSalt)
+                .then(|| meta.pseudonym_salt().map(str::to_string)),
+        }
+    }
+}
+
+/// The merged result of all layers, remembering which layer provided each value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EffectiveConfig {
+    pub preset: Setting<Option<Preset>>,
+    pub patient_name: Setting<TagAction<String>>,
+    pub patient_birth_day: Setting<TagAction<DaValue>>,
+    pub generalize_birth_date: Setting<BirthDateGeneralization>,
+    pub patient_age: Setting<PatientAgeAction>,
+    pub max_patient_age: Setting<Option<u16>>,
+    pub patient_sex: Setting<TagAction<PatientSex>>,
+    pub patient_sex_map: Setting<SexMapping>,
+    pub value_strictness: Setting<ValueStrictness>,
+    /// Tags accumulate over all layers instead of being replaced.
+    pub remove_tags: Vec<Setting<Tag>>,
+    pub scrub_text: Setting<bool>,
+    /// Patterns accumulate over all layers, like tags.
+    pub scrub_patterns: Vec<Setting<Pattern>>,
+    /// Filter rules of higher layers are checked before the ones of lower layers.
+    pub filters: Vec<Setting<FilterRule>>,
+    /// Script rules of higher layers run before the ones of lower layers, like filters.
+    pub scripts: Vec<Setting<ScriptRule>>,
+    pub transfer_syntax: Setting<OutputTransferSyntax>,
+    pub pseudonym_salt: Setting<Option<String>>,
+}
+
+impl Default for EffectiveConfig {
+    fn default() -> Self {
+        Self {
+            preset: Setting::new(None, Source::Default),
+            patient_name: Setting::new(TagAction::Keep, Source::Default),
+            patient_birth_day: Setting::new(TagAction::Keep, Source::Default),
+            generalize_birth_date: Setting::new(BirthDateGeneralization::Full, Source::Default),
+            patient_age: Setting::new(PatientAgeAction::Keep, Source::Default),
+            max_patient_age: Setting::new(None, Source::Default),
+            patient_sex: Setting::new(TagAction::Keep, Source::Default),
+            patient_sex_map: Setting::new(SexMapping::default(), Source::Default),
+            value_strictness: Setting::new(ValueStrictness::Reject, Source::Default),
+            remove_tags: Vec::new(),
+            scrub_text: Setting::new(false, Source::Default),
+            scrub_patterns: Vec::new(),
+            filters: Vec::new(),
+            scripts: Vec::new(),
+            transfer_syntax: Setting::new(OutputTransferSyntax::Keep, Source::Default),
+            pseudonym_salt: Setting::new(None, Source::Default),
+        }
+    }
+}
+
+impl EffectiveConfig {
+    /// Applies `layer` on top of the current values. Layers have to be applied from lowest to
+    /// highest precedence.
+    pub fn apply(&mut self, layer: ConfigLayer, source: Source) -> &mut Self {
+        if let Some(value) = layer.preset {
+            self.preset = Setting::new(value, source.clone());
+        }
+        if let Some(value) = layer.patient_name {
+            self.patient_name = Setting::new(value, source.clone());
+        }
+        if let Some(value) = layer.patient_birth_day {
+            self.patient_birth_day = Setting::new(value, source.clone());
+        }
+        if let Some(value) = layer.generalize_birth_date {
+            self.generalize_birth_date = Setting::new(value, source.clone());
+        }
+        if let Some(value) = layer.patient_age {
+            self.patient_age = Setting::new(value, source.clone());
+        }
+        if let Some(value) = layer.max_patient_age {
+            self.max_patient_age = Setting::new(value, source.clone());
+        }
+        if let Some(value) = layer.patient_sex {
+            self.patient_sex = Setting::new(value, source.clone());
+        }
+        if let Some(value) = layer.patient_sex_map {
+            self.patient_sex_map = Setting::new(value, source.clone());
+        }
+        if let Some(value) = layer.value_strictness {
+            self.value_strictness = Setting::new(value, source.clone());
+        }
+        for tag in layer.remove_tags.unwrap_or_default() {
+            if !self.remove_tags.iter().any(|item| item.value == tag) {
+                self.remove_tags.push(Setting::new(tag, source.clone()));
+            }
+        }
+        if let Some(value) = layer.scrub_text {
+            self.scrub_text = Setting::new(value, source.clone());
+        }
+        for pattern in layer.scrub_patterns.unwrap_or_default() {
+            if !self.scrub_patterns.iter().any(|item| item.value == pattern) {
+                self.scrub_patterns
+                    .push(Setting::new(pattern, source.clone()));
+            }
+        }
+        if let Some(rules) = layer.filters {
+            let rules = rules
+                .into_iter()
+                .map(|rule| Setting::new(rule, source.clone()));
+            self.filters.splice(0..0, rules);
+        }
+        if let Some(rules) = layer.scripts {
+            let rules = rules
+                .into_iter()
+                .map(|rule| Setting::new(rule, source.clone()));
+            self.scripts.splice(0..0, rules);
+        }
+        if let Some(value) = layer.transfer_syntax {
+            self.transfer_syntax = Setting::new(value, source.clone());
+        }
+        if let Some(value) = layer.pseudonym_salt {
+            self.pseudonym_salt = Setting::new(value, source.clone());
+        }
+
+        self
+    }
+
+    /// The meta of the merged layers. Only the settings a layer gave are set, the others are left
+    /// to the preset, see `AnonymizerMeta::with_preset`.
+    pub fn meta(&self) -> Result<AnonymizerMeta> {
+        fn given<T>(setting: &Setting<T>) -> Option<&T> {
+            (setting.source != Source::Default).then_some(&setting.value)
+        }
+        fn values<T: Clone>(settings: &[Setting<T>]) -> Option<Vec<T>> {
+            let values = settings
+                .iter()
+                .map(|item| item.value.clone())
+                .collect::<Vec<_>>();
+            (!values.is_empty()).then_some(values)
+        }
+
+        let mut builder = Anonymizer::meta_builder();
+
+        if let Some(value) = given(&self.preset) {
+            builder.preset(*value);
+        }
+        if let Some(value) = given(&self.patient_name) {
+            builder.patient_name(value.clone());
+        }
+        if let Some(value) = given(&self.patient_sex) {
+            builder.patient_sex(value.clone());
+        }
+        if let Some(value) = given(&self.patient_sex_map) {
+            builder.patient_sex_map(value.clone());
+        }
+        if let Some(value) = given(&self.value_strictness) {
+            builder.value_strictness(*value);
+        }
+        if let Some(value) = given(&self.patient_birth_day) {
+            builder.patient_birth_date(value.clone());
+        }
+        if let Some(value) = given(&self.generalize_birth_date) {
+            builder.generalize_birth_date(*value);
+        }
+        if let Some(value) = given(&self.patient_age) {
+            builder.patient_age(*value);
+        }
+        if let Some(value) = given(&self.max_patient_age) {
+            builder.max_patient_age(*value);
+        }
+        if let Some(tags) = values(&self.remove_tags) {
+            builder.remove_tags(tags.into());
+        }
+        if let Some(value) = given(&self.scrub_text) {
+            builder.scrub_text(*value);
+        }
+        if let Some(patterns) = values(&self.scrub_patterns) {
+            builder.scrub_patterns(patterns);
+        }
+        if let Some(rules) = values(&self.filters) {
+            builder.filters(rules);
+        }
+        if let Some(rules) = values(&self.scripts) {
+            builder.scripts(rules);
+        }
+        if let Some(value) = given(&self.transfer_syntax) {
+            builder.transfer_syntax(*value);
+        }
+        if let Some(value) = given(&self.pseudonym_salt) {
+            builder.pseudonym_salt(value.clone());
+        }
+
+        let meta = builder.build()?;
+
+        // Refused before the first file, not halfway through a batch
+        let errors = meta.value_errors();
+        if !errors.is_empty() {
+            let lines = errors
+                .iter()
+                .map(|error| {
+                    let source = match error.key.split('.').next() {
+                        Some("patient_name") => &self.patient_name.source,
+                        Some("patient_birth_date") => &self.patient_birth_day.source,
+                        _ => &self.patient_sex.source,
+                    };
+                    format!("  {} [{}]", error, source)
+                })
+                .collect::<Vec<_>>();
+            bail!(
+                "New values that don't fit their VR, `--value-strictness coerce` cuts them to fit:\n{}",
+                lines.join("\n")
+            );
+        }
+        for rule in &self.scripts {
+            rule.value
+                .check()
+                .with_context(|| format!("Invalid script rule {} [{}]", rule.value, rule.source))?;
+        }
+
+        Ok(meta)
+    }
+
+    /// Human readable list of every effective setting and its source.
+    pub fn explain(&self) -> String {
+        fn action<T: fmt::Display>(value: &TagAction<T>) -> String {
+            match value {
+                TagAction::Change(value) => format!("change to \"{}\"", value),
+                TagAction::Keep => "keep".to_string(),
+                TagAction::Remove => "remove".to_string(),
+            }
+        }
+
+        let mut lines = vec![
+            format!(
+                "preset:            {:<30} [{}]",
+                match self.preset.value {
+                    Some(preset) => preset.to_string(),
+                    None => "none".to_string(),
+                },
+                self.preset.source
+            ),
+            format!(
+                "patient_name:      {:<30} [{}]",
+                action(&self.patient_name.value),
+                self.patient_name.source
+            ),
+            format!(
+                "patient_birth_day: {:<30} [{}]",
+                action(&self.patient_birth_day.value),
+                self.patient_birth_day.source
+            ),
+            format!(
+                "generalize_birth_date: {:<26} [{}]",
+                self.generalize_birth_date.value.to_string(),
+                self.generalize_birth_date.source
+            ),
+            format!(
+                "patient_age:       {:<30} [{}]",
+                self.patient_age.value.to_string(),
+                self.patient_age.source
+            ),
+            format!(
+                "max_patient_age:   {:<30} [{}]",
+                match self.max_patient_age.value {
+                    Some(years) => years.to_string(),
+                    None => "none".to_string(),
+                },
+                self.max_patient_age.source
+            ),
+            format!(
+                "patient_sex:       {:<30} [{}]",
+                action(&self.patient_sex.value),
+                self.patient_sex.source
+            ),
+            format!(
+                "patient_sex_map:   {:<30} [{}]",
+                match self.patient_sex_map.value.is_empty() {
+                    true => "none".to_string(),
+                    false => self.patient_sex_map.value.to_string(),
+                },
+                self.patient_sex_map.source
+            ),
+            format!(
+                "value_strictness:  {:<30} [{}]",
+                self.value_strictness.value.to_string(),
+                self.value_strictness.source
+            ),
+        ];
+
+        match self.remove_tags.is_empty() {
+            true => lines.push(format!(
+                "remove_tags:       {:<30} [{}]",
+                "none",
+                Source::Default
+            )),
+            false => {
+                lines.push("remove_tags:".to_string());
+                for tag in &self.remove_tags {
+                    lines.push(format!(
+                        "  - {:<34} [{}]",
+                        tag.value.to_string(),
+                        tag.source
+                    ));
+                }
+            }
+        }
+
+        lines.push(format!(
+            "scrub_text:        {:<30} [{}]",
+            self.scrub_text.value, self.scrub_text.source
+        ));
+        match self.scrub_patterns.is_empty() {
+            true => lines.push(format!(
+                "scrub_patterns:    {:<30} [{}]",
+                "none",
+                Source::Default
+            )),
+            false => {
+                lines.push("scrub_patterns:".to_string());
+                for pattern in &self.scrub_patterns {
+                    lines.push(format!(
+                        "  - {:<34} [{}]",
+                        pattern.value.to_string(),
+                        pattern.source
+                    ));
+                }
+            }
+        }
+
+        match self.filters.is_empty() {
+            true => lines.push(format!(
+                "filters:           {:<30} [{}]",
+                "anonymize every file",
+                Source::Default
+            )),
+            false => {
+                lines.push("filters:".to_string());
+                for rule in &self.filters {
+                    lines.push(format!(
+                        "  - {:<34} [{}]",
+                        rule.value.to_string(),
+                        rule.source
+                    ));
+                }
+            }
+        }
+
+        match self.scripts.is_empty() {
+            true => lines.push(format!(
+                "scripts:           {:<30} [{}]",
+                "none",
+                Source::Default
+            )),
+            false => {
+                lines.push("scripts:".to_string());
+                for rule in &self.scripts {
+                    lines.push(format!(
+                        "  - {:<34} [{}]",
+                        rule.value.to_string(),
+                        rule.source
+                    ));
+                }
+            }
+        }
+
+        lines.push(format!(
+            "transfer_syntax:   {:<30} [{}]",
+            self.transfer_syntax.value.to_string(),
+            self.transfer_syntax.source
+        ));
+        // The salt is a secret, only whether there is one is shown
+        lines.push(format!(
+            "pseudonym_salt:    {:<30} [{}]",
+            match self.pseudonym_salt.value {
+                Some(_) => "set",
+                None => "none",
+            },
+            self.pseudonym_salt.source
+        ));
+
+        lines.join("\n")
+    }
+}
+
+/// System wide config, e.g. provisioned by an administrator.
+pub fn system_config_path() -> PathBuf {
+    PathBuf::from("/etc/dicom-tools/config.yaml")
+}
+
+/// Per-user config in `$XDG_CONFIG_HOME/dicom-tools` or `~/.config/dicom-tools`.
+pub fn user_config_path() -> Option<PathBuf> {
+    std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .map(|dir| dir.join("dicom-tools").join("config.yaml"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ConfigLayer, EffectiveConfig, Source};
+    use anonymizer_lib::config::ConfigFileVersions;
+    use anonymizer_lib::types::{FilterRule, Pattern, ScriptRule, SexMapping};
+    use anonymizer_lib::{PatientSex, TagAction, ValueStrictness};
+    use dicom_core::Tag;
+    use serde_json::json;
+    use std::path::PathBuf;
+    use std::str::FromStr;
+
+    fn env(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn later_layers_win() {
+        let mut config = EffectiveConfig::default();
+        config
+            .apply(
+                ConfigLayer {
+                    patient_name: Some(TagAction::Change("File".to_string())),
+                    patient_sex: Some(TagAction::Change(PatientSex::F)),
+                    ..Default::default()
+                },
+                Source::ConfigFile(PathBuf::from("test.yaml")),
+            )
+            .apply(
+                ConfigLayer {
+                    patient_name: Some(TagAction::Change("Cli".to_string())),
+                    ..Default::default()
+                },
+                Source::Cli("--patient-name".to_string()),
+            );
+
+        assert_eq!(
+            config.patient_name.value,
+            TagAction::Change("Cli".to_string())
+        );
+        assert_eq!(
+            config.patient_name.source,
+            Source::Cli("--patient-name".to_string())
+        );
+        assert_eq!(config.patient_sex.value, TagAction::Change(PatientSex::F));
+        assert_eq!(
+            config.patient_sex.source,
+            Source::ConfigFile(PathBuf::from("test.yaml"))
+        );
+        assert_eq!(config.patient_birth_day.source, Source::Default);
+    }
+
+    #[test]
+    fn default_values_of_higher_layers_win() {
+        let layer = |value: serde_json::Value| {
+            ConfigLayer::from(ConfigFileVersions::from_value(value).unwrap())
+        };
+        let system = layer(json!({
+            "version": "2.0",
+            "config": {
+                "patient_sex": {"Change": "O"},
+                "value_strictness": "Coerce",
+                "scrub_text": true
+            }
+        }));
+        let file = layer(json!({
+            "version": "2.0",
+            "config": {"patient_sex": "Keep", "value_strictness": "Reject", "scrub_text": false}
+        }));
+        // Keys a file doesn't have stay untouched
+        assert_eq!(file.patient_age, None);
+
+        let mut config = EffectiveConfig::default();
+        config
+            .apply(system, Source::System(PathBuf::from("/etc/config.yaml")))
+            .apply(file, Source::ConfigFile(PathBuf::from("test.yaml")));
+
+        let meta = config.meta().unwrap();
+        assert_eq!(meta.patient_sex(), &TagAction::Keep);
+        assert_eq!(meta.value_strictness(), ValueStrictness::Reject);
+        assert!(!meta.scrub_text());
+        assert!(config
+            .explain()
+            .contains("value_strictness:  Reject                         [--config (test.yaml)]"));
+
+        let v1 = layer(json!({"version": "1.1", "config": {"scrub_text": false}}));
+        assert_eq!(v1.scrub_text, Some(false));
+        assert_eq!(v1.patient_sex, None);
+    }
+
+    #[test]
+    fn default_values_of_higher_layers_win_over_the_preset() {
+        let layer = |value: serde_json::Value| {
+            ConfigLayer::from(ConfigFileVersions::from_value(value).unwrap())
+        };
+        let system = layer(json!({"version": "2.0", "config": {"preset": "HipaaSafeHarbor"}}));
+        let file = layer(json!({
+            "version": "2.0",
+            "config": {"patient_name": "Keep", "scrub_text": false}
+        }));
+
+        let mut config = EffectiveConfig::default();
+        config
+            .apply(system, Source::System(PathBuf::from("/etc/config.yaml")))
+            .apply(file, Source::ConfigFile(PathBuf::from("test.yaml")));
+
+        let meta = config.meta().unwrap().with_preset();
+        assert_eq!(meta.patient_name(), &TagAction::Keep);
+        assert!(!meta.scrub_text());
+        assert_eq!(meta.max_patient_age(), Some(89));
+    }
+
+    /// The effective config of a system config with `lower` and a user config with `higher`.
+    fn system_and_user(lower: serde_json::Value, higher: serde_json::Value) -> EffectiveConfig {
+        let layer = |config| {
+            let value = json!({"version": "2.0", "config": config});
+            ConfigLayer::from(ConfigFileVersions::from_value(value).unwrap())
+        };
+        let mut config = EffectiveConfig::default();
+        config
+            .apply(
+                layer(lower),
+                Source::System(PathBuf::from("/etc/config.yaml")),
+            )
+            .apply(layer(higher), Source::User(PathBuf::from("user.yaml")));
+        config
+    }
+
+    #[test]
+    fn higher_layers_clear_the_preset() {
+        let config = system_and_user(
+            json!({"preset": "HipaaSafeHarbor"}),
+            json!({"preset": null}),
+        );
+
+        let meta = config.meta().unwrap();
+        assert_eq!(meta.preset(), None);
+        assert_eq!(meta.with_preset().patient_name(), &TagAction::Keep);
+        assert_eq!(
+            config.preset.source,
+            Source::User(PathBuf::from("user.yaml"))
+        );
+    }
+
+    #[test]
+    fn higher_layers_clear_the_max_patient_age() {
+        let config = system_and_user(
+            json!({"preset": "HipaaSafeHarbor", "max_patient_age": 89}),
+            json!({"max_patient_age": null}),
+        );
+
+        assert_eq!(config.meta().unwrap().with_preset().max_patient_age(), None);
+        assert!(config.explain().contains(
+            "max_patient_age:   none                           [user config (user.yaml)]"
+        ));
+    }
+
+    #[test]
+    fn higher_layers_clear_the_pseudonym_salt() {
+        let config = system_and_user(
+            json!({"pseudonym_salt": "secret"}),
+            json!({"pseudonym_salt": null}),
+        );
+
+        assert_eq!(config.meta().unwrap().pseudonym_salt(), None);
+        assert_eq!(
+            config.pseudonym_salt.source,
+            Source::User(PathBuf::from("user.yaml"))
+        );
+    }
+
+    #[test]
+    fn remove_tags_accumulate() {
+        let mut config = EffectiveConfig::default();
+        config
+            .apply(
+                ConfigLayer {
+                    remove_tags: Some(vec![Tag(0x0010, 0x0020), Tag(0x0010, 0x0040)]),
+                    ..Default::default()
+                },
+                Source::User(PathBuf::from("user.yaml")),
+            )
+            .apply(
+                ConfigLayer {
+                    remove_tags: Some(vec![Tag(0x0010, 0x0040), Tag(0x0010, 0x1010)]),
+                    ..Default::default()
+                },
+                Source::Cli("--remove-tags".to_string()),
+            );
+
+        let tags = config
+            .remove_tags
+            .iter()
+            .map(|item| (item.value, item.source.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            tags,
+            vec![
+                (
+                    Tag(0x0010, 0x0020),
+                    Source::User(PathBuf::from("user.yaml"))
+                ),
+                (
+                    Tag(0x0010, 0x0040),
+                    Source::User(PathBuf::from("user.yaml"))
+                ),
+                (
+                    Tag(0x0010, 0x1010),
+                    Source::Cli("--remove-tags".to_string())
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn filters_of_higher_layers_come_first() {
+        let rule = |value: &str| FilterRule::from_str(value).unwrap();
+
+        let mut config = EffectiveConfig::default();
+        config
+            .apply(
+                ConfigLayer {
+                    filters: Some(vec![rule("skip:modality=SR"), rule("copy")]),
+                    ..Default::default()
+                },
+                Source::ConfigFile(PathBuf::from("test.yaml")),
+            )
+            .apply(
+                ConfigLayer {
+                    filters: Some(vec![rule("anonymize:modality=SR;image_type=ORIGINAL")]),
+                    ..Default::default()
+                },
+                Source::Cli("--filter".to_string()),
+            );
+
+        let rules = config.meta().unwrap().filters().to_vec();
+        assert_eq!(
+            rules,
+            vec![
+                rule("anonymize:modality=SR;image_type=ORIGINAL"),
+                rule("skip:modality=SR"),
+                rule("copy"),
+            ]
+        );
+        assert_eq!(
+            config.filters[0].source,
+            Source::Cli("--filter".to_string())
+        );
+    }
+
+    #[test]
+    fn scripts_are_read_when_building_the_meta() {
+        let file = std::env::temp_dir().join("dicom-tools-layers.rhai");
+        std::fs::write(&file, r#""ANON-" + hash(value)"#).unwrap();
+        let rule = |file: &str| ScriptRule::from_str(&format!("PatientID={}", file)).unwrap();
+
+        let mut config = EffectiveConfig::default();
+        config.apply(
+            ConfigLayer {
+                scripts: Some(vec![rule(&file.to_string_lossy())]),
+                ..Default::default()
+            },
+            Source::Cli("--script".to_string()),
+        );
+        assert_eq!(config.meta().unwrap().scripts().len(), 1);
+
+        config.apply(
+            ConfigLayer {
+                scripts: Some(vec![rule("/does/not/exist.rhai")]),
+                ..Default::default()
+            },
+            Source::ConfigFile(PathBuf::from("test.yaml")),
+        );
+        let err = config.meta().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid script rule PatientID=/does/not/exist.rhai [--config (test.yaml)]"
+        );
+    }
+
+    #[test]
+    fn reads_prefixed_env_vars() {
+        let layers = ConfigLayer::from_env(env(&[
+            ("HOME", "/root"),
+            ("DICOM_TOOLS_PATIENT_SEX", "o"),
+            ("DICOM_TOOLS_REMOVE_TAGS", "0x0010-0x0020, 0x0010-0x0040"),
+        ]))
+        .unwrap();
+
+        assert_eq!(layers.len(), 2);
+        assert_eq!(
+            layers[0].0.patient_sex,
+            Some(TagAction::Change(PatientSex::O))
+        );
+        assert_eq!(
+            layers[0].1,
+            Source::Env("DICOM_TOOLS_PATIENT_SEX".to_string())
+        );
+        assert_eq!(
+            layers[1].0.remove_tags,
+            Some(vec![Tag(0x0010, 0x0020), Tag(0x0010, 0x0040)])
+        );
+    }
+
+    #[test]
+    fn invalid_env_vars_are_errors() {
+        let err = ConfigLayer::from_env(env(&[("DICOM_TOOLS_PATIENT_SEX", "x")])).unwrap_err();
+
+        assert!(err
+            .to_string()
+            .starts_with("Invalid value in DICOM_TOOLS_PATIENT_SEX"));
+        assert!(ConfigLayer::from_env(env(&[("DICOM_TOOLS_MAX_PATIENT_AGE", "old")])).is_err());
+    }
+
+    #[test]
+    fn patient_sex_map_is_replaced_by_higher_layers() {
+        let layers = ConfigLayer::from_env(env(&[("DICOM_TOOLS_PATIENT_SEX_MAP", "*=O")])).unwrap();
+        let mut config = EffectiveConfig::default();
+        for (layer, source) in layers {
+            config.apply(layer, source);
+        }
+        config.apply(
+            ConfigLayer {
+                patient_sex_map: Some(SexMapping::from_str("M=M,F=F,*=").unwrap()),
+                ..Default::default()
+            },
+            Source::Cli("--patient-sex-map".to_string()),
+        );
+
+        let meta = config.meta().unwrap();
+        assert_eq!(meta.patient_sex_map().to_string(), "*=,F=F,M=M");
+        assert!(config.explain().contains(
+            "patient_sex_map:   *=,F=F,M=M                     [command line flag --patient-sex-map]"
+        ));
+    }
+
+    #[test]
+    fn settings_come_from_env() {
+        for (name, value, line) in [
+            (
+                "DICOM_TOOLS_PRESET",
+                "hipaa-safe-harbor",
+                "preset:            HipaaSafeHarbor                [environment variable DICOM_TOOLS_PRESET]",
+            ),
+            (
+                "DICOM_TOOLS_GENERALIZE_BIRTH_DATE",
+                "year",
+                "generalize_birth_date: Year                       [environment variable DICOM_TOOLS_GENERALIZE_BIRTH_DATE]",
+            ),
+            (
+                "DICOM_TOOLS_PATIENT_AGE",
+                "recompute",
+                "patient_age:       Recompute                      [environment variable DICOM_TOOLS_PATIENT_AGE]",
+            ),
+            (
+                "DICOM_TOOLS_MAX_PATIENT_AGE",
+                "89",
+                "max_patient_age:   89                             [environment variable DICOM_TOOLS_MAX_PATIENT_AGE]",
+            ),
+            (
+                "DICOM_TOOLS_SCRUB_TEXT",
+                "true",
+                "scrub_text:        true                           [environment variable DICOM_TOOLS_SCRUB_TEXT]",
+            ),
+            (
+                "DICOM_TOOLS_TRANSFER_SYNTAX",
+                "deflated",
+                "transfer_syntax:   DeflatedExplicitVrLittleEndian [environment variable DICOM_TOOLS_TRANSFER_SYNTAX]",
+            ),
+            (
+                "DICOM_TOOLS_PSEUDONYM_SALT",
+                "secret",
+                "pseudonym_salt:    set                            [environment variable DICOM_TOOLS_PSEUDONYM_SALT]",
+            ),
+        ] {
+            let mut config = EffectiveConfig::default();
+            for (layer, source) in ConfigLayer::from_env(env(&[(name, value)])).unwrap() {
+                config.apply(layer, source);
+            }
+
+            assert!(
+                config.explain().contains(line),
+                "{}:\n{}",
+                name,
+                config.explain()
+            );
+            config.meta().unwrap();
+        }
+    }
+
+    #[test]
+    fn pseudonym_salt_isnt_shown() {
+        let layers =
+            ConfigLayer::from_env(env(&[("DICOM_TOOLS_PSEUDONYM_SALT", "secret")])).unwrap();
+        let mut config = EffectiveConfig::default();
+        for (layer, source) in layers {
+            config.apply(layer, source);
+        }
+
+        assert_eq!(config.meta().unwrap().pseudonym_salt(), Some("secret"));
+        assert!(!config.explain().contains("secret"));
+    }
+
+    #[test]
+    fn scrub_patterns_accumulate() {
+        let pattern = |value: &str| Pattern::from_str(value).unwrap();
+        let layers = ConfigLayer::from_env(env(&[("DICOM_TOOLS_SCRUB_TEXT", "true")])).unwrap();
+        let mut config = EffectiveConfig::default();
+        for (layer, source) in layers {
+            config.apply(layer, source);
+        }
+        config
+            .apply(
+                ConfigLayer {
+                    scrub_patterns: Some(vec![pattern(r"\d{3}-\d{4}")]),
+                    ..Default::default()
+                },
+                Source::User(PathBuf::from("user.yaml")),
+            )
+            .apply(
+                ConfigLayer {
+                    scrub_patterns: Some(vec![pattern(r"\d{3}-\d{4}"), pattern("MRN\\d+")]),
+                    ..Default::default()
+                },
+                Source::Cli("--scrub-pattern".to_string()),
+            );
+
+        let meta = config.meta().unwrap();
+        assert!(meta.scrub_text());
+        assert_eq!(
+            meta.scrub_patterns(),
+            &[pattern(r"\d{3}-\d{4}"), pattern("MRN\\d+")]
+        );
+        let explain = config.explain();
+        assert!(explain.contains(
+            "scrub_text:        true                           [environment variable DICOM_TOOLS_SCRUB_TEXT]"
+        ));
+        assert!(
+            explain.contains(r"  - \d{3}-\d{4}                        [user config (user.yaml)]")
+        );
+        assert!(ConfigLayer::from_env(env(&[("DICOM_TOOLS_SCRUB_TEXT", "yes")])).is_err());
+    }
+
+    #[test]
+    fn new_values_have_to_fit_their_vr() {
+        let name = "N".repeat(65);
+        let mut config = EffectiveConfig::default();
+        config.apply(
+            ConfigLayer {
+                patient_name: Some(TagAction::Change(name.clone())),
+                ..Default::default()
+            },
+            Source::Cli("--patient-name".to_string()),
+        );
+
+        let error = config.meta().unwrap_err().to_string();
+        assert!(error.contains("`patient_name.Change`: A person name has at most 64 characters"));
+        assert!(
+            error.ends_with("[command line flag --patient-name]"),
+            "{}",
+            error
+        );
+
+        let layers =
+            ConfigLayer::from_env(env(&[("DICOM_TOOLS_VALUE_STRICTNESS", "coerce")])).unwrap();
+        for (layer, source) in layers {
+            config.apply(layer, source);
+        }
+        assert_eq!(
+            config.meta().unwrap().value_strictness(),
+            ValueStrictness::Coerce
+        );
+    }
+}
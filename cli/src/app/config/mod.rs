@@ -1,5 +1,13 @@
+mod commands;
+pub(crate) mod document;
+mod effective;
+mod error;
 mod file;
-mod macros;
-mod versions;
+mod layers;
+mod profiles;
+mod render;
 
-pub use file::ConfigFileVersions;
+pub use commands::logic;
+pub use effective::effective_config;
+pub use layers::EffectiveConfig;
+pub use profiles::Profile;
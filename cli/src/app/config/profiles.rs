@@ -0,0 +1,56 @@
+use anonymizer_lib::{Anonymizer, AnonymizerMeta, Preset, RemoveTagsInput, TagAction};
+use dicom_core::Tag;
+use dicom_dictionary_std::tags;
+use strum::{Display, EnumString, EnumVariantNames};
+use tags_list_lib::List as TagsList;
+
+/// Starting points for `config init`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display, EnumString, EnumVariantNames)]
+#[strum(serialize_all = "kebab-case")]
+pub enum Profile {
+    Empty,
+    Basic,
+    Patient,
+    HipaaSafeHarbor,
+}
+
+impl Profile {
+    pub fn description(&self) -> &'static str {
+        match self {
+            Profile::Empty => "Changes nothing, every setting is listed with its default",
+            Profile::Basic => {
+                "Replaces the patient name and removes the most common direct identifiers"
+            }
+            Profile::Patient => "Removes every patient related attribute",
+            Profile::HipaaSafeHarbor => {
+                "Uses the HIPAA Safe Harbor preset, every setting is listed with the value of the preset"
+            }
+        }
+    }
+
+    /// The settings of the profile, every one of them is set so `config init` lists it.
+    pub fn meta(&self) -> AnonymizerMeta {
+        let mut builder = Anonymizer::meta_builder();
+
+        match self {
+            Profile::Empty => (),
+            Profile::Basic => {
+                builder.patient_name(TagAction::Change("Anonymous".to_string()));
+                builder.remove_tags(RemoveTagsInput::from(vec![
+                    tags::PATIENT_ID,
+                    tags::PATIENT_BIRTH_DATE,
+                    tags::PATIENT_ADDRESS,
+                    tags::PATIENT_TELEPHONE_NUMBERS,
+                ] as Vec<Tag>));
+            }
+            Profile::Patient => {
+                builder.remove_tags(RemoveTagsInput::from(TagsList::PATIENT));
+            }
+            Profile::HipaaSafeHarbor => {
+                builder.preset(Preset::HipaaSafeHarbor);
+            }
+        }
+
+        builder.finish().with_every_key()
+    }
+}
@@ -0,0 +1,211 @@
+use anonymizer_lib::config::ConfigFileVersions;
+use anyhow::Result;
+
+use crate::app::config::document::ConfigFormat;
+
+/// Explanation written above each setting by `config init`.
+const COMMENTS: [(&str, &str); 16] = [
+    (
+        "preset",
+        "Settings of a de-identification standard, e.g. \"HipaaSafeHarbor\", the settings below win over it",
+    ),
+    (
+        "patient_name",
+        "Patient name: \"Keep\", \"Remove\" or {\"Change\": \"New Name\"}",
+    ),
+    (
+        "patient_birth_date",
+        "Patient birth date: \"Keep\", \"Remove\" or {\"Change\": \"20000101\"}, partial dates like \"2000\" or \"200001\" are allowed",
+    ),
+    (
+        "generalize_birth_date",
+        "Kept birth dates: \"Full\", \"Year\" (1980) or \"JanuaryFirst\" (19800101)",
+    ),
+    (
+        "patient_age",
+        "Patient age: \"Keep\" or \"Recompute\" from the original birth date and the study date",
+    ),
+    (
+        "max_patient_age",
+        "Older patients get this age + 1 and a matching birth date, HIPAA Safe Harbor uses 89",
+    ),
+    (
+        "remove_tags",
+        "Tags that are deleted from the file, e.g. {\"group\": 16, \"element\": 32} for (0010,0020)",
+    ),
+    (
+        "patient_sex",
+        "Patient sex: \"Keep\", \"Remove\" or {\"Change\": \"M\" | \"F\" | \"O\" | \"Empty\"}",
+    ),
+    (
+        "patient_sex_map",
+        "Replaces kept patient sex values, \"*\" for any other value, e.g. {\"M\": \"M\", \"F\": \"F\", \"*\": \"Empty\"}",
+    ),
+    (
+        "value_strictness",
+        "New values that don't fit their VR, e.g. names over 64 characters: \"Reject\" the config or \"Coerce\" them with a warning",
+    ),
+    (
+        "scrub_text",
+        "Redact the name, IDs and birth date of the patient from descriptions and comments: true or false",
+    ),
+    (
+        "scrub_patterns",
+        "Regular expressions redacted from descriptions and comments, e.g. \"\\\\d{3}-\\\\d{4}\"",
+    ),
+    (
+        "filters",
+        "Batch runs: the first matching rule decides, e.g. {\"action\": \"Skip\", \"modality\": [\"SR\", \"PR\"]}",
+    ),
+    (
+        "scripts",
+        "Rhai scripts that compute new values, paths are relative to this file, e.g. {\"attribute\": \"PatientID\", \"file\": \"patient_id.rhai\"}",
+    ),
+    (
+        "transfer_syntax",
+        "Transfer syntax of the written files: \"Keep\", \"ExplicitVrLittleEndian\", \"ImplicitVrLittleEndian\" or \"DeflatedExplicitVrLittleEndian\"",
+    ),
+    (
+        "pseudonym_salt",
+        "Secret key of the UIDs and patient IDs that replace the originals in zip archives and of hash() in scripts, keep it out of shared configs",
+    ),
+];
+
+pub fn render(cfv: &ConfigFileVersions, format: ConfigFormat) -> Result<String> {
+    let content = match format {
+        ConfigFormat::Yaml => serde_yaml::to_string(cfv)?,
+        // The TOML serializer can't write enum variants with data, their JSON form is a plain map.
+        ConfigFormat::Toml => {
+            let value = without_nulls(serde_json::to_value(cfv)?);
+            toml::to_string_pretty(&toml::Value::try_from(value)?)?
+        }
+        ConfigFormat::Json => serde_json::to_string_pretty(cfv)? + "\n",
+    };
+
+    Ok(content)
+}
+
+/// TOML has no null, a setting without value like `preset` is left out instead.
+fn without_nulls(value: serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(map) => map
+            .into_iter()
+            .filter(|(_, value)| !value.is_null())
+            .map(|(key, value)| (key, without_nulls(value)))
+            .collect(),
+        value => value,
+    }
+}
+
+/// Like `render`, but with a header and an explanation above every setting. JSON has no comments,
+/// so it is returned without them.
+pub fn render_commented(
+    cfv: &ConfigFileVersions,
+    format: ConfigFormat,
+    header: &[&str],
+) -> Result<String> {
+    let content = render(cfv, format)?;
+
+    if format == ConfigFormat::Json {
+        return Ok(content);
+    }
+
+    let mut lines = header
+        .iter()
+        .map(|line| format!("# {}", line))
+        .collect::<Vec<_>>();
+    lines.push(String::new());
+
+    let mut commented = Vec::new();
+    for line in content.lines() {
+        let trimmed = line.trim_start();
+        let key = trimmed
+            .trim_start_matches('[')
+            .trim_start_matches("config.")
+            .split([':', ' ', ']', '.'])
+            .next()
+            .unwrap_or_default();
+
+        if let Some((key, comment)) = COMMENTS.iter().find(|(name, _)| *name == key) {
+            if !commented.contains(key) {
+                let indent = &line[..line.len() - trimmed.len()];
+                lines.push(format!("{}# {}", indent, comment));
+                commented.push(*key);
+            }
+        }
+        lines.push(line.to_string());
+    }
+
+    Ok(lines.join("\n") + "\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render, render_commented, COMMENTS};
+    use crate::app::config::document::{ConfigDocument, ConfigFormat};
+    use crate::app::config::file::parse_document;
+    use crate::app::config::profiles::Profile;
+    use anonymizer_lib::config::ConfigFileVersions;
+    use anonymizer_lib::types::CustomTag;
+    use anonymizer_lib::{Anonymizer, TagAction};
+
+    fn factory() -> ConfigFileVersions {
+        let mut builder = Anonymizer::meta_builder();
+        builder.patient_name(TagAction::Change("Anonymous".to_string()));
+        builder.remove_tag(CustomTag::new(0x0010, 0x0020));
+
+        builder.build().unwrap().into()
+    }
+
+    #[test]
+    fn every_format_can_be_read_back() {
+        let cfv = factory();
+
+        for format in [ConfigFormat::Yaml, ConfigFormat::Toml, ConfigFormat::Json] {
+            let content = render_commented(&cfv, format, &["Header"]).unwrap();
+            let doc = ConfigDocument::from_str("test", format, &content).unwrap();
+
+            assert_eq!(parse_document(&doc).unwrap(), cfv, "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn comments_every_setting_once() {
+        let content = render_commented(&factory(), ConfigFormat::Yaml, &["Header"]).unwrap();
+
+        assert!(content.starts_with("# Header\n"));
+        assert_eq!(content.matches("# Patient name").count(), 1);
+        assert_eq!(content.matches("# Tags that are deleted").count(), 1);
+        assert!(!render(&factory(), ConfigFormat::Yaml)
+            .unwrap()
+            .contains('#'));
+    }
+
+    #[test]
+    fn init_lists_every_setting() {
+        for profile in [Profile::Empty, Profile::HipaaSafeHarbor] {
+            let cfv = ConfigFileVersions::from(profile.meta());
+            let content = render_commented(&cfv, ConfigFormat::Yaml, &["Header"]).unwrap();
+
+            for (key, comment) in COMMENTS {
+                assert!(
+                    content.contains(&format!("\n  {}:", key)),
+                    "{} in {}",
+                    key,
+                    profile
+                );
+                assert!(content.contains(comment), "{} in {}", key, profile);
+            }
+            for format in [ConfigFormat::Toml, ConfigFormat::Json] {
+                let content = render(&cfv, format).unwrap();
+                let doc = ConfigDocument::from_str("test", format, &content).unwrap();
+                assert_eq!(
+                    parse_document(&doc).unwrap().meta().with_every_key(),
+                    cfv.meta(),
+                    "{:?}",
+                    format
+                );
+            }
+        }
+    }
+}
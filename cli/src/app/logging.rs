@@ -0,0 +1,61 @@
+use clap::ArgMatches;
+use std::io::{self, IsTerminal};
+use tracing::level_filters::LevelFilter;
+
+/// Sends the events of the anonymizer and the CLI to stderr, stdout only gets the results of a
+/// command like the report or an exported config.
+pub fn init(matches: &ArgMatches) {
+    let builder = tracing_subscriber::fmt()
+        .with_writer(io::stderr)
+        .with_ansi(io::stderr().is_terminal())
+        .with_max_level(level(matches))
+        .with_target(false);
+
+    // Fails if a subscriber is already set, e.g. when the tests run the CLI more than once
+    let _ = match matches.value_of("log_format") {
+        Some("json") => builder.json().try_init(),
+        _ => builder.without_time().try_init(),
+    };
+}
+
+/// Info by default, so every file shows up with its action and warnings.
+fn level(matches: &ArgMatches) -> LevelFilter {
+    match (
+        matches.is_present("quiet"),
+        matches.occurrences_of("verbose"),
+    ) {
+        (true, _) => LevelFilter::ERROR,
+        (false, 0) => LevelFilter::INFO,
+        (false, 1) => LevelFilter::DEBUG,
+        (false, _) => LevelFilter::TRACE,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::level;
+    use crate::app::args;
+    use clap::Command;
+    use tracing::level_filters::LevelFilter;
+
+    fn level_of(args: &[&str]) -> LevelFilter {
+        let command = Command::new("dicom-tools").args(args::logging());
+        level(&command.try_get_matches_from(args).unwrap())
+    }
+
+    #[test]
+    fn verbosity_flags_set_the_level() {
+        assert_eq!(level_of(&["dicom-tools"]), LevelFilter::INFO);
+        assert_eq!(level_of(&["dicom-tools", "-v"]), LevelFilter::DEBUG);
+        assert_eq!(level_of(&["dicom-tools", "-vv"]), LevelFilter::TRACE);
+        assert_eq!(level_of(&["dicom-tools", "-q"]), LevelFilter::ERROR);
+    }
+
+    #[test]
+    fn quiet_conflicts_with_verbose() {
+        let command = Command::new("dicom-tools").args(args::logging());
+        assert!(command
+            .try_get_matches_from(["dicom-tools", "-q", "-v"])
+            .is_err());
+    }
+}
@@ -1,6 +1,7 @@
 mod anonymizer;
 mod args;
 mod config;
+mod logging;
 mod types;
 mod utils;
 mod validator;
@@ -1,151 +1,97 @@
-use anyhow::{bail, Result};
-use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, ParseResult, Utc};
+use anonymizer_lib::types::{CustomTag, DaValue};
+use anonymizer_lib::{FileLayout, LAYOUT_HEAD_LENGTH};
+use anyhow::Result;
 use dicom_core::Tag;
 use std::fs::File;
-use std::io::{BufReader, Read};
+use std::io::Read;
 use std::path::Path;
+use std::str::FromStr;
 
-const DICM_PREAMBLE: [u8; 128] = [0u8; 128];
-const DICM_MAGIC_CODE: [u8; 4] = [b'D', b'I', b'C', b'M'];
-
+/// Whether the file at `path` looks like DICOM: a part 10 file with any preamble or without it,
+/// or a bare dataset without file meta group.
 pub fn is_dcm_file<P>(path: P) -> Result<bool>
 where
     P: AsRef<Path>,
 {
-    // Inspiration:
-    // - https://docs.rs/dicom-object/0.5.0/src/dicom_object/mem.rs.html#239
-    // - https://docs.rs/dicom-object/0.5.0/src/dicom_object/meta.rs.html#172
-    let path = path.as_ref();
-    let mut file = BufReader::new(File::open(path)?);
-
-    let mut buf = [0u8; 128];
-    file.read_exact(&mut buf)?;
-    assert_eq!(buf, DICM_PREAMBLE, "First 128 bytes should be 0x0");
-
-    let mut buf = [0u8; 4];
-    file.read_exact(&mut buf)?;
-    assert_eq!(buf, DICM_MAGIC_CODE, "Magic number should be DICM");
+    let mut head = Vec::with_capacity(LAYOUT_HEAD_LENGTH);
+    File::open(path)?
+        .take(LAYOUT_HEAD_LENGTH as u64)
+        .read_to_end(&mut head)?;
 
-    Ok(true)
+    Ok(FileLayout::detect(&head).is_some())
 }
 
-pub fn is_dcm_path<P>(path: P) -> bool
+pub fn is_zip_path<P>(path: P) -> bool
 where
     P: AsRef<Path>,
 {
     matches!(
         path.as_ref().extension().map(|ext| ext.to_str()),
-        Some(Some("dcm"))
+        Some(Some("zip"))
     )
 }
 
-pub fn parse_date(value: &str) -> ParseResult<NaiveDate> {
-    NaiveDate::parse_from_str(value, "%Y-%m-%d")
-}
-
-pub fn parse_datetime(value: &str) -> ParseResult<NaiveDateTime> {
-    parse_date(value).map(|value| value.and_time(NaiveTime::from_hms(0, 0, 0)))
-}
-
-pub fn parse_datetime_utc(value: &str) -> ParseResult<DateTime<FixedOffset>> {
-    let ndt = parse_datetime(value)?;
-    let dtf: DateTime<FixedOffset> = DateTime::<Utc>::from_utc(ndt, Utc).into();
-    Ok(dtf)
+/// A birth date, `yyyy-mm-dd` or a DICOM date that may be partial (`1980`, `198002`).
+pub fn parse_date(value: &str) -> Result<DaValue> {
+    DaValue::parse_date(value)
 }
 
 pub fn parse_tag(value: &str) -> Result<Tag> {
-    let splitted = value.split('-').collect::<Vec<&str>>();
-
-    if splitted.len() != 2 {
-        bail!(
-            "Error while parsing tags, has to be 0x____-0x____ but received {}",
-            value
-        );
-    }
-
-    let group_number = match u16::from_str_radix(splitted[0].trim_start_matches("0x"), 16) {
-        Ok(value) => value,
-        Err(_) => {
-            bail!("Error while parsing input as hex number")
-        }
-    };
-    let element_number = match u16::from_str_radix(splitted[1].trim_start_matches("0x"), 16) {
-        Ok(value) => value,
-        Err(_) => {
-            bail!("Error while parsing input as hex number")
-        }
-    };
-
-    Ok(Tag(group_number, element_number))
+    CustomTag::from_str(value).map(Tag::from)
 }
 
 #[cfg(test)]
 mod tests {
-    mod is_dcm_path {
-        use crate::app::utils::is_dcm_path;
-        use std::path::PathBuf;
+    mod is_dcm_file {
+        use crate::app::utils::is_dcm_file;
+        use std::fs;
 
         #[test]
-        fn only_allow_paths_with_dcm_extension() {
-            assert!(is_dcm_path(PathBuf::from("/test.dcm")));
-            assert!(is_dcm_path(PathBuf::from("./test.dcm")));
-            assert!(!is_dcm_path(PathBuf::from("/test.jpg")));
-            assert!(!is_dcm_path(PathBuf::from("/test")));
+        fn detect_dicom_by_content_not_by_name() {
+            let dir = std::env::temp_dir().join("dicom-tools-is-dcm-file");
+            fs::create_dir_all(&dir).unwrap();
+
+            let mut part10 = vec![0xFF; 128];
+            part10.extend_from_slice(b"DICM\x02\x00\x00\x00UL\x04\x00");
+            let files = [
+                ("IM000001", part10),
+                ("image.IMA", b"\x08\x00\x05\x00CS\x0A\x00".to_vec()),
+                ("notes.dcm", b"Not an image".to_vec()),
+                ("empty", Vec::new()),
+            ];
+            for (name, data) in &files {
+                fs::write(dir.join(name), data).unwrap();
+            }
+
+            assert!(is_dcm_file(dir.join("IM000001")).unwrap());
+            assert!(is_dcm_file(dir.join("image.IMA")).unwrap());
+            assert!(!is_dcm_file(dir.join("notes.dcm")).unwrap());
+            assert!(!is_dcm_file(dir.join("empty")).unwrap());
+            assert!(is_dcm_file(dir.join("missing")).is_err());
+
+            fs::remove_dir_all(dir).unwrap();
         }
     }
 
-    mod parse_date {
-        use crate::app::utils::parse_date;
-        use chrono::NaiveDate;
+    mod is_zip_path {
+        use crate::app::utils::is_zip_path;
+        use std::path::PathBuf;
 
         #[test]
-        fn only_parse_dates_ymd_format() {
-            match parse_date("2022-03-15") {
-                Ok(value) => {
-                    assert_eq!(value, NaiveDate::from_ymd(2022, 3, 15));
-                }
-                Err(_) => {
-                    assert!(false)
-                }
-            };
-
-            match parse_date("2022-15-03") {
-                Ok(_) => {
-                    assert!(false);
-                }
-                Err(_) => {
-                    assert!(true)
-                }
-            };
+        fn only_allow_paths_with_zip_extension() {
+            assert!(is_zip_path(PathBuf::from("./study.zip")));
+            assert!(!is_zip_path(PathBuf::from("./study.dcm")));
         }
     }
 
-    mod parse_datetime {
-        use crate::app::utils::parse_datetime;
-        use chrono::{NaiveDate, NaiveTime};
+    mod parse_date {
+        use crate::app::utils::parse_date;
 
         #[test]
-        fn only_parse_dates_ymd_format() {
-            match parse_datetime("2022-03-15") {
-                Ok(value) => {
-                    assert_eq!(
-                        value,
-                        NaiveDate::from_ymd(2022, 3, 15).and_time(NaiveTime::from_hms(0, 0, 0))
-                    );
-                }
-                Err(_) => {
-                    assert!(false)
-                }
-            };
-
-            match parse_datetime("2022-15-03") {
-                Ok(_) => {
-                    assert!(false);
-                }
-                Err(_) => {
-                    assert!(true)
-                }
-            };
+        fn parse_dashed_and_dicom_dates() {
+            assert_eq!(parse_date("2022-03-15").unwrap().to_string(), "20220315");
+            assert_eq!(parse_date("202203").unwrap().to_string(), "202203");
+            assert!(parse_date("2022-15-03").is_err());
         }
     }
 
@@ -155,36 +101,24 @@ mod tests {
 
         #[test]
         fn parse_tags_in_the_right_format() {
-            match parse_tag("0x0001-0x0001") {
-                Ok(tag) => assert_eq!(tag, Tag(1, 1)),
-                Err(_) => assert!(false),
-            };
-            match parse_tag("0x00FF-0x0012") {
-                Ok(tag) => assert_eq!(tag, Tag(255, 18)),
-                Err(_) => assert!(false),
-            };
+            assert_eq!(parse_tag("0x0001-0x0001").unwrap(), Tag(1, 1));
+            assert_eq!(parse_tag("0x00FF-0x0012").unwrap(), Tag(255, 18));
         }
 
         #[test]
         fn parse_errors() {
-            match parse_tag("0x0001-0x0001-0x001") {
-                Ok(_) => assert!(false),
-                Err(err) => {
-                    assert_eq!(err.to_string(), "Error while parsing tags, has to be 0x____-0x____ but received 0x0001-0x0001-0x001");
-                }
-            };
-            match parse_tag("0x001G-0x001") {
-                Ok(_) => assert!(false),
-                Err(err) => {
-                    assert_eq!(err.to_string(), "Error while parsing input as hex number");
-                }
-            };
-            match parse_tag("0x001F-0x001P") {
-                Ok(_) => assert!(false),
-                Err(err) => {
-                    assert_eq!(err.to_string(), "Error while parsing input as hex number");
-                }
-            };
+            assert_eq!(
+                parse_tag("0x0001-0x0001-0x001").unwrap_err().to_string(),
+                "Error while parsing tags, has to be 0x____-0x____ but received 0x0001-0x0001-0x001"
+            );
+            assert_eq!(
+                parse_tag("0x001G-0x001").unwrap_err().to_string(),
+                "Error while parsing input as hex number"
+            );
+            assert_eq!(
+                parse_tag("0x001F-0x001P").unwrap_err().to_string(),
+                "Error while parsing input as hex number"
+            );
         }
     }
 }
@@ -1,4 +1,5 @@
-use crate::app::utils::{is_dcm_file, is_dcm_path, parse_date};
+use crate::app::utils::{is_dcm_file, is_zip_path, parse_date};
+use anonymizer_lib::types::{Pattern, ScriptRule, SexMapping};
 use anonymizer_lib::PatientSex;
 use std::path::Path;
 use std::str::FromStr;
@@ -8,7 +9,10 @@ pub type ValidatorType = Result<(), String>;
 pub fn validator_is_file_path(path: &str) -> ValidatorType {
     let path = Path::new(path);
     let file_exists = path.exists();
-    let parent_exists = path.parent().map(|item| item.exists());
+    // A bare file name has an empty parent, which is the working directory
+    let parent_exists = path
+        .parent()
+        .map(|item| item.as_os_str().is_empty() || item.exists());
 
     match (file_exists, parent_exists) {
         (_, None) => Err(String::from("Parent directory doesn't exists")),
@@ -17,31 +21,34 @@ pub fn validator_is_file_path(path: &str) -> ValidatorType {
     }
 }
 
-pub fn validator_is_dcm_path(path: &str) -> ValidatorType {
-    match validator_is_file_path(path) {
-        Ok(_) => (),
-        Err(e) => {
-            return Err(e);
-        }
-    }
-
-    if is_dcm_path(path) {
-        return Ok(());
-    }
-    Err(String::from("Must be a valid dicom file path"))
+/// A file path with any name, a zip archive, or a folder for batch runs. Whether it's a file or a
+/// folder follows from the input.
+pub fn validator_is_output_path(path: &str) -> ValidatorType {
+    validator_is_file_path(path)
 }
 
+/// An existing file that looks like DICOM, whatever its name.
 pub fn validator_is_dcm_file(path: &str) -> ValidatorType {
     match is_dcm_file(path) {
-        Ok(_) => Ok(()),
+        Ok(true) => Ok(()),
+        Ok(false) => Err(String::from("Not a DICOM file")),
         Err(e) => Err(e.to_string()),
     }
 }
 
+/// An existing zip archive.
+pub fn validator_is_zip_file(path: &str) -> ValidatorType {
+    match (Path::new(path).is_file(), is_zip_path(path)) {
+        (true, true) => Ok(()),
+        (false, _) => Err(String::from("File doesn't exists")),
+        (_, false) => Err(String::from("Must be a zip archive")),
+    }
+}
+
 pub fn validator_is_date(value: &str) -> ValidatorType {
     match parse_date(value) {
         Ok(_) => Ok(()),
-        Err(_) => Err(String::from("Must be in yyyy-mm-dd or yyyy-m-d format")),
+        Err(e) => Err(e.to_string()),
     }
 }
 
@@ -49,7 +56,32 @@ pub fn validator_is_sex(value: &str) -> ValidatorType {
     match PatientSex::from_str(value) {
         Ok(_) => Ok(()),
         Err(_) => Err(String::from(
-            "Only one of the values M,F,O are allowed (not case sensitive)",
+            "Only one of the values M,F,O or an empty value are allowed (not case sensitive)",
         )),
     }
 }
+
+pub fn validator_is_age_limit(value: &str) -> ValidatorType {
+    match value.parse::<u16>() {
+        Ok(years) if years < 999 => Ok(()),
+        _ => Err(String::from("Must be a number of years below 999")),
+    }
+}
+
+pub fn validator_is_pattern(value: &str) -> ValidatorType {
+    Pattern::from_str(value)
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+pub fn validator_is_script_rule(value: &str) -> ValidatorType {
+    ScriptRule::from_str(value)
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+pub fn validator_is_sex_map(value: &str) -> ValidatorType {
+    SexMapping::from_str(value)
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
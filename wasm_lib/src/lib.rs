@@ -1,48 +1,457 @@
-use std::io::Cursor;
-use anonymizer_lib::{Anonymizer, AnonymizerMeta};
-use dicom_object::from_reader;
-use js_sys::Uint8Array;
-use once_cell::sync::OnceCell;
+use anonymizer_lib::archive::anonymize_zip as anonymize_zip_archive;
+use anonymizer_lib::config::{ConfigFileVersions, FieldError};
+use anonymizer_lib::{Anonymizer, AnonymizerMeta, NonDicomPolicy, StreamingAnonymizer};
+use js_sys::{Array, Object, Reflect, Uint8Array, JSON};
+use serde::Serialize;
+use serde_json::Value;
+use std::fmt::Display;
+use std::str::FromStr;
 use wasm_bindgen::prelude::*;
 
-static ANONYMIZER: OnceCell<Anonymizer> = OnceCell::new();
+#[wasm_bindgen(typescript_custom_section)]
+const TYPES: &'static str = include_str!("types.d.ts");
 
-fn get_mut_anonymizer() -> Result<Anonymizer, JsValue> {
-    let mut any = Anonymizer::new().unwrap();
-    ANONYMIZER.get().unwrap().clone_into(&mut any);
-    Ok(any)
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(typescript_type = "AnonymizerConfig")]
+    pub type AnonymizerConfig;
+
+    #[wasm_bindgen(typescript_type = "ElementInfo[]")]
+    pub type ElementInfoList;
+
+    #[wasm_bindgen(typescript_type = "ElementChange[]")]
+    pub type ElementChangeList;
+
+    #[wasm_bindgen(typescript_type = "string[]")]
+    pub type StringList;
+
+    #[wasm_bindgen(typescript_type = "ZipResult")]
+    pub type ZipResult;
+
+    #[wasm_bindgen(typescript_type = "FilterAction | null")]
+    pub type OptionalFilterAction;
+}
+
+/// Plain JS objects for the given value, via JSON so maps become objects instead of `Map`s.
+fn to_js<T, R>(value: &T) -> Result<R, JsValue>
+where
+    T: Serialize,
+    R: JsCast,
+{
+    let json = serde_json::to_string(value).map_err(js_error)?;
+
+    Ok(JSON::parse(&json)?.unchecked_into())
+}
+
+fn js_error<E: Display>(e: E) -> JsValue {
+    js_sys::Error::new(&e.to_string()).into()
+}
+
+/// A JS `Error` listing every invalid setting, with an `errors` array of `{path, message}`.
+fn config_error(errors: Vec<FieldError>) -> JsValue {
+    let mut message = format!("Invalid config ({} errors):", errors.len());
+    let list = Array::new();
+
+    for error in errors {
+        message.push_str(&format!("\n  {}", error));
+
+        let item = Object::new();
+        let _ = Reflect::set(&item, &"path".into(), &error.key.into());
+        let _ = Reflect::set(&item, &"message".into(), &error.message.into());
+        list.push(&item);
+    }
+
+    let error = js_sys::Error::new(&message);
+    error.set_name("ConfigError");
+    let _ = Reflect::set(&error, &"errors".into(), &list);
+    error.into()
+}
+
+/// Accepts a versioned config file (it has a `config` section) or a bare `AnonymizerMeta`.
+/// New values that break the rules of their VR are errors unless the config coerces them.
+fn parse_config(config: &AnonymizerConfig) -> Result<AnonymizerMeta, JsValue> {
+    let json = JSON::stringify(config)?
+        .as_string()
+        .ok_or_else(|| config_error(vec![FieldError::new("", "Has to be an object")]))?;
+    let value: Value = serde_json::from_str(&json).map_err(js_error)?;
+
+    let (meta, errors) = match value.get("config") {
+        Some(_) => {
+            let cfv = ConfigFileVersions::from_value(value).map_err(config_error)?;
+            (cfv.meta(), cfv.value_errors())
+        }
+        None => {
+            let meta = AnonymizerMeta::from_value(value).map_err(config_error)?;
+            let errors = meta.value_errors();
+            (meta, errors)
+        }
+    };
+
+    match errors.is_empty() {
+        true => Ok(meta),
+        false => Err(config_error(errors)),
+    }
+}
+
+/// Anonymizes the bytes of one DICOM file and returns the bytes of the anonymized file.
+/// Nothing is kept between calls, so it can be called for any number of files.
+/// Throws a `ConfigError` if the config is invalid.
+#[wasm_bindgen]
+pub fn anonymize(data: &[u8], config: &AnonymizerConfig) -> Result<Uint8Array, JsValue> {
+    let mut file = DicomFile::new(data)?;
+    file.anonymize(config)?;
+
+    file.to_bytes()
+}
+
+/// Anonymizes every DICOM file of a zip archive, with the same replacement for a UID or patient
+/// ID in all of its files. Entries that aren't DICOM are left out, or copied with
+/// `non_dicom = "Copy"`. Throws a `ConfigError` if the config is invalid.
+#[wasm_bindgen]
+pub fn anonymize_zip(
+    data: &[u8],
+    config: &AnonymizerConfig,
+    non_dicom: Option<String>,
+) -> Result<ZipResult, JsValue> {
+    let meta = parse_config(config)?;
+    let non_dicom = match non_dicom {
+        Some(value) => NonDicomPolicy::from_str(&value).map_err(js_error)?,
+        None => NonDicomPolicy::default(),
+    };
+
+    let (data, report) = anonymize_zip_archive(data, &meta, non_dicom).map_err(js_error)?;
+
+    let result = Object::new();
+    Reflect::set(&result, &"data".into(), &Uint8Array::from(data.as_slice()))?;
+    Reflect::set(&result, &"report".into(), &to_js::<_, JsValue>(&report)?)?;
+    Ok(result.unchecked_into())
 }
 
+/// Every element of the file, so it can be reviewed before it leaves the browser.
 #[wasm_bindgen]
-pub fn init_anonymize(data: Uint8Array) -> Result<(), JsValue> {
-    let file = Cursor::new(data.to_vec());
+pub fn inspect(data: &[u8]) -> Result<ElementInfoList, JsValue> {
+    DicomFile::new(data)?.elements()
+}
 
-    let obj = from_reader(file).unwrap_throw();
-    let any = Anonymizer::from_object(obj).unwrap_throw();
-    ANONYMIZER.set(any).unwrap_throw();
+/// The changes `anonymize` would make to the file with the given config.
+/// Throws a `ConfigError` if the config is invalid.
+#[wasm_bindgen]
+pub fn preview(data: &[u8], config: &AnonymizerConfig) -> Result<ElementChangeList, JsValue> {
+    DicomFile::new(data)?.preview(config)
+}
 
-    Ok(())
+/// Handle to one DICOM file, for callers that want to keep a file around between calls.
+#[wasm_bindgen]
+pub struct DicomFile {
+    anonymizer: Anonymizer,
 }
 
 #[wasm_bindgen]
-pub fn anonymize(config: &JsValue) -> Result<(), JsValue> {
-    let config: AnonymizerMeta = config.into_serde().unwrap_throw();
+impl DicomFile {
+    #[wasm_bindgen(constructor)]
+    pub fn new(data: &[u8]) -> Result<DicomFile, JsValue> {
+        let anonymizer = Anonymizer::from_bytes(data).map_err(js_error)?;
+
+        Ok(DicomFile { anonymizer })
+    }
+
+    /// Throws a `ConfigError` if the config is invalid.
+    pub fn anonymize(&mut self, config: &AnonymizerConfig) -> Result<(), JsValue> {
+        self.anonymizer.meta(parse_config(config)?);
+        self.anonymizer.anonymize().map_err(js_error)
+    }
+
+    /// Problems found by the last `anonymize`, e.g. non-standard values.
+    pub fn warnings(&self) -> Result<StringList, JsValue> {
+        to_js(&self.anonymizer.warnings())
+    }
+
+    pub fn elements(&self) -> Result<ElementInfoList, JsValue> {
+        to_js(&self.anonymizer.elements().map_err(js_error)?)
+    }
+
+    /// The changes `anonymize` would make, the file itself stays unchanged.
+    /// Throws a `ConfigError` if the config is invalid.
+    pub fn preview(&self, config: &AnonymizerConfig) -> Result<ElementChangeList, JsValue> {
+        let mut anonymizer = self.anonymizer.clone();
+        anonymizer.meta(parse_config(config)?);
+
+        to_js(&anonymizer.preview().map_err(js_error)?)
+    }
 
-    let mut any = get_mut_anonymizer().unwrap_throw();
+    pub fn to_bytes(&self) -> Result<Uint8Array, JsValue> {
+        let data = self.anonymizer.to_bytes().map_err(js_error)?;
 
-    any.meta(config);
-    any.anonymize();
-    ANONYMIZER.set(any).unwrap_throw();
+        Ok(Uint8Array::from(data.as_slice()))
+    }
+}
 
-    Ok(())
+/// Anonymizes a file that is read in chunks, e.g. from `File.stream()` in a Web Worker.
+/// Only the header is kept in memory, the pixel data is returned as it comes in.
+#[wasm_bindgen]
+pub struct AnonymizerStream {
+    stream: StreamingAnonymizer,
+}
+
+#[wasm_bindgen]
+impl AnonymizerStream {
+    /// Throws a `ConfigError` if the config is invalid.
+    #[wasm_bindgen(constructor)]
+    pub fn new(config: &AnonymizerConfig) -> Result<AnonymizerStream, JsValue> {
+        let stream = StreamingAnonymizer::new(parse_config(config)?);
+
+        Ok(AnonymizerStream { stream })
+    }
+
+    /// The next chunk of the input, returns the output that is ready, which may be empty.
+    pub fn push(&mut self, chunk: &[u8]) -> Result<Uint8Array, JsValue> {
+        let data = self.stream.push(chunk).map_err(js_error)?;
+
+        Ok(Uint8Array::from(data.as_slice()))
+    }
+
+    /// Ends the input and returns the rest of the output.
+    pub fn finish(&mut self) -> Result<Uint8Array, JsValue> {
+        let data = self.stream.finish().map_err(js_error)?;
+
+        Ok(Uint8Array::from(data.as_slice()))
+    }
+
+    /// What the filters decided for the file, `null` until the header has been read.
+    /// A skipped file produces no output at all.
+    pub fn action(&self) -> Result<OptionalFilterAction, JsValue> {
+        to_js(&self.stream.action())
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::{
+        anonymize, anonymize_zip, inspect, preview, AnonymizerConfig, AnonymizerStream, DicomFile,
+    };
+    use anonymizer_lib::{Anonymizer, TagAction};
+    use dicom_core::{DataElement, PrimitiveValue, VR};
+    use dicom_dictionary_std::tags;
+    use dicom_object::{FileMetaTableBuilder, InMemDicomObject};
+    use js_sys::{Array, Reflect, Uint8Array, JSON};
+    use std::io::{Cursor, Read, Write};
+    use wasm_bindgen::{JsCast, JsValue};
     use wasm_bindgen_test::*;
+    use zip::write::FileOptions;
+    use zip::{ZipArchive, ZipWriter};
+
+    fn factory(name: &str) -> Vec<u8> {
+        let mut obj = InMemDicomObject::new_empty();
+        obj.put(DataElement::new(
+            tags::PATIENT_NAME,
+            VR::PN,
+            PrimitiveValue::from(name),
+        ));
+
+        let mut data = Vec::new();
+        obj.with_meta(
+            FileMetaTableBuilder::new()
+                .transfer_syntax("1.2.840.10008.1.2.1")
+                .media_storage_sop_class_uid("1.2.840.10008.5.1.4.1.1.2")
+                .media_storage_sop_instance_uid("1.2.3.4"),
+        )
+        .unwrap()
+        .write_all(&mut data)
+        .unwrap();
+        data
+    }
+
+    fn js(json: &str) -> AnonymizerConfig {
+        JSON::parse(json).unwrap().unchecked_into()
+    }
+
+    fn config() -> AnonymizerConfig {
+        let mut builder = Anonymizer::meta_builder();
+        builder.patient_name(TagAction::Change("Anonymous".to_string()));
+
+        js(&serde_json::to_string(&builder.build().unwrap()).unwrap())
+    }
+
+    fn patient_name(data: &[u8]) -> String {
+        let obj = dicom_object::OpenFileOptions::new()
+            .read_preamble(dicom_object::file::ReadPreamble::Always)
+            .from_reader(data)
+            .unwrap();
+        let name = obj.element(tags::PATIENT_NAME).unwrap().to_str().unwrap();
+        name.trim_end().to_string()
+    }
+
+    #[wasm_bindgen_test]
+    fn anonymizes_many_files() {
+        for name in ["Doe^John", "Doe^Jane"] {
+            let data = anonymize(&factory(name), &config()).unwrap().to_vec();
+
+            assert_eq!(patient_name(&data), "Anonymous");
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn handles_are_independent() {
+        let mut first = DicomFile::new(&factory("Doe^John")).unwrap();
+        let second = DicomFile::new(&factory("Doe^Jane")).unwrap();
+        first.anonymize(&config()).unwrap();
+
+        assert_eq!(
+            patient_name(&first.to_bytes().unwrap().to_vec()),
+            "Anonymous"
+        );
+        assert_eq!(
+            patient_name(&second.to_bytes().unwrap().to_vec()),
+            "Doe^Jane"
+        );
+    }
 
     #[wasm_bindgen_test]
-    fn pass() {
-        assert_eq!(1, 1);
+    fn accepts_versioned_config_files() {
+        let config = js(r#"{"version": "1.1", "config": {"patient_name": "Anonymous"}}"#);
+
+        let data = anonymize(&factory("Doe^John"), &config).unwrap().to_vec();
+        assert_eq!(patient_name(&data), "Anonymous");
+    }
+
+    #[wasm_bindgen_test]
+    fn invalid_configs_throw_with_field_paths() {
+        let config = js(
+            r#"{"version": "2.0", "config": {"patient_sex": {"Change": "X"}, "remove_tags": [{"group": "16", "element": 32}]}}"#,
+        );
+
+        let error = anonymize(&factory("Doe^John"), &config).unwrap_err();
+        let error = error.dyn_into::<js_sys::Error>().unwrap();
+        assert_eq!(error.name(), "ConfigError");
+
+        let errors: Array = Reflect::get(&error, &"errors".into()).unwrap().into();
+        let paths = errors
+            .iter()
+            .map(|item| {
+                Reflect::get(&item, &"path".into())
+                    .unwrap()
+                    .as_string()
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            vec!["config.patient_sex.Change", "config.remove_tags[0].group"]
+        );
+    }
+
+    #[wasm_bindgen_test]
+    fn names_that_break_their_vr_throw_unless_coerced() {
+        let name = "N".repeat(65);
+        let config = js(&format!(r#"{{"patient_name": {{"Change": "{}"}}}}"#, name));
+        let error = anonymize(&factory("Doe^John"), &config).unwrap_err();
+        let error = error.dyn_into::<js_sys::Error>().unwrap();
+        assert_eq!(error.name(), "ConfigError");
+
+        let config = js(&format!(
+            r#"{{"patient_name": {{"Change": "{}"}}, "value_strictness": "Coerce"}}"#,
+            name
+        ));
+        let data = anonymize(&factory("Doe^John"), &config).unwrap().to_vec();
+        assert_eq!(patient_name(&data), "N".repeat(64));
+    }
+
+    fn field(item: &JsValue, name: &str) -> JsValue {
+        Reflect::get(item, &name.into()).unwrap()
+    }
+
+    #[wasm_bindgen_test]
+    fn lists_the_elements() {
+        let elements: Array = inspect(&factory("Doe^John")).unwrap().unchecked_into();
+
+        assert_eq!(elements.length(), 1);
+        let element = elements.get(0);
+        assert_eq!(
+            field(&element, "keyword").as_string().unwrap(),
+            "PatientName"
+        );
+        assert_eq!(field(&element, "tag").as_string().unwrap(), "(0010,0010)");
+        assert_eq!(field(&element, "vr").as_string().unwrap(), "PN");
+        assert_eq!(field(&element, "value").as_string().unwrap(), "Doe^John");
+    }
+
+    #[wasm_bindgen_test]
+    fn previews_the_changes() {
+        let data = factory("Doe^John");
+        let changes: Array = preview(&data, &config()).unwrap().unchecked_into();
+
+        assert_eq!(changes.length(), 1);
+        let change = changes.get(0);
+        assert_eq!(field(&change, "kind").as_string().unwrap(), "Changed");
+        assert_eq!(field(&change, "before").as_string().unwrap(), "Doe^John");
+        assert_eq!(field(&change, "after").as_string().unwrap(), "Anonymous");
+
+        let file = DicomFile::new(&data).unwrap();
+        file.preview(&config()).unwrap();
+        assert_eq!(patient_name(&file.to_bytes().unwrap().to_vec()), "Doe^John");
+    }
+
+    #[wasm_bindgen_test]
+    fn invalid_files_throw() {
+        assert!(DicomFile::new(b"not a DICOM file").is_err());
+    }
+
+    #[wasm_bindgen_test]
+    fn streams_files_in_chunks() {
+        let data = factory("Doe^John");
+        let mut stream = AnonymizerStream::new(&config()).unwrap();
+
+        let mut output = Vec::new();
+        for chunk in data.chunks(64) {
+            output.extend(stream.push(chunk).unwrap().to_vec());
+        }
+        output.extend(stream.finish().unwrap().to_vec());
+
+        assert_eq!(patient_name(&output), "Anonymous");
+        assert_eq!(
+            stream.action().unwrap().as_string().as_deref(),
+            Some("Anonymize")
+        );
+    }
+
+    #[wasm_bindgen_test]
+    fn anonymizes_zip_archives() {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .start_file("study/1.dcm", FileOptions::default())
+            .unwrap();
+        writer.write_all(&factory("Doe^John")).unwrap();
+        writer
+            .start_file("notes.txt", FileOptions::default())
+            .unwrap();
+        writer.write_all(b"not a DICOM file").unwrap();
+        let data = writer.finish().unwrap().into_inner();
+
+        let result = anonymize_zip(&data, &config(), Some("copy".to_string())).unwrap();
+
+        let report = field(&result, "report");
+        assert_eq!(field(&report, "anonymized").as_f64(), Some(1.0));
+        assert_eq!(field(&report, "not_dicom").as_f64(), Some(1.0));
+
+        let data: Uint8Array = field(&result, "data").unchecked_into();
+        let mut archive = ZipArchive::new(Cursor::new(data.to_vec())).unwrap();
+        assert_eq!(archive.len(), 2);
+        let mut content = Vec::new();
+        archive
+            .by_name("study/1.dcm")
+            .unwrap()
+            .read_to_end(&mut content)
+            .unwrap();
+        assert_eq!(patient_name(&content), "Anonymous");
+    }
+
+    #[wasm_bindgen_test]
+    fn lists_warnings_of_the_last_run() {
+        let mut file = DicomFile::new(&factory("Doe^John")).unwrap();
+        file.anonymize(&js(r#"{"patient_sex": {"Change": "Empty"}}"#))
+            .unwrap();
+
+        let warnings: Array = file.warnings().unwrap().unchecked_into();
+        assert_eq!(warnings.length(), 0);
     }
 }
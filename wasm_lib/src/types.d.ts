@@ -0,0 +1,190 @@
+/** `"Keep"` leaves the element as it is, `"Remove"` deletes it, `{ Change: value }` replaces it. */
+export type TagAction<T> = "Keep" | "Remove" | { Change: T };
+
+/**
+ * A DICOM date `YYYY`, `YYYYMM` or `YYYYMMDD`, or a range like `"1980-1990"`. The object form
+ * `{ data: "2000-11-05 12:14:05 +01:00" }` of earlier versions is still read.
+ */
+export type DicomDate = string;
+
+/** `"Empty"` is a present but empty value, Patient Sex is a type 2 attribute. */
+export type PatientSex = "M" | "F" | "O" | "Empty";
+
+/**
+ * Replacements for the Patient Sex values found in files, applied when the sex is kept.
+ * Keys are compared without case, `"*"` matches every value without its own entry,
+ * e.g. `{ M: "M", F: "F", "*": "Empty" }`.
+ */
+export type SexMapping = Record<string, PatientSex>;
+
+/** How much of a kept birth date is written, `"Year"` gives `1980`, `"JanuaryFirst"` `19800101`. */
+export type BirthDateGeneralization = "Full" | "Year" | "JanuaryFirst";
+
+/** `"Recompute"` writes Patient Age from the original birth date and the study date. */
+export type PatientAgeAction = "Keep" | "Recompute";
+
+/** What happens to new values that don't fit their VR: `"Coerce"` cuts them to fit with a warning. */
+export type ValueStrictness = "Reject" | "Coerce";
+
+export type OutputTransferSyntax =
+  | "Keep"
+  | "ExplicitVrLittleEndian"
+  | "ImplicitVrLittleEndian"
+  | "DeflatedExplicitVrLittleEndian";
+
+/**
+ * Settings of a de-identification standard the other settings are applied on top of.
+ * `"HipaaSafeHarbor"` removes the 18 identifier kinds of HIPAA Safe Harbor, keeps only the year of
+ * the birth date and caps ages above 89.
+ */
+export type Preset = "HipaaSafeHarbor";
+
+/** A DICOM tag as numbers, e.g. `{ group: 0x0010, element: 0x0020 }` for Patient ID. */
+export interface CustomTag {
+  group: number;
+  element: number;
+}
+
+export type FilterAction = "Anonymize" | "Copy" | "Skip";
+
+/** Every condition that is set has to match, a rule without conditions matches every file. */
+export interface FilterRule {
+  action: FilterAction;
+  /** Modality (0008,0060) is one of these values */
+  modality?: string[];
+  /** SOP Class UID (0008,0016) is one of these values */
+  sop_class_uid?: string[];
+  /** Series Description (0008,103E) matches this regular expression */
+  series_description?: string;
+  /** Image Type (0008,0008) contains every one of these values */
+  image_type?: string[];
+}
+
+/** Computes the new value of an attribute with a Rhai script file. */
+export interface ScriptRule {
+  /** Keyword of the attribute, e.g. `"PatientID"` */
+  attribute: string;
+  file: string;
+}
+
+/** Every setting is optional and defaults to `"Keep"` or an empty list. */
+export interface AnonymizerMeta {
+  preset?: Preset;
+  patient_name?: TagAction<string>;
+  patient_birth_date?: TagAction<DicomDate>;
+  generalize_birth_date?: BirthDateGeneralization;
+  patient_age?: PatientAgeAction;
+  /** Older patients get this age + 1 and a matching birth date, HIPAA Safe Harbor uses 89 */
+  max_patient_age?: number;
+  remove_tags?: CustomTag[];
+  patient_sex?: TagAction<PatientSex>;
+  patient_sex_map?: SexMapping;
+  /** `"Reject"` (default) throws a `ConfigError` for new values that don't fit their VR */
+  value_strictness?: ValueStrictness;
+  /** Redact the name, IDs and birth date of the patient from LO, LT, ST and UT values */
+  scrub_text?: boolean;
+  /** Regular expressions redacted from the same values */
+  scrub_patterns?: string[];
+  filters?: FilterRule[];
+  /** Scripts are read from the file system, in the browser `anonymize` throws an `Error` */
+  scripts?: ScriptRule[];
+  /** Encapsulated (compressed) pixel data can't be converted, streams keep their transfer syntax */
+  transfer_syntax?: OutputTransferSyntax;
+  /** Secret key of the replaced UIDs and patient IDs of `anonymize_zip` and of `hash()` in scripts */
+  pseudonym_salt?: string;
+}
+
+/** Settings of the version 1 config files, in the notation of the CLI flags. */
+export interface ConfigFileV1 {
+  /** e.g. `"hipaa-safe-harbor"` */
+  preset?: string;
+  patient_name?: string;
+  /** `yyyy-mm-dd` or a DICOM date like `"19800101"`, `"198001"` or `"1980"` */
+  patient_birth_day?: string;
+  /** `"full"`, `"year"` or `"january-first"` */
+  generalize_birth_date?: string;
+  /** `"keep"` or `"recompute"` */
+  patient_age?: string;
+  max_patient_age?: number;
+  /** `"M"`, `"F"`, `"O"` or `""`, not case sensitive */
+  patient_sex?: string;
+  /** e.g. `"M=M,F=F,*="` */
+  patient_sex_map?: string;
+  /** `"reject"` or `"coerce"` */
+  value_strictness?: string;
+  /** `0x____-0x____`, e.g. `"0x0010-0x0020"` */
+  remove_tags?: string[];
+  scrub_text?: boolean;
+  scrub_patterns?: string[];
+  /** e.g. `"skip:modality=SR,PR"` */
+  filters?: string[];
+  /** e.g. `"PatientID=rules/patient_id.rhai"` */
+  scripts?: string[];
+  /** `"keep"`, `"explicit"`, `"implicit"` or `"deflated"` */
+  transfer_syntax?: string;
+}
+
+/** A config file as used by the CLI, a missing version is read as `"1.0"`. */
+export type ConfigFile =
+  | { version?: "1.0" | "1.1"; config: ConfigFileV1 }
+  | { version: "2.0"; config: AnonymizerMeta };
+
+/** Either a versioned config file or a bare `AnonymizerMeta`. */
+export type AnonymizerConfig = ConfigFile | AnonymizerMeta;
+
+export interface ConfigFieldError {
+  /** Path of the invalid setting, e.g. `config.remove_tags[1].group` */
+  path: string;
+  message: string;
+}
+
+/** Thrown for an invalid config, `errors` lists every invalid setting. */
+export interface ConfigError extends Error {
+  errors: ConfigFieldError[];
+}
+
+/** One element of a dataset, elements inside sequences are listed after their sequence. */
+export interface ElementInfo {
+  /** e.g. `"(0010,0010)"` */
+  tag: string;
+  /** e.g. `"PatientName"`, empty for private and unknown tags */
+  keyword: string;
+  vr: string;
+  /** The value as text, binary values are summarized as `"<N bytes>"` */
+  value: string;
+  /** Unique position in the dataset, e.g. `"ReferencedImageSequence[0].ReferencedSOPInstanceUID"` */
+  path: string;
+}
+
+export type ChangeKind = "Changed" | "Removed" | "Added";
+
+/** Difference of one element between the original and the anonymized dataset. */
+export interface ElementChange {
+  tag: string;
+  keyword: string;
+  path: string;
+  kind: ChangeKind;
+  before: string | null;
+  after: string | null;
+}
+
+/** What happens to the entries of a zip archive that aren't DICOM files. */
+export type NonDicomPolicy = "Skip" | "Copy";
+
+/** How many files ended up with which decision. */
+export interface BatchReport {
+  anonymized: number;
+  copied: number;
+  skipped: number;
+  not_dicom: number;
+  /** DICOM entries that couldn't be read, they are left out of the archive */
+  failed: number;
+  /** Problems found in the files, e.g. non-standard values, prefixed with the entry name */
+  warnings: string[];
+}
+
+export interface ZipResult {
+  /** The anonymized zip archive */
+  data: Uint8Array;
+  report: BatchReport;
+}